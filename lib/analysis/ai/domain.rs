@@ -343,7 +343,15 @@ impl<M, V> State<M, V> where M: Memory<V>, V: Value {
             il::Expression::Sext(bits, ref rhs) =>
                 Expression::sext(bits, self.symbolize(rhs)),
             il::Expression::Trun(bits, ref rhs) =>
-                Expression::trun(bits, self.symbolize(rhs))
+                Expression::trun(bits, self.symbolize(rhs)),
+            il::Expression::Neg(ref rhs) =>
+                Expression::sub(Expression::value(V::constant(il::const_(0, rhs.bits()))),
+                                self.symbolize(rhs)),
+            // Abstract domains do not count bits
+            il::Expression::Popcnt(_) |
+            il::Expression::Clz(_) |
+            il::Expression::Ctz(_) =>
+                Expression::value(V::top(expression.bits()))
         }
     }
 }
@@ -60,10 +60,28 @@ impl<'a, D, M, V> fixed_point::FixedPointAnalysis<'a, domain::State<M, V>> for I
                         let target = self.domain.eval(&state.symbolize(target))?;
                         self.domain.brc(&target, state)?
                     },
+                    il::Operation::Call { ref target, ref result } => {
+                        let target = self.domain.eval(&state.symbolize(target))?;
+                        let mut state = self.domain.brc(&target, state)?;
+                        if let Some(ref result) = *result {
+                            state.set_variable(result.clone(), V::top(result.bits()));
+                        }
+                        state
+                    },
                     il::Operation::Raise { ref expr } => {
                         let expr = self.domain.eval(&state.symbolize(expr))?;
                         self.domain.raise(&expr, state)?
-                    }
+                    },
+                    // The semantics of an intrinsic are unknown, so everything
+                    // it may write becomes top.
+                    il::Operation::Intrinsic { ref results, .. } => {
+                        state.memory_mut().top()?;
+                        for result in results {
+                            state.set_variable(result.clone(), V::top(result.bits()));
+                        }
+                        state
+                    },
+                    il::Operation::Nop => state
                 }
             },
             il::RefFunctionLocation::Edge(_) |
@@ -0,0 +1,195 @@
+//! Determines which arguments a function uses.
+
+use analysis::calling_convention::CallingConvention;
+use analysis::fixed_point;
+use error::*;
+use il;
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::BTreeSet;
+
+
+/// Compute the indices of the arguments, as given by the calling convention,
+/// which the function reads before writing.
+///
+/// Only arguments passed in registers are detected. The greatest index
+/// returned, plus one, is a first approximation of the function's arity.
+pub fn used_arguments(function: &il::Function, calling_convention: &CallingConvention)
+-> Result<Vec<usize>> {
+    let argument_registers = calling_convention.argument_registers();
+
+    let read = read_before_written(function,
+                                   argument_registers.iter().cloned().collect())?;
+
+    Ok(argument_registers.iter()
+                         .enumerate()
+                         .filter(|&(_, register)| read.contains(register))
+                         .map(|(index, _)| index)
+                         .collect())
+}
+
+
+/// Compute which of the given registers the function may read before writing,
+/// which are those whose value at function entry may be read.
+pub(crate) fn read_before_written(function: &il::Function, registers: BTreeSet<il::Scalar>)
+-> Result<BTreeSet<il::Scalar>> {
+    let analysis = ArgumentsAnalysis { argument_registers: registers };
+    let states = fixed_point::fixed_point_forward(analysis, function)?;
+
+    let mut read = BTreeSet::new();
+    for state in states.values() {
+        read.extend(state.read.iter().cloned());
+    }
+
+    Ok(read)
+}
+
+
+/// The argument registers which may not have been written yet, and those
+/// which have been read while not yet written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct ArgumentsState {
+    unwritten: BTreeSet<il::Scalar>,
+    read: BTreeSet<il::Scalar>
+}
+
+
+impl ArgumentsState {
+    fn join(mut self, other: &ArgumentsState) -> ArgumentsState {
+        self.unwritten.extend(other.unwritten.iter().cloned());
+        self.read.extend(other.read.iter().cloned());
+        self
+    }
+}
+
+
+fn subset_cmp(lhs: &BTreeSet<il::Scalar>, rhs: &BTreeSet<il::Scalar>) -> Option<Ordering> {
+    if lhs == rhs {
+        Some(Ordering::Equal)
+    }
+    else if lhs.is_subset(rhs) {
+        Some(Ordering::Less)
+    }
+    else if lhs.is_superset(rhs) {
+        Some(Ordering::Greater)
+    }
+    else {
+        None
+    }
+}
+
+
+impl PartialOrd for ArgumentsState {
+    fn partial_cmp(&self, other: &ArgumentsState) -> Option<Ordering> {
+        match (subset_cmp(&self.unwritten, &other.unwritten),
+               subset_cmp(&self.read, &other.read)) {
+            (Some(Ordering::Equal), ordering) |
+            (ordering, Some(Ordering::Equal)) => ordering,
+            (Some(lhs), Some(rhs)) => if lhs == rhs { Some(lhs) } else { None },
+            _ => None
+        }
+    }
+}
+
+
+struct ArgumentsAnalysis {
+    argument_registers: BTreeSet<il::Scalar>
+}
+
+
+impl<'f> fixed_point::FixedPointAnalysis<'f, ArgumentsState> for ArgumentsAnalysis {
+    fn trans(&self, location: il::RefProgramLocation<'f>, state: Option<ArgumentsState>)
+        -> Result<ArgumentsState> {
+
+        let mut state = match state {
+            Some(state) => state,
+            None => ArgumentsState {
+                unwritten: self.argument_registers.clone(),
+                read: BTreeSet::new()
+            }
+        };
+
+        let (scalars_read, scalars_written) = match *location.function_location() {
+            il::RefFunctionLocation::Instruction(_, ref instruction) =>
+                (instruction.scalars_read(), instruction.scalars_written()),
+            il::RefFunctionLocation::Edge(ref edge) => match *edge.condition() {
+                Some(ref condition) => (condition.scalars(), Vec::new()),
+                None => (Vec::new(), Vec::new())
+            },
+            il::RefFunctionLocation::EmptyBlock(_) => (Vec::new(), Vec::new())
+        };
+
+        for scalar in scalars_read {
+            if state.unwritten.contains(scalar) {
+                state.read.insert(scalar.clone());
+            }
+        }
+        for scalar in scalars_written {
+            state.unwritten.remove(scalar);
+        }
+
+        Ok(state)
+    }
+
+    fn join(&self, state0: ArgumentsState, state1: &ArgumentsState)
+        -> Result<ArgumentsState> {
+
+        Ok(state0.join(state1))
+    }
+}
+
+
+#[test]
+fn used_arguments_test() {
+    use analysis::calling_convention::CallingConventionType;
+
+    /*
+    $a1 = 7
+    $v0 = $a0 + $a1
+    if $a2 == 0 {
+        $a3 = 1
+    }
+    $v0 = $v0 + $a3
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$a1", 32), il::expr_const(7, 32));
+        block.assign(il::scalar("$v0", 32),
+                     il::Expression::add(il::expr_scalar("$a0", 32),
+                                         il::expr_scalar("$a1", 32)).unwrap());
+        block.index()
+    };
+
+    let then_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$a3", 32), il::expr_const(1, 32));
+        block.index()
+    };
+
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$v0", 32),
+                     il::Expression::add(il::expr_scalar("$v0", 32),
+                                         il::expr_scalar("$a3", 32)).unwrap());
+        block.index()
+    };
+
+    let condition = il::Expression::cmpeq(
+        il::expr_scalar("$a2", 32),
+        il::expr_const(0, 32)
+    ).unwrap();
+
+    control_flow_graph.conditional_edge(head_index, then_index, condition.clone()).unwrap();
+    control_flow_graph.conditional_edge(head_index, tail_index,
+        il::Expression::cmpeq(condition, il::expr_const(0, 1)).unwrap()
+    ).unwrap();
+    control_flow_graph.unconditional_edge(then_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let calling_convention = CallingConvention::new(CallingConventionType::MipsSystemV);
+
+    // $a3 is written on only one path to its read
+    assert_eq!(used_arguments(&function, &calling_convention).unwrap(), vec![0, 2, 3]);
+}
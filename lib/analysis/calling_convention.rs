@@ -60,13 +60,21 @@ pub struct CallingConvention {
     return_address_type: ReturnAddressType,
 
     /// The register the returned value is given in.
-    return_register: il::Scalar
+    return_register: il::Scalar,
+
+    /// The registers holding the high and low halves of a returned value
+    /// twice the width of a register, if one can be returned.
+    return_register_high: Option<il::Scalar>,
+    return_register_low: Option<il::Scalar>,
+
+    /// The stack pointer.
+    stack_pointer: il::Scalar
 }
 
 /*
     Mips System V:
         $16-$23 and $29-$31 are saved. This is $s0-S8, $sp and $ra.
-        Result is in $v0.
+        Result is in $v0, with 64-bit results in $v0 and $v1 in memory order.
         Everything else is trashed.
 */
 
@@ -78,6 +86,12 @@ impl CallingConvention {
         match typ {
             CallingConventionType::MipsSystemV |
             CallingConventionType::MipselSystemV => {
+                let (return_register_high, return_register_low) = match typ {
+                    CallingConventionType::MipselSystemV =>
+                        (il::scalar("$v1", 32), il::scalar("$v0", 32)),
+                    _ => (il::scalar("$v0", 32), il::scalar("$v1", 32))
+                };
+
                 let argument_registers = vec![
                     il::scalar("$a0", 32), il::scalar("$a1", 32),
                     il::scalar("$a2", 32), il::scalar("$a3", 32)
@@ -124,7 +138,10 @@ impl CallingConvention {
                     stack_argument_offset: 0,
                     stack_argument_length: 4,
                     return_address_type: return_type,
-                    return_register: il::scalar("$v0", 32)
+                    return_register: il::scalar("$v0", 32),
+                    return_register_high: Some(return_register_high),
+                    return_register_low: Some(return_register_low),
+                    stack_pointer: il::scalar("$sp", 32)
                 }
             },
             CallingConventionType::Cdecl => {
@@ -149,7 +166,10 @@ impl CallingConvention {
                     stack_argument_offset: 4,
                     stack_argument_length: 4,
                     return_address_type: return_type,
-                    return_register: il::scalar("eax", 32)
+                    return_register: il::scalar("eax", 32),
+                    return_register_high: Some(il::scalar("edx", 32)),
+                    return_register_low: Some(il::scalar("eax", 32)),
+                    stack_pointer: il::scalar("esp", 32)
                 }
             },
         }
@@ -196,6 +216,26 @@ impl CallingConvention {
         &self.return_register
     }
 
+    /// The register the high half of a returned value twice the width of a
+    /// register is given in, if one can be returned.
+    pub fn return_register_high(&self) -> Option<&il::Scalar> {
+        self.return_register_high.as_ref()
+    }
+
+    /// The registers a returned value twice the width of a register is given
+    /// in, as the high and low halves, if one can be returned.
+    pub fn return_value_64(&self) -> Option<(il::Scalar, il::Scalar)> {
+        match (&self.return_register_high, &self.return_register_low) {
+            (&Some(ref high), &Some(ref low)) => Some((high.clone(), low.clone())),
+            _ => None
+        }
+    }
+
+    /// The stack pointer.
+    pub fn stack_pointer(&self) -> &il::Scalar {
+        &self.stack_pointer
+    }
+
     /// Get the type for the given argument, starting with 0 index.
     pub fn argument_type(&self, argument_number: usize) -> ArgumentType {
         if argument_number >= self.argument_registers.len() {
@@ -208,6 +248,17 @@ impl CallingConvention {
         }
     }
 
+    /// Get the type for the given argument to a variadic function, starting
+    /// with 0 index and counting the fixed arguments.
+    ///
+    /// Variadic arguments continue the sequence of fixed arguments, as given
+    /// by `argument_type`, so arguments past the registers are on the stack.
+    /// Conventions which pass some variadic arguments in a separate area, such
+    /// as floating point arguments on System V x86-64, are not modelled.
+    pub fn variadic_argument_type(&self, argument_number: usize) -> ArgumentType {
+        self.argument_type(argument_number)
+    }
+
     /// Is the given register preserved.
     pub fn is_preserved(&self, scalar: &il::Scalar) -> PartialBoolean {
         if self.preserved_registers.contains(scalar) {
@@ -233,4 +284,41 @@ impl CallingConvention {
             PartialBoolean::Unknown
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn return_value_64() {
+    let cdecl = CallingConvention::new(CallingConventionType::Cdecl);
+    assert_eq!(cdecl.return_register_high(), Some(&il::scalar("edx", 32)));
+    assert_eq!(cdecl.return_value_64(),
+               Some((il::scalar("edx", 32), il::scalar("eax", 32))));
+
+    let mips = CallingConvention::new(CallingConventionType::MipsSystemV);
+    assert_eq!(mips.return_register_high(), Some(&il::scalar("$v0", 32)));
+    assert_eq!(mips.return_value_64(),
+               Some((il::scalar("$v0", 32), il::scalar("$v1", 32))));
+
+    let mipsel = CallingConvention::new(CallingConventionType::MipselSystemV);
+    assert_eq!(mipsel.return_value_64(),
+               Some((il::scalar("$v1", 32), il::scalar("$v0", 32))));
+}
+
+
+#[test]
+fn variadic_argument_type() {
+    let cdecl = CallingConvention::new(CallingConventionType::Cdecl);
+    match cdecl.variadic_argument_type(9) {
+        ArgumentType::Stack(offset) => assert_eq!(offset, 40),
+        ArgumentType::Register(_) => panic!("expected a stack argument")
+    }
+
+    let mips = CallingConvention::new(CallingConventionType::MipsSystemV);
+    match mips.variadic_argument_type(1) {
+        ArgumentType::Register(register) => assert_eq!(register, il::scalar("$a1", 32)),
+        ArgumentType::Stack(_) => panic!("expected a register argument")
+    }
+    match mips.variadic_argument_type(9) {
+        ArgumentType::Stack(offset) => assert_eq!(offset, 20),
+        ArgumentType::Register(_) => panic!("expected a stack argument")
+    }
+}
@@ -0,0 +1,344 @@
+//! Determines which registers a function writes, and which it restores.
+
+use analysis::arguments::read_before_written;
+use analysis::calling_convention::CallingConvention;
+use analysis::fixed_point;
+use analysis::stack_pointer;
+use error::*;
+use il;
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::{BTreeMap, BTreeSet};
+use types::PartialBoolean;
+
+
+/// Get every scalar written anywhere in the function.
+pub fn clobbered_registers(function: &il::Function) -> BTreeSet<il::Scalar> {
+    function.instructions()
+            .into_iter()
+            .flat_map(|(_, instruction)| instruction.scalars_written())
+            .cloned()
+            .collect()
+}
+
+
+/// Get the registers of the calling convention which hold their value from
+/// function entry at every exit of the function.
+///
+/// A register is restored when it is never written, when it is copied back
+/// from another register or from a stack slot holding its entry value, or,
+/// for the stack pointer, when its offset at exit is 0. Calls are assumed to
+/// write every register the calling convention trashes.
+pub fn restored_registers(function: &il::Function, calling_convention: &CallingConvention)
+-> Result<BTreeSet<il::Scalar>> {
+    let registers = calling_convention.preserved_registers()
+                                      .iter()
+                                      .chain(calling_convention.trashed_registers().iter())
+                                      .cloned()
+                                      .collect::<BTreeSet<il::Scalar>>();
+
+    let stack_pointer = calling_convention.stack_pointer().clone();
+    let offsets = stack_pointer::stack_offsets(function, &stack_pointer)?;
+
+    let analysis = RestoreAnalysis {
+        calling_convention,
+        offsets: &offsets,
+        registers: &registers
+    };
+    let states = fixed_point::fixed_point_forward(analysis, function)?;
+
+    let mut restored = registers.clone();
+    for (location, state) in &states {
+        if !location.forward()?.is_empty() {
+            continue;
+        }
+        let offset = offsets.get(&il::ProgramLocation::from(location.clone()))
+                            .cloned()
+                            .unwrap_or(None);
+        restored.retain(|register|
+            if *register == stack_pointer {
+                offset == Some(0)
+            }
+            else {
+                state.holds.get(&Place::Register(register.clone())) == Some(register)
+            });
+    }
+
+    Ok(restored)
+}
+
+
+/// Determine whether the function preserves the registers the calling
+/// convention requires to be preserved.
+///
+/// This is `True` when every preserved register the function writes is
+/// restored, as given by `restored_registers`, and `False` when the function
+/// writes a preserved register without ever reading the value it held at
+/// entry, so the value cannot be restored. Otherwise this is `Unknown`.
+pub fn conforms_to(function: &il::Function, calling_convention: &CallingConvention)
+-> PartialBoolean {
+    let restored = match restored_registers(function, calling_convention) {
+        Ok(restored) => restored,
+        Err(_) => return PartialBoolean::Unknown
+    };
+
+    let unrestored = clobbered_registers(function)
+        .into_iter()
+        .filter(|scalar| calling_convention.is_preserved(scalar).is_true())
+        .filter(|scalar| !restored.contains(scalar))
+        .collect::<BTreeSet<il::Scalar>>();
+    if unrestored.is_empty() {
+        return PartialBoolean::True;
+    }
+
+    match read_before_written(function, unrestored.clone()) {
+        Ok(ref read) if read.len() < unrestored.len() => PartialBoolean::False,
+        _ => PartialBoolean::Unknown
+    }
+}
+
+
+/// A place which may hold the value of a register from function entry.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Place {
+    Register(il::Scalar),
+    /// A stack slot, by its offset from the stack pointer at function entry,
+    /// and its size in bits.
+    Stack(i64, usize)
+}
+
+
+/// Each place known to hold the value of a register from function entry, and
+/// that register.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct RestoreState {
+    holds: BTreeMap<Place, il::Scalar>
+}
+
+
+impl RestoreState {
+    fn join(mut self, other: &RestoreState) -> RestoreState {
+        self.holds.retain(|place, register| other.holds.get(place) == Some(register));
+        self
+    }
+
+    fn is_subset(&self, other: &RestoreState) -> bool {
+        self.holds
+            .iter()
+            .all(|(place, register)| other.holds.get(place) == Some(register))
+    }
+}
+
+
+/// Knowing less about where entry values are held is greater.
+impl PartialOrd for RestoreState {
+    fn partial_cmp(&self, other: &RestoreState) -> Option<Ordering> {
+        match (self.is_subset(other), other.is_subset(self)) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Greater),
+            (false, true) => Some(Ordering::Less),
+            (false, false) => None
+        }
+    }
+}
+
+
+struct RestoreAnalysis<'a> {
+    calling_convention: &'a CallingConvention,
+    offsets: &'a BTreeMap<il::ProgramLocation, Option<i64>>,
+    registers: &'a BTreeSet<il::Scalar>
+}
+
+
+impl<'a> RestoreAnalysis<'a> {
+    /// Get the offset of the stack pointer from its value at function entry
+    /// before this location.
+    fn offset_before(&self, location: &il::RefProgramLocation) -> Result<Option<i64>> {
+        let predecessors = location.backward()?;
+        if predecessors.is_empty() {
+            return Ok(Some(0));
+        }
+
+        let mut offsets = predecessors.into_iter().map(|predecessor|
+            self.offsets.get(&il::ProgramLocation::from(predecessor)).cloned().unwrap_or(None));
+        let offset = offsets.next().unwrap_or(None);
+        Ok(if offsets.all(|other| other == offset) { offset } else { None })
+    }
+}
+
+
+impl<'a, 'f> fixed_point::FixedPointAnalysis<'f, RestoreState> for RestoreAnalysis<'a> {
+    fn trans(&self, location: il::RefProgramLocation<'f>, state: Option<RestoreState>)
+        -> Result<RestoreState> {
+
+        let mut state = match state {
+            Some(state) => state,
+            None => RestoreState {
+                holds: self.registers
+                           .iter()
+                           .map(|register| (Place::Register(register.clone()), register.clone()))
+                           .collect()
+            }
+        };
+
+        let instruction = match location.instruction() {
+            Some(instruction) => instruction,
+            None => return Ok(state)
+        };
+
+        let offset = self.offset_before(&location)?;
+        let address = |index: &il::Expression|
+            stack_pointer::adjustment(self.calling_convention.stack_pointer(), index)
+                .and_then(|adjustment| offset.and_then(|offset| offset.checked_add(adjustment)));
+
+        // The register whose entry value is written, if it is known.
+        let held = match *instruction.operation() {
+            il::Operation::Assign { ref src, .. } |
+            il::Operation::Store { ref src, .. } => match *src {
+                il::Expression::Scalar(ref scalar) =>
+                    state.holds.get(&Place::Register(scalar.clone())).cloned(),
+                _ => None
+            },
+            il::Operation::Load { ref dst, ref index } =>
+                address(index).and_then(|address|
+                    state.holds.get(&Place::Stack(address, dst.bits())).cloned()),
+            _ => None
+        };
+
+        for scalar in instruction.scalars_written() {
+            state.holds.remove(&Place::Register(scalar.clone()));
+        }
+
+        match *instruction.operation() {
+            il::Operation::Assign { ref dst, .. } |
+            il::Operation::Load { ref dst, .. } => if let Some(held) = held {
+                state.holds.insert(Place::Register(dst.clone()), held);
+            },
+            il::Operation::Store { ref index, ref src } => match address(index) {
+                Some(address) => {
+                    let end = address.saturating_add((src.bits() / 8) as i64);
+                    state.holds.retain(|place, _| match *place {
+                        Place::Stack(offset, bits) =>
+                            offset >= end || offset.saturating_add((bits / 8) as i64) <= address,
+                        Place::Register(_) => true
+                    });
+                    if let Some(held) = held {
+                        state.holds.insert(Place::Stack(address, src.bits()), held);
+                    }
+                },
+                // A store to an unknown address may overwrite any stack slot.
+                None => state.holds.retain(|place, _| match *place {
+                    Place::Stack(_, _) => false,
+                    Place::Register(_) => true
+                })
+            },
+            il::Operation::Call { .. } => {
+                let calling_convention = self.calling_convention;
+                state.holds.retain(|place, _| match *place {
+                    Place::Register(ref scalar) => !calling_convention.is_trashed(scalar).is_true(),
+                    Place::Stack(_, _) => true
+                });
+            },
+            _ => {}
+        }
+
+        Ok(state)
+    }
+
+    fn join(&self, state0: RestoreState, state1: &RestoreState) -> Result<RestoreState> {
+        Ok(state0.join(state1))
+    }
+}
+
+
+#[test]
+fn clobbered_registers_test() {
+    use analysis::calling_convention::CallingConventionType;
+
+    let calling_convention = CallingConvention::new(CallingConventionType::MipsSystemV);
+
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$t0", 32), il::expr_scalar("$a0", 32));
+        block.store(il::expr_scalar("$sp", 32), il::expr_scalar("$t0", 32));
+        block.index()
+    };
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let clobbered = clobbered_registers(&function);
+    assert_eq!(clobbered.into_iter().collect::<Vec<il::Scalar>>(), vec![il::scalar("$t0", 32)]);
+    assert_eq!(conforms_to(&function, &calling_convention), PartialBoolean::True);
+
+    let mut function = function;
+    function.control_flow_graph_mut()
+            .block_mut(index)
+            .unwrap()
+            .assign(il::scalar("$s0", 32), il::expr_const(0, 32));
+    // The value of $s0 at entry is never read, so it cannot be restored
+    assert_eq!(conforms_to(&function, &calling_convention), PartialBoolean::False);
+}
+
+
+#[test]
+fn restored_registers_test() {
+    use analysis::calling_convention::CallingConventionType;
+
+    let calling_convention = CallingConvention::new(CallingConventionType::MipsSystemV);
+
+    let slot = || il::Expression::add(il::expr_scalar("$sp", 32),
+                                      il::expr_const(4, 32)).unwrap();
+
+    /*
+    $sp = $sp - 8
+    [$sp + 4] = $s0
+    $s0 = 0
+    call $t9
+    $s0 = [$sp + 4]
+    $sp = $sp + 8
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$sp", 32),
+                     il::Expression::sub(il::expr_scalar("$sp", 32),
+                                         il::expr_const(8, 32)).unwrap());
+        block.store(slot(), il::expr_scalar("$s0", 32));
+        block.assign(il::scalar("$s0", 32), il::expr_const(0, 32));
+        block.call(il::expr_scalar("$t9", 32), None);
+        block.load(il::scalar("$s0", 32), slot());
+        block.assign(il::scalar("$sp", 32),
+                     il::Expression::add(il::expr_scalar("$sp", 32),
+                                         il::expr_const(8, 32)).unwrap());
+        block.index()
+    };
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let restored = restored_registers(&function, &calling_convention).unwrap();
+    assert!(restored.contains(&il::scalar("$s0", 32)));
+    assert!(restored.contains(&il::scalar("$sp", 32)));
+    assert!(restored.contains(&il::scalar("$s1", 32)));
+    // The call trashes $t9
+    assert!(!restored.contains(&il::scalar("$t9", 32)));
+    assert_eq!(conforms_to(&function, &calling_convention), PartialBoolean::True);
+
+    // $s0 = $s0 + 1 reads the value of $s0 at entry, but does not restore it
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$s0", 32),
+                     il::Expression::add(il::expr_scalar("$s0", 32),
+                                         il::expr_const(1, 32)).unwrap());
+        block.index()
+    };
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let restored = restored_registers(&function, &calling_convention).unwrap();
+    assert!(!restored.contains(&il::scalar("$s0", 32)));
+    assert_eq!(conforms_to(&function, &calling_convention), PartialBoolean::Unknown);
+}
@@ -0,0 +1,130 @@
+use error::*;
+use il;
+use std::collections::BTreeMap;
+
+
+/// Propagate copies through the given function.
+///
+/// A copy is an `Assign` whose source is a single scalar, such as `t1 = a`.
+/// Subsequent reads of the destination in the same block are replaced with
+/// the source, until either the source or the destination is written again.
+/// Copies are not propagated across block boundaries, or into the conditions
+/// of edges.
+pub fn copy_propagation(function: &il::Function) -> Result<il::Function> {
+    let mut function = function.clone();
+
+    for block in function.blocks_mut() {
+        // destination of a copy -> the scalar it holds a copy of
+        let mut copies: BTreeMap<il::Scalar, il::Scalar> = BTreeMap::new();
+
+        for instruction in block.instructions_mut() {
+            for scalar in instruction.scalars_read_mut() {
+                if let Some(source) = copies.get(scalar) {
+                    *scalar = source.clone();
+                }
+            }
+
+            let written = instruction.scalars_written()
+                                     .into_iter()
+                                     .cloned()
+                                     .collect::<Vec<il::Scalar>>();
+            if written.is_empty() {
+                continue;
+            }
+
+            copies = copies.into_iter()
+                           .filter(|&(ref dst, ref src)| {
+                               !written.contains(dst) && !written.contains(src)
+                           })
+                           .collect();
+
+            if let il::Operation::Assign { ref dst, ref src } = *instruction.operation() {
+                if let il::Expression::Scalar(ref src) = *src {
+                    if src != dst {
+                        copies.insert(dst.clone(), src.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(function)
+}
+
+
+#[test]
+fn copy_propagation_chain() {
+    /*
+    t1 = a
+    t2 = t1
+    t3 = t2
+    [t3] = t3 + 1
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+
+        block.assign(il::scalar("t1", 32), il::expr_scalar("a", 32));
+        block.assign(il::scalar("t2", 32), il::expr_scalar("t1", 32));
+        block.assign(il::scalar("t3", 32), il::expr_scalar("t2", 32));
+        block.store(il::expr_scalar("t3", 32),
+                    il::Expression::add(il::expr_scalar("t3", 32),
+                                        il::expr_const(1, 32)).unwrap());
+
+        block.index()
+    };
+
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let function = copy_propagation(&function).unwrap();
+
+    let block = function.block(index).unwrap();
+    for instruction in block.instructions() {
+        for scalar in instruction.scalars_read() {
+            assert_eq!(*scalar, il::scalar("a", 32));
+        }
+    }
+    assert_eq!(block.instructions()[3].scalars_read().len(), 2);
+}
+
+
+#[test]
+fn copy_propagation_redefinition() {
+    /*
+    b = a
+    a = 7
+    c = b
+    b = 1
+    d = c
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+
+        block.assign(il::scalar("b", 32), il::expr_scalar("a", 32));
+        block.assign(il::scalar("a", 32), il::expr_const(7, 32));
+        block.assign(il::scalar("c", 32), il::expr_scalar("b", 32));
+        block.assign(il::scalar("b", 32), il::expr_const(1, 32));
+        block.assign(il::scalar("d", 32), il::expr_scalar("c", 32));
+
+        block.index()
+    };
+
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let function = copy_propagation(&function).unwrap();
+
+    let block = function.block(index).unwrap();
+    let read = |position: usize| {
+        block.instructions()[position].scalars_read()[0].clone()
+    };
+
+    // a is redefined, so b no longer holds a copy of a
+    assert_eq!(read(2), il::scalar("b", 32));
+    // b is redefined, so c no longer holds a copy of b
+    assert_eq!(read(4), il::scalar("c", 32));
+}
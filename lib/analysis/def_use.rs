@@ -10,21 +10,19 @@ use std::collections::HashMap;
 /// Compute definition use chains for the given function.
 pub fn def_use<'r>(function: &'r il::Function)
 -> Result<HashMap<il::RefProgramLocation<'r>, LocationSet<'r>>> {
-    let rd = reaching_definitions::reaching_definitions(function)?;
+    let rd = reaching_definitions::reaching_definitions_by_scalar(function)?;
 
     let mut du: HashMap<il::RefProgramLocation<'r>, LocationSet<'r>> = HashMap::new();
 
     for (location, _) in &rd {
+        // the definitions reaching each location are read before it executes
+        let reaching = reaching_definitions::reaching_before(&rd, location)?;
         du.entry(location.clone()).or_insert(LocationSet::new());
         match *location.function_location() {
             il::RefFunctionLocation::Instruction(_, ref instruction) => {
                 for scalar_read in instruction.operation().scalars_read() {
-                    for rd in rd[&location].locations() {
-                        if rd.instruction()
-                             .unwrap()
-                             .operation()
-                             .scalar_written()
-                             .unwrap() == scalar_read {
+                    if let Some(definitions) = reaching.get(scalar_read) {
+                        for rd in definitions.locations() {
                             du.entry(rd.clone()).or_insert(LocationSet::new()).insert(location.clone());
                         }
                     }
@@ -33,12 +31,8 @@ pub fn def_use<'r>(function: &'r il::Function)
             il::RefFunctionLocation::Edge(ref edge) => {
                 if let Some(ref condition) = *edge.condition() {
                     for scalar_read in condition.scalars() {
-                        for rd in rd[&location].locations() {
-                            if rd.instruction()
-                                 .unwrap()
-                                 .operation()
-                                 .scalar_written()
-                                 .unwrap() == scalar_read {
+                        if let Some(definitions) = reaching.get(scalar_read) {
+                            for rd in definitions.locations() {
                                 du.entry(rd.clone()).or_insert(LocationSet::new()).insert(location.clone());
                             }
                         }
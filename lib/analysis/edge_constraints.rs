@@ -0,0 +1,73 @@
+//! Collects the conditions known to hold when control flows along an edge.
+
+use il;
+use std::collections::BTreeMap;
+
+
+/// Get the guard expressions which hold when each edge, keyed by
+/// `(head, tail)`, is taken.
+///
+/// A conditional edge carries its own condition. An unconditional edge out of
+/// a block which also has conditional edges is only taken when none of them
+/// are, and so carries the logical negation of each of their conditions.
+/// Other edges carry no constraints.
+pub fn edge_constraints(function: &il::Function)
+-> BTreeMap<(u64, u64), Vec<il::Expression>> {
+    let control_flow_graph = function.control_flow_graph();
+    let mut constraints = BTreeMap::new();
+
+    for block in function.blocks() {
+        let head = block.index();
+        let edges = control_flow_graph.successor_indices(head)
+                                      .unwrap_or_default()
+                                      .into_iter()
+                                      .filter_map(|tail| function.edge(head, tail))
+                                      .collect::<Vec<&il::Edge>>();
+
+        let negations = edges.iter()
+                             .filter_map(|edge| edge.condition().clone())
+                             .filter_map(|condition| il::Expression::logical_not(condition).ok())
+                             .collect::<Vec<il::Expression>>();
+
+        for edge in edges {
+            let guards = match *edge.condition() {
+                Some(ref condition) => vec![condition.clone()],
+                None => negations.clone()
+            };
+            constraints.insert((head, edge.tail()), guards);
+        }
+    }
+
+    constraints
+}
+
+
+#[test]
+fn edge_constraints_test() {
+    /*
+    head:  if x <u 10 goto taken else fall through to fallthrough
+    taken, fallthrough -> tail
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let head_index = control_flow_graph.new_block().unwrap().index();
+    let taken_index = control_flow_graph.new_block().unwrap().index();
+    let fallthrough_index = control_flow_graph.new_block().unwrap().index();
+    let tail_index = control_flow_graph.new_block().unwrap().index();
+
+    let condition = il::Expression::cmpltu(il::expr_scalar("x", 32), il::expr_const(10, 32)).unwrap();
+    control_flow_graph.conditional_edge(head_index, taken_index, condition.clone()).unwrap();
+    control_flow_graph.unconditional_edge(head_index, fallthrough_index).unwrap();
+    control_flow_graph.unconditional_edge(taken_index, tail_index).unwrap();
+    control_flow_graph.unconditional_edge(fallthrough_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let constraints = edge_constraints(&function);
+
+    assert_eq!(constraints.len(), 4);
+    assert_eq!(constraints[&(head_index, taken_index)], vec![condition.clone()]);
+    assert_eq!(constraints[&(head_index, fallthrough_index)],
+               vec![il::Expression::logical_not(condition).unwrap()]);
+    assert!(constraints[&(taken_index, tail_index)].is_empty());
+    assert!(constraints[&(fallthrough_index, tail_index)].is_empty());
+}
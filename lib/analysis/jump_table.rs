@@ -0,0 +1,88 @@
+//! Resolves the targets of indirect branches, as used to implement switch
+//! statements, from the results of `value_set::value_sets`.
+
+use analysis::value_set::StridedInterval;
+use il;
+use std::collections::BTreeMap;
+
+
+/// Find the possible targets of each branch with a target which is not
+/// constant.
+///
+/// The targets of a branch are enumerated from the value set of its target
+/// expression. Branches with more than max_targets possible targets, or
+/// without value sets, are not resolved and are left out of the result.
+pub fn resolve_indirect(
+    function: &il::Function,
+    value_sets: &BTreeMap<il::ProgramLocation, BTreeMap<il::Scalar, StridedInterval>>,
+    max_targets: usize
+) -> Vec<(il::ProgramLocation, Vec<u64>)> {
+    let mut resolved = Vec::new();
+
+    for location in function.instruction_locations() {
+        let target = match location.instruction().map(|instruction| instruction.operation()) {
+            Some(&il::Operation::Branch { ref target }) => target.clone(),
+            _ => continue
+        };
+        if let il::Expression::Constant(_) = target {
+            continue;
+        }
+
+        let location: il::ProgramLocation = location.into();
+        let targets = value_sets.get(&location)
+                                .map(|values| StridedInterval::eval(&target, values))
+                                .and_then(|values| values.values(max_targets));
+        if let Some(targets) = targets {
+            resolved.push((location, targets));
+        }
+    }
+
+    resolved
+}
+
+
+#[test]
+fn resolve_indirect_test() {
+    /*
+    head:     if index <u 4 goto dispatch, else fall through to default
+    dispatch: branch 0x1000 + index * 4
+    */
+    let index = il::scalar("index", 32);
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let head_index = control_flow_graph.new_block().unwrap().index();
+
+    let dispatch_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(il::Expression::add(
+            il::expr_const(0x1000, 32),
+            il::Expression::mul(index.clone().into(), il::expr_const(4, 32)).unwrap()
+        ).unwrap());
+        block.index()
+    };
+
+    let default_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(il::expr_const(0x2000, 32));
+        block.index()
+    };
+
+    let condition = il::Expression::cmpltu(index.clone().into(), il::expr_const(4, 32)).unwrap();
+    control_flow_graph.conditional_edge(head_index, dispatch_index, condition).unwrap();
+    control_flow_graph.unconditional_edge(head_index, default_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let value_sets = ::analysis::value_set::value_sets(&function).unwrap();
+
+    let resolved = resolve_indirect(&function, &value_sets, 16);
+    assert_eq!(resolved.len(), 1);
+    let block = function.block(dispatch_index).unwrap();
+    let location = il::RefProgramLocation::new(
+        &function,
+        il::RefFunctionLocation::Instruction(block, block.instruction(0).unwrap())
+    );
+    assert_eq!(resolved[0], (location.into(), vec![0x1000, 0x1004, 0x1008, 0x100c]));
+
+    assert!(resolve_indirect(&function, &value_sets, 3).is_empty());
+}
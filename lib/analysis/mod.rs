@@ -1,14 +1,30 @@
 //! Implementations and traits for static analysis over Falcon IL.
 
 pub mod ai;
+mod arguments;
 pub mod calling_convention;
+mod clobber;
+mod copy_propagation;
 mod def_use;
+pub mod edge_constraints;
 pub mod fixed_point;
+pub mod jump_table;
 mod location_set;
+pub mod no_return;
 mod reaching_definitions;
+mod slicing;
+pub mod stack_pointer;
+pub mod summaries;
+pub mod syscalls;
+pub mod taint;
 mod use_def;
+pub mod value_set;
 
+pub use self::arguments::used_arguments;
+pub use self::clobber::{clobbered_registers, conforms_to, restored_registers};
+pub use self::copy_propagation::copy_propagation;
 pub use self::def_use::def_use;
 pub use self::location_set::LocationSet;
 pub use self::reaching_definitions::reaching_definitions;
-pub use self::use_def::use_def;
\ No newline at end of file
+pub use self::slicing::backward_slice;
+pub use self::use_def::{use_def, UseDefChains};
\ No newline at end of file
@@ -0,0 +1,149 @@
+//! Infers which functions of a `Program` never return to their callers.
+
+use il;
+use std::collections::{BTreeSet, VecDeque};
+
+
+/// Find the indices of the functions in this program which never return.
+///
+/// Functions marked with `Program::mark_no_return` never return. Otherwise a
+/// function never returns when no path from its entry reaches a block without
+/// successors which may return. A block does not return if it calls or
+/// branches to a constant address of a function which never returns, or if it
+/// has no successors and ends in a `Raise`. Functions without an entry are
+/// assumed to return.
+pub fn infer_no_return(program: &il::Program) -> BTreeSet<u64> {
+    let mut no_return = program.functions_map()
+                               .iter()
+                               .filter(|&(_, function)| function.is_no_return())
+                               .map(|(index, _)| *index)
+                               .collect::<BTreeSet<u64>>();
+
+    // Finding a function which never returns may show its callers never
+    // return, so iterate until nothing changes.
+    loop {
+        let mut changed = false;
+        for (index, function) in program.functions_map() {
+            if !no_return.contains(index) && !returns(program, function, &no_return) {
+                no_return.insert(*index);
+                changed = true;
+            }
+        }
+        if !changed {
+            return no_return;
+        }
+    }
+}
+
+
+/// Returns true if some path through this function may return.
+fn returns(program: &il::Program, function: &il::Function, no_return: &BTreeSet<u64>) -> bool {
+    let cfg = function.control_flow_graph();
+    let entry = match cfg.entry() {
+        Some(entry) => entry,
+        None => return true
+    };
+
+    let mut visited = BTreeSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(entry);
+
+    while let Some(index) = queue.pop_front() {
+        if !visited.insert(index) {
+            continue;
+        }
+        let block = match cfg.block(index) {
+            Some(block) => block,
+            None => return true
+        };
+        if calls_no_return(program, block, no_return) {
+            continue;
+        }
+        let successors = match cfg.successor_indices(index) {
+            Ok(successors) => successors,
+            Err(_) => return true
+        };
+        if successors.is_empty() {
+            match block.instructions().last().map(|instruction| instruction.operation()) {
+                Some(&il::Operation::Raise { .. }) => continue,
+                _ => return true
+            }
+        }
+        queue.extend(successors);
+    }
+
+    false
+}
+
+
+/// Returns true if this block calls or branches to a constant address of a
+/// function which never returns.
+fn calls_no_return(program: &il::Program, block: &il::Block, no_return: &BTreeSet<u64>) -> bool {
+    block.instructions().iter().any(|instruction| {
+        let target = match *instruction.operation() {
+            il::Operation::Branch { ref target } |
+            il::Operation::Call { ref target, .. } => target,
+            _ => return false
+        };
+        match *target {
+            il::Expression::Constant(ref constant) =>
+                program.function_by_address(constant.value())
+                       .and_then(|function| function.index())
+                       .map(|index| no_return.contains(&index))
+                       .unwrap_or(false),
+            _ => false
+        }
+    })
+}
+
+
+#[test]
+fn infer_no_return_test() {
+    let mut program = il::Program::new();
+
+    // 0: raise, with no way out
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().raise(il::expr_scalar("exit", 32));
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x1000, control_flow_graph));
+
+    // 1: if c { call 0x1000 } else { branch ra }
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let head = control_flow_graph.new_block().unwrap().index();
+    let fatal = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.call(il::expr_const(0x1000, 32), None);
+        block.index()
+    };
+    let ret = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(il::expr_scalar("ra", 32));
+        block.index()
+    };
+    control_flow_graph.conditional_edge(head, fatal, il::expr_scalar("c", 1)).unwrap();
+    control_flow_graph.conditional_edge(head, ret, il::expr_scalar("d", 1)).unwrap();
+    control_flow_graph.set_entry(head).unwrap();
+    program.add_function(il::Function::new(0x2000, control_flow_graph));
+
+    // 2: call 0x1000, then falls through to a return
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.call(il::expr_const(0x1000, 32), None);
+        block.branch(il::expr_scalar("ra", 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x3000, control_flow_graph));
+
+    // 3: branch ra, but marked as never returning
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().branch(il::expr_scalar("ra", 32));
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x4000, control_flow_graph));
+    program.mark_no_return(3).unwrap();
+    assert!(program.mark_no_return(4).is_err());
+
+    assert!(!program.function(0).unwrap().is_no_return());
+    assert!(program.function(3).unwrap().is_no_return());
+    assert_eq!(infer_no_return(&program), vec![0, 2, 3].into_iter().collect());
+}
@@ -1,14 +1,93 @@
 use analysis::{fixed_point, LocationSet};
 use error::*;
 use il;
-use std::collections::{HashMap};
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 
 #[allow(dead_code)]
 /// Compute reaching definitions for the given function.
+///
+/// A definition reaches a location while any scalar it writes has not been
+/// written again.
 pub fn reaching_definitions<'r>(function: &'r il::Function)
 -> Result<HashMap<il::RefProgramLocation<'r>, LocationSet>> {
-    fixed_point::fixed_point_forward(ReachingDefinitions{}, function)
+    Ok(reaching_definitions_by_scalar(function)?
+        .into_iter()
+        .map(|(location, definitions)| {
+            let mut reaching = LocationSet::new();
+            for locations in definitions.values() {
+                for definition in locations.locations() {
+                    reaching.insert(definition.clone());
+                }
+            }
+            (location, reaching)
+        })
+        .collect())
+}
+
+
+/// Compute, for each scalar, the definitions of that scalar which reach each
+/// location in the given function.
+///
+/// Writing a scalar kills the definitions of that scalar only, so an
+/// instruction which writes several scalars still defines the others.
+pub(crate) fn reaching_definitions_by_scalar<'r>(function: &'r il::Function)
+-> Result<HashMap<il::RefProgramLocation<'r>, BTreeMap<il::Scalar, LocationSet<'r>>>> {
+    Ok(fixed_point::fixed_point_forward(ReachingDefinitions{}, function)?
+        .into_iter()
+        .map(|(location, definitions)| (location, definitions.0))
+        .collect())
+}
+
+
+/// Get the definitions of each scalar which reach the given location before
+/// it executes, from the reaching definitions computed after each location.
+pub(crate) fn reaching_before<'r>(
+    rd: &HashMap<il::RefProgramLocation<'r>, BTreeMap<il::Scalar, LocationSet<'r>>>,
+    location: &il::RefProgramLocation<'r>
+) -> Result<BTreeMap<il::Scalar, LocationSet<'r>>> {
+    let mut reaching = BTreeMap::new();
+    for predecessor in location.backward()? {
+        if let Some(definitions) = rd.get(&predecessor) {
+            for (scalar, locations) in definitions {
+                let reaching = reaching.entry(scalar.clone()).or_insert_with(LocationSet::new);
+                for definition in locations.locations() {
+                    reaching.insert(definition.clone());
+                }
+            }
+        }
+    }
+    Ok(reaching)
+}
+
+
+/// The definitions of each scalar reaching a location.
+#[derive(Clone, Debug, PartialEq)]
+struct ScalarDefinitions<'r>(BTreeMap<il::Scalar, LocationSet<'r>>);
+
+
+impl<'r> PartialOrd for ScalarDefinitions<'r> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        let empty = LocationSet::new();
+        let scalars = self.0.keys().chain(other.0.keys()).collect::<BTreeSet<&il::Scalar>>();
+
+        let mut ordering = Ordering::Equal;
+        for scalar in scalars {
+            let lhs = self.0.get(scalar).unwrap_or(&empty);
+            let rhs = other.0.get(scalar).unwrap_or(&empty);
+            match lhs.partial_cmp(rhs)? {
+                Ordering::Equal => {},
+                scalar_ordering => if ordering == Ordering::Equal {
+                    ordering = scalar_ordering;
+                }
+                else if ordering != scalar_ordering {
+                    return None;
+                }
+            }
+        }
+        Some(ordering)
+    }
 }
 
 
@@ -16,32 +95,22 @@ pub fn reaching_definitions<'r>(function: &'r il::Function)
 struct ReachingDefinitions {}
 
 
-impl<'r> fixed_point::FixedPointAnalysis<'r, LocationSet<'r>> for ReachingDefinitions {
-    fn trans(&self, location: il::RefProgramLocation<'r>, state: Option<LocationSet<'r>>)
-        -> Result<LocationSet<'r>> {
+impl<'r> fixed_point::FixedPointAnalysis<'r, ScalarDefinitions<'r>> for ReachingDefinitions {
+    fn trans(&self, location: il::RefProgramLocation<'r>, state: Option<ScalarDefinitions<'r>>)
+        -> Result<ScalarDefinitions<'r>> {
 
         let mut state = match state {
             Some(state) => state,
-            None => LocationSet::new()
+            None => ScalarDefinitions(BTreeMap::new())
         };
 
         match *location.function_location() {
             il::RefFunctionLocation::Instruction(_, ref instruction) => {
-                let mut kill = Vec::new();
-                if let Some(scalar) = instruction.operation().scalar_written() {
-                    for location in state.locations() {
-                        if location.instruction()
-                                   .unwrap()
-                                   .operation()
-                                   .scalar_written()
-                                   .unwrap() == scalar {
-                            kill.push(location.clone());
-                        }
-                    }
-                    for k in kill {
-                        state.remove(&k);
-                    }
-                    state.insert(location.clone());
+                // This kills the definitions of each scalar written here.
+                for scalar in instruction.operation().scalars_written() {
+                    let mut definitions = LocationSet::new();
+                    definitions.insert(location.clone());
+                    state.0.insert(scalar.clone(), definitions);
                 }
             },
             il::RefFunctionLocation::EmptyBlock(_) |
@@ -52,11 +121,14 @@ impl<'r> fixed_point::FixedPointAnalysis<'r, LocationSet<'r>> for ReachingDefini
     }
 
 
-    fn join(&self, mut state0: LocationSet<'r>, state1: &LocationSet<'r>)
-        -> Result<LocationSet<'r>> {
+    fn join(&self, mut state0: ScalarDefinitions<'r>, state1: &ScalarDefinitions<'r>)
+        -> Result<ScalarDefinitions<'r>> {
 
-        for state in state1.locations() {
-            state0.insert(state.clone());
+        for (scalar, locations) in &state1.0 {
+            let definitions = state0.0.entry(scalar.clone()).or_insert_with(LocationSet::new);
+            for location in locations.locations() {
+                definitions.insert(location.clone());
+            }
         }
         Ok(state0)
     }
@@ -0,0 +1,207 @@
+//! Backward program slicing.
+
+use analysis::{reaching_definitions, UseDefChains};
+use error::*;
+use il;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+
+/// Compute the locations in the function which may affect the values of the
+/// given scalars after the given location.
+///
+/// The slice follows data dependences, through use-definition chains, and
+/// control dependences, through the conditional edges which decide whether a
+/// location executes. It contains the instructions and edges found, but not
+/// the given location unless it is found.
+pub fn backward_slice(
+    function: &il::Function,
+    location: il::ProgramLocation,
+    scalars: &[il::Scalar]
+) -> Result<BTreeSet<il::ProgramLocation>> {
+
+    let function_location = location.function_location()
+                                    .apply(function)
+                                    .ok_or(format!("Location {} not in function", location))?;
+    let ref_location = il::RefProgramLocation::new(function, function_location);
+
+    let rd = reaching_definitions::reaching_definitions_by_scalar(function)?;
+    let chains = UseDefChains::new(function)?;
+    let control_dependences = function.control_flow_graph().control_dependences()?;
+
+    let mut slice = BTreeSet::new();
+    let mut queue = VecDeque::new();
+
+    // definitions of the scalars after the given location
+    if let Some(reaching) = rd.get(&ref_location) {
+        for scalar in scalars {
+            if let Some(definitions) = reaching.get(scalar) {
+                for definition in definitions.locations() {
+                    queue.push_back(il::ProgramLocation::from(definition.clone()));
+                }
+            }
+        }
+    }
+    queue.extend(branches(function, &control_dependences, &location)?);
+
+    while let Some(location) = queue.pop_front() {
+        if !slice.insert(location.clone()) {
+            continue;
+        }
+        // the definitions of every scalar read here, and the branches which
+        // decide whether this executes
+        queue.extend(chains.definitions_of(&location));
+        queue.extend(branches(function, &control_dependences, &location)?);
+    }
+
+    Ok(slice)
+}
+
+
+/// Get the conditional edges out of every block the given location is control
+/// dependent on.
+fn branches(
+    function: &il::Function,
+    control_dependences: &BTreeMap<u64, BTreeSet<u64>>,
+    location: &il::ProgramLocation
+) -> Result<Vec<il::ProgramLocation>> {
+
+    let block_index = match *location.function_location() {
+        il::FunctionLocation::Instruction(block_index, _) |
+        il::FunctionLocation::EmptyBlock(block_index) => block_index,
+        il::FunctionLocation::Edge(head, _) => head
+    };
+
+    let mut branches = Vec::new();
+    if let Some(branch_indices) = control_dependences.get(&block_index) {
+        let control_flow_graph = function.control_flow_graph();
+        for branch_index in branch_indices {
+            for successor_index in control_flow_graph.successor_indices(*branch_index)? {
+                let edge = control_flow_graph.edge(*branch_index, successor_index)
+                                             .ok_or("Could not find edge")?;
+                if edge.condition().is_some() {
+                    branches.push(il::ProgramLocation::new(
+                        location.function_index(),
+                        il::FunctionLocation::Edge(edge.head(), edge.tail())));
+                }
+            }
+        }
+    }
+
+    Ok(branches)
+}
+
+
+#[test]
+fn backward_slice_test() {
+    /*
+    a = in
+    b = 3
+    c = in2
+    if c == 0 {
+        r = a + 1
+    }
+    else {
+        x = b
+    }
+    y = r
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("a", 32), il::expr_scalar("in", 32));
+        block.assign(il::scalar("b", 32), il::expr_const(3, 32));
+        block.assign(il::scalar("c", 32), il::expr_scalar("in2", 32));
+        block.index()
+    };
+
+    let then_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("r", 32),
+                     il::Expression::add(il::expr_scalar("a", 32),
+                                         il::expr_const(1, 32)).unwrap());
+        block.index()
+    };
+
+    let else_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("x", 32), il::expr_scalar("b", 32));
+        block.index()
+    };
+
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("y", 32), il::expr_scalar("r", 32));
+        block.index()
+    };
+
+    let condition = il::Expression::cmpeq(
+        il::expr_scalar("c", 32),
+        il::expr_const(0, 32)
+    ).unwrap();
+
+    control_flow_graph.conditional_edge(head_index, then_index, condition.clone()).unwrap();
+    control_flow_graph.conditional_edge(head_index, else_index,
+        il::Expression::cmpeq(condition, il::expr_const(0, 1)).unwrap()
+    ).unwrap();
+    control_flow_graph.unconditional_edge(then_index, tail_index).unwrap();
+    control_flow_graph.unconditional_edge(else_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let instruction = |block_index, instruction_index| {
+        il::ProgramLocation::new(None,
+            il::FunctionLocation::Instruction(block_index, instruction_index))
+    };
+    let edge = |head, tail| {
+        il::ProgramLocation::new(None, il::FunctionLocation::Edge(head, tail))
+    };
+
+    let slice = backward_slice(&function,
+                               instruction(tail_index, 0),
+                               &[il::scalar("y", 32)]).unwrap();
+
+    let expected = vec![
+        instruction(head_index, 0),
+        instruction(head_index, 2),
+        instruction(then_index, 0),
+        instruction(tail_index, 0),
+        edge(head_index, then_index),
+        edge(head_index, else_index)
+    ];
+    assert_eq!(slice, expected.into_iter().collect());
+}
+
+
+#[test]
+fn backward_slice_redefinition() {
+    /*
+    a = 0
+    a = a + 1
+    [0] = a
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("a", 32), il::expr_const(0, 32));
+        block.assign(il::scalar("a", 32),
+                     il::Expression::add(il::expr_scalar("a", 32),
+                                         il::expr_const(1, 32)).unwrap());
+        block.store(il::expr_const(0, 32), il::expr_scalar("a", 32));
+        block.index()
+    };
+    control_flow_graph.set_entry(index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let instruction = |instruction_index| {
+        il::ProgramLocation::new(None,
+            il::FunctionLocation::Instruction(index, instruction_index))
+    };
+
+    let slice = backward_slice(&function, instruction(2), &[il::scalar("a", 32)]).unwrap();
+
+    let expected = vec![instruction(0), instruction(1)];
+    assert_eq!(slice, expected.into_iter().collect());
+}
@@ -0,0 +1,241 @@
+//! Tracks the offset of the stack pointer relative to its value at function
+//! entry.
+
+use analysis::fixed_point;
+use error::*;
+use il;
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::BTreeMap;
+
+
+/// Compute the offset of the stack pointer from its value at function entry
+/// after each location in the function.
+///
+/// The offset is `None` where it cannot be determined.
+pub fn stack_offsets(function: &il::Function, stack_pointer: &il::Scalar)
+-> Result<BTreeMap<il::ProgramLocation, Option<i64>>> {
+    let analysis = StackPointerAnalysis { stack_pointer: stack_pointer.clone() };
+    let states = fixed_point::fixed_point_forward(analysis, function)?;
+
+    let mut offsets = BTreeMap::new();
+    for (location, state) in states {
+        let offset = match state {
+            StackPointerOffset::Offset(offset) => Some(offset),
+            StackPointerOffset::Top => None
+        };
+        offsets.insert(location.into(), offset);
+    }
+
+    Ok(offsets)
+}
+
+
+/// The offset of the stack pointer at a location.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum StackPointerOffset {
+    Offset(i64),
+    Top
+}
+
+
+impl StackPointerOffset {
+    fn join(&self, other: &StackPointerOffset) -> StackPointerOffset {
+        if self == other {
+            self.clone()
+        }
+        else {
+            StackPointerOffset::Top
+        }
+    }
+}
+
+
+impl PartialOrd for StackPointerOffset {
+    fn partial_cmp(&self, other: &StackPointerOffset) -> Option<Ordering> {
+        match (self, other) {
+            (&StackPointerOffset::Top, &StackPointerOffset::Top) => Some(Ordering::Equal),
+            (&StackPointerOffset::Top, _) => Some(Ordering::Greater),
+            (_, &StackPointerOffset::Top) => Some(Ordering::Less),
+            (&StackPointerOffset::Offset(lhs), &StackPointerOffset::Offset(rhs)) =>
+                if lhs == rhs { Some(Ordering::Equal) } else { None }
+        }
+    }
+}
+
+
+struct StackPointerAnalysis {
+    stack_pointer: il::Scalar
+}
+
+
+/// If this expression is the stack pointer adjusted by a constant, return the
+/// adjustment, or `None` if it does not fit in an `i64`.
+pub(crate) fn adjustment(stack_pointer: &il::Scalar, expression: &il::Expression)
+-> Option<i64> {
+    match *expression {
+        il::Expression::Scalar(ref scalar) =>
+            if scalar == stack_pointer { Some(0) } else { None },
+        il::Expression::Add(ref lhs, ref rhs) => {
+            match (&**lhs, &**rhs) {
+                (&il::Expression::Scalar(ref scalar), &il::Expression::Constant(ref constant)) |
+                (&il::Expression::Constant(ref constant), &il::Expression::Scalar(ref scalar)) =>
+                    if scalar == stack_pointer {
+                        Some(constant.value_i64())
+                    }
+                    else {
+                        None
+                    },
+                _ => None
+            }
+        },
+        il::Expression::Sub(ref lhs, ref rhs) => {
+            match (&**lhs, &**rhs) {
+                (&il::Expression::Scalar(ref scalar), &il::Expression::Constant(ref constant)) =>
+                    if scalar == stack_pointer {
+                        constant.value_i64().checked_neg()
+                    }
+                    else {
+                        None
+                    },
+                _ => None
+            }
+        },
+        _ => None
+    }
+}
+
+
+impl<'f> fixed_point::FixedPointAnalysis<'f, StackPointerOffset> for StackPointerAnalysis {
+    fn trans(&self, location: il::RefProgramLocation<'f>, state: Option<StackPointerOffset>)
+        -> Result<StackPointerOffset> {
+
+        let state = match state {
+            Some(state) => state,
+            None => StackPointerOffset::Offset(0)
+        };
+
+        let instruction = match location.instruction() {
+            Some(instruction) => instruction,
+            None => return Ok(state)
+        };
+
+        if !instruction.scalars_written().contains(&&self.stack_pointer) {
+            return Ok(state);
+        }
+
+        Ok(match (state, instruction.operation()) {
+            (StackPointerOffset::Offset(offset), &il::Operation::Assign { ref src, .. }) =>
+                match adjustment(&self.stack_pointer, src)
+                          .and_then(|adjustment| offset.checked_add(adjustment)) {
+                    Some(offset) => StackPointerOffset::Offset(offset),
+                    None => StackPointerOffset::Top
+                },
+            _ => StackPointerOffset::Top
+        })
+    }
+
+    fn join(&self, state0: StackPointerOffset, state1: &StackPointerOffset)
+        -> Result<StackPointerOffset> {
+
+        Ok(state0.join(state1))
+    }
+}
+
+
+#[test]
+fn stack_offsets_test() {
+    /*
+    sp = sp - 16
+    [sp + 4] = a
+    sp = sp + 8         (or, in the second function, sp = a)
+    sp = sp - 8
+    sp = sp + 16
+    */
+    let sp = il::scalar("sp", 32);
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let prologue_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), il::Expression::sub(sp.clone().into(), il::expr_const(16, 32)).unwrap());
+        block.store(il::Expression::add(sp.clone().into(), il::expr_const(4, 32)).unwrap(),
+                    il::expr_scalar("a", 32));
+        block.index()
+    };
+
+    let balanced_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), il::Expression::add(sp.clone().into(), il::expr_const(8, 32)).unwrap());
+        block.assign(sp.clone(), il::Expression::sub(sp.clone().into(), il::expr_const(8, 32)).unwrap());
+        block.index()
+    };
+
+    let epilogue_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), il::Expression::add(sp.clone().into(), il::expr_const(16, 32)).unwrap());
+        block.index()
+    };
+
+    control_flow_graph.unconditional_edge(prologue_index, balanced_index).unwrap();
+    control_flow_graph.unconditional_edge(balanced_index, epilogue_index).unwrap();
+    control_flow_graph.set_entry(prologue_index).unwrap();
+
+    let mut unknown = control_flow_graph.clone();
+    let unknown_index = {
+        let block = unknown.new_block().unwrap();
+        block.assign(sp.clone(), il::expr_scalar("a", 32));
+        block.index()
+    };
+    unknown.unconditional_edge(prologue_index, unknown_index).unwrap();
+    unknown.unconditional_edge(unknown_index, epilogue_index).unwrap();
+
+    let offset_after = |offsets: &BTreeMap<il::ProgramLocation, Option<i64>>,
+                        function: &il::Function,
+                        block_index: u64,
+                        instruction_index: u64| {
+        let block = function.block(block_index).unwrap();
+        let location = il::RefProgramLocation::new(
+            function,
+            il::RefFunctionLocation::Instruction(block, block.instruction(instruction_index).unwrap())
+        );
+        offsets[&location.into()]
+    };
+
+    let function = il::Function::new(0, control_flow_graph);
+    let offsets = stack_offsets(&function, &sp).unwrap();
+    assert_eq!(offset_after(&offsets, &function, prologue_index, 0), Some(-16));
+    assert_eq!(offset_after(&offsets, &function, prologue_index, 1), Some(-16));
+    assert_eq!(offset_after(&offsets, &function, balanced_index, 0), Some(-8));
+    assert_eq!(offset_after(&offsets, &function, balanced_index, 1), Some(-16));
+    assert_eq!(offset_after(&offsets, &function, epilogue_index, 0), Some(0));
+
+    let function = il::Function::new(0, unknown);
+    let offsets = stack_offsets(&function, &sp).unwrap();
+    assert_eq!(offset_after(&offsets, &function, unknown_index, 0), None);
+    assert_eq!(offset_after(&offsets, &function, epilogue_index, 0), None);
+}
+
+
+#[test]
+fn stack_offsets_overflow() {
+    let sp = il::scalar("sp", 64);
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(),
+                     il::Expression::add(sp.clone().into(), il::expr_const(1 << 63, 64)).unwrap());
+        block.assign(sp.clone(),
+                     il::Expression::sub(sp.clone().into(), il::expr_const(1, 64)).unwrap());
+        block.assign(sp.clone(),
+                     il::Expression::sub(sp.clone().into(), il::expr_const(1 << 63, 64)).unwrap());
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    let function = il::Function::new(0, control_flow_graph);
+
+    let offsets = stack_offsets(&function, &sp).unwrap();
+    let offset_after = |instruction_index: u64| {
+        offsets[&il::ProgramLocation::new(None, il::FunctionLocation::Instruction(0, instruction_index))]
+    };
+    assert_eq!(offset_after(0), Some(::std::i64::MIN));
+    assert_eq!(offset_after(1), None);
+    assert_eq!(offset_after(2), None);
+}
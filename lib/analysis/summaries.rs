@@ -0,0 +1,205 @@
+//! Summarizes the registers each function of a `Program` reads and writes,
+//! so calls can be analyzed without analyzing the callee again.
+
+use analysis::calling_convention::CallingConvention;
+use analysis::{clobbered_registers, restored_registers};
+use il;
+use std::collections::{BTreeMap, BTreeSet};
+
+
+/// The registers a function, and the functions it calls, read and write.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FunctionSummary {
+    /// Scalars which may be written by a call to the function.
+    pub clobbered: BTreeSet<il::Scalar>,
+    /// Registers of the calling convention which a call to the function does
+    /// not write, or restores to their value from before the call.
+    pub preserved: BTreeSet<il::Scalar>,
+    /// Scalars which may be read by a call to the function.
+    pub reads: BTreeSet<il::Scalar>
+}
+
+
+/// Compute a `FunctionSummary` for every function in the program, by index.
+///
+/// A call or branch to the constant address of a function includes the
+/// summary of that function. Any other call is assumed to write every
+/// register the calling convention trashes. Branches to other targets, such
+/// as returns, read their target but are not assumed to write anything.
+///
+/// A register is preserved when neither the function nor its callees write
+/// it, or when the function restores it, as given by `restored_registers`.
+pub fn compute_summaries(program: &il::Program, calling_convention: &CallingConvention)
+-> BTreeMap<u64, FunctionSummary> {
+    let trashed = calling_convention.trashed_registers()
+                                    .iter()
+                                    .cloned()
+                                    .collect::<BTreeSet<il::Scalar>>();
+
+    let mut summaries = BTreeMap::new();
+    let mut callees = BTreeMap::new();
+    let mut restored = BTreeMap::new();
+    let mut unrestored = BTreeMap::new();
+    for (index, function) in program.functions_map() {
+        let mut summary = FunctionSummary::default();
+        summary.clobbered = clobbered_registers(function);
+
+        let mut function_callees = BTreeSet::new();
+        for (_, instruction) in function.instructions() {
+            summary.reads.extend(instruction.scalars_read().into_iter().cloned());
+            let (target, is_call) = match *instruction.operation() {
+                il::Operation::Call { ref target, .. } => (target, true),
+                il::Operation::Branch { ref target } => (target, false),
+                _ => continue
+            };
+            let callee = target.as_constant()
+                               .and_then(|target| program.function_by_address(target.value()))
+                               .and_then(|callee| callee.index());
+            match callee {
+                Some(callee) => { function_callees.insert(callee); },
+                None => if is_call {
+                    summary.clobbered.extend(trashed.iter().cloned());
+                }
+            }
+        }
+        for edge in function.edges() {
+            if let Some(ref condition) = *edge.condition() {
+                summary.reads.extend(condition.scalars().into_iter().cloned());
+            }
+        }
+
+        let function_restored = restored_registers(function, calling_convention)
+            .unwrap_or_default();
+        unrestored.insert(*index, summary.clobbered
+                                         .difference(&function_restored)
+                                         .cloned()
+                                         .collect::<BTreeSet<il::Scalar>>());
+        restored.insert(*index, function_restored);
+        summaries.insert(*index, summary);
+        callees.insert(*index, function_callees);
+    }
+
+    // Include the summaries of callees until nothing changes, which handles
+    // recursion.
+    loop {
+        let mut changed = false;
+        for (index, function_callees) in &callees {
+            let mut summary = summaries[index].clone();
+            let mut function_unrestored = unrestored[index].clone();
+            for callee in function_callees {
+                function_unrestored.extend(unrestored[callee].iter().cloned());
+                let callee = &summaries[callee];
+                summary.clobbered.extend(callee.clobbered.iter().cloned());
+                summary.reads.extend(callee.reads.iter().cloned());
+            }
+            // A register the function restores is preserved, even when a
+            // callee writes it
+            let function_unrestored = function_unrestored.difference(&restored[index])
+                                                         .cloned()
+                                                         .collect::<BTreeSet<il::Scalar>>();
+            if summary != summaries[index] || function_unrestored != unrestored[index] {
+                summaries.insert(*index, summary);
+                unrestored.insert(*index, function_unrestored);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let registers = calling_convention.preserved_registers()
+                                      .iter()
+                                      .chain(trashed.iter())
+                                      .cloned()
+                                      .collect::<BTreeSet<il::Scalar>>();
+    for (index, summary) in summaries.iter_mut() {
+        summary.preserved = registers.difference(&unrestored[index]).cloned().collect();
+    }
+
+    summaries
+}
+
+
+#[test]
+fn compute_summaries_test() {
+    use analysis::calling_convention::CallingConventionType;
+
+    let calling_convention = CallingConvention::new(CallingConventionType::MipsSystemV);
+    let mut program = il::Program::new();
+
+    // 0: $v0 = $a0 + 1
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    control_flow_graph.new_block()
+                      .unwrap()
+                      .assign(il::scalar("$v0", 32),
+                              il::Expression::add(il::expr_scalar("$a0", 32),
+                                                  il::expr_const(1, 32)).unwrap());
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x1000, control_flow_graph));
+
+    // 1: call 0x1000, then $t0 = 0
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.call(il::expr_const(0x1000, 32), None);
+        block.assign(il::scalar("$t0", 32), il::expr_const(0, 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x2000, control_flow_graph));
+
+    // 2: call $t9
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().call(il::expr_scalar("$t9", 32), None);
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x3000, control_flow_graph));
+
+    // 3: save $s0 to the stack, $s0 = 0, then restore $s0
+    let slot = || il::Expression::add(il::expr_scalar("$sp", 32),
+                                      il::expr_const(4, 32)).unwrap();
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("$sp", 32),
+                     il::Expression::sub(il::expr_scalar("$sp", 32),
+                                         il::expr_const(8, 32)).unwrap());
+        block.store(slot(), il::expr_scalar("$s0", 32));
+        block.assign(il::scalar("$s0", 32), il::expr_const(0, 32));
+        block.load(il::scalar("$s0", 32), slot());
+        block.assign(il::scalar("$sp", 32),
+                     il::Expression::add(il::expr_scalar("$sp", 32),
+                                         il::expr_const(8, 32)).unwrap());
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x4000, control_flow_graph));
+
+    // 4: call 0x4000
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().call(il::expr_const(0x4000, 32), None);
+    control_flow_graph.set_entry(0).unwrap();
+    program.add_function(il::Function::new(0x5000, control_flow_graph));
+
+    let summaries = program.compute_summaries(&calling_convention);
+
+    let set = |names: &[&str]| names.iter()
+                                    .map(|name| il::scalar(*name, 32))
+                                    .collect::<BTreeSet<il::Scalar>>();
+    assert_eq!(summaries[&0].clobbered, set(&["$v0"]));
+    assert_eq!(summaries[&0].reads, set(&["$a0"]));
+    assert!(!summaries[&0].preserved.contains(&il::scalar("$v0", 32)));
+    assert!(summaries[&0].preserved.contains(&il::scalar("$v1", 32)));
+    assert!(summaries[&0].preserved.contains(&il::scalar("$s0", 32)));
+
+    assert_eq!(summaries[&1].clobbered, set(&["$v0", "$t0"]));
+    assert_eq!(summaries[&1].reads, set(&["$a0"]));
+
+    assert!(summaries[&2].clobbered.contains(&il::scalar("$v1", 32)));
+    assert!(summaries[&2].preserved.contains(&il::scalar("$s0", 32)));
+    assert!(!summaries[&2].preserved.contains(&il::scalar("$t0", 32)));
+
+    // Written, but restored to the value from entry
+    assert!(summaries[&3].clobbered.contains(&il::scalar("$s0", 32)));
+    assert!(summaries[&3].preserved.contains(&il::scalar("$s0", 32)));
+    assert!(summaries[&3].preserved.contains(&il::scalar("$sp", 32)));
+    assert!(summaries[&4].preserved.contains(&il::scalar("$s0", 32)));
+}
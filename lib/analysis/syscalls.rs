@@ -0,0 +1,46 @@
+//! Enumerates the system calls made by a function.
+
+use il;
+
+
+/// Find each `Raise` in this function which is a system call, with its system
+/// call number.
+///
+/// See `Operation::as_syscall` for which operations are system calls.
+pub fn syscall_sites(function: &il::Function) -> Vec<(il::ProgramLocation, u64)> {
+    let mut sites = Vec::new();
+    for block in function.blocks() {
+        for instruction in block.instructions_of_kind(il::OperationKind::Raise) {
+            if let Some(number) = instruction.operation().as_syscall() {
+                let location = il::RefProgramLocation::new(
+                    function,
+                    il::RefFunctionLocation::Instruction(block, instruction)
+                );
+                sites.push((location.into(), number));
+            }
+        }
+    }
+    sites
+}
+
+
+#[test]
+fn syscall_sites_test() {
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("v0", 32), il::expr_const(4001, 32));
+        block.raise(il::expr_const(4001, 32));
+        block.raise(il::expr_scalar("v0", 32));
+    }
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.raise(il::expr_const(60, 64));
+    }
+    let function = il::Function::new(0, control_flow_graph);
+
+    assert_eq!(syscall_sites(&function), vec![
+        (il::ProgramLocation::new(None, il::FunctionLocation::Instruction(0, 1)), 4001),
+        (il::ProgramLocation::new(None, il::FunctionLocation::Instruction(1, 0)), 60)
+    ]);
+}
@@ -0,0 +1,189 @@
+//! Tracks which scalars hold values derived from a set of source scalars.
+
+use analysis::fixed_point;
+use error::*;
+use il;
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::{BTreeMap, BTreeSet};
+
+
+/// Compute the scalars which are tainted after each location in the function,
+/// given the scalars tainted at function entry.
+///
+/// A scalar assigned or loaded from an `Expression` which `is_tainted` becomes
+/// tainted, and any other write to a scalar clears its taint. Memory is not
+/// tracked, so a `Load` is tainted only through its index, and the results of
+/// an `Intrinsic` are tainted when any of its arguments are.
+pub fn taint_analysis(function: &il::Function, sources: &BTreeSet<il::Scalar>)
+-> Result<BTreeMap<il::ProgramLocation, BTreeSet<il::Scalar>>> {
+    let analysis = TaintAnalysis { sources: sources.clone() };
+    let states = fixed_point::fixed_point_forward(analysis, function)?;
+
+    Ok(states.into_iter()
+             .map(|(location, state)| (location.into(), state.0))
+             .collect())
+}
+
+
+/// The tainted scalars at a location, ordered by inclusion.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct TaintSet(BTreeSet<il::Scalar>);
+
+
+impl PartialOrd for TaintSet {
+    fn partial_cmp(&self, other: &TaintSet) -> Option<Ordering> {
+        if self.0 == other.0 {
+            Some(Ordering::Equal)
+        }
+        else if self.0.is_subset(&other.0) {
+            Some(Ordering::Less)
+        }
+        else if self.0.is_superset(&other.0) {
+            Some(Ordering::Greater)
+        }
+        else {
+            None
+        }
+    }
+}
+
+
+struct TaintAnalysis {
+    sources: BTreeSet<il::Scalar>
+}
+
+
+impl<'f> fixed_point::FixedPointAnalysis<'f, TaintSet> for TaintAnalysis {
+    fn trans(&self, location: il::RefProgramLocation<'f>, state: Option<TaintSet>)
+        -> Result<TaintSet> {
+
+        let mut state = state.map(|state| state.0).unwrap_or_default();
+
+        // The sources are tainted at function entry, even when the entry is
+        // also reached from inside the function
+        let entry = location.function().control_flow_graph().entry();
+        let at_entry = match *location.function_location() {
+            il::RefFunctionLocation::Instruction(block, instruction) =>
+                Some(block.index()) == entry
+                && block.instructions()
+                        .first()
+                        .map(|first| first.index()) == Some(instruction.index()),
+            il::RefFunctionLocation::EmptyBlock(block) => Some(block.index()) == entry,
+            il::RefFunctionLocation::Edge(_) => false
+        };
+        if at_entry {
+            state.extend(self.sources.iter().cloned());
+        }
+
+        let instruction = match location.instruction() {
+            Some(instruction) => instruction,
+            None => return Ok(TaintSet(state))
+        };
+
+        let tainted = match *instruction.operation() {
+            il::Operation::Assign { ref src, .. } => src.is_tainted(&state),
+            il::Operation::Load { ref index, .. } => index.is_tainted(&state),
+            il::Operation::Intrinsic { ref arguments, .. } =>
+                arguments.iter().any(|argument| argument.is_tainted(&state)),
+            _ => false
+        };
+
+        for scalar in instruction.scalars_written() {
+            if tainted {
+                state.insert(scalar.clone());
+            }
+            else {
+                state.remove(scalar);
+            }
+        }
+
+        Ok(TaintSet(state))
+    }
+
+    fn join(&self, state0: TaintSet, state1: &TaintSet) -> Result<TaintSet> {
+        Ok(TaintSet(state0.0.union(&state1.0).cloned().collect()))
+    }
+}
+
+
+#[test]
+fn taint_analysis_test() {
+    /*
+    b = a + 1
+    c = [b]
+    d = e
+    a = 0
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("b", 32),
+                     il::Expression::add(il::expr_scalar("a", 32), il::expr_const(1, 32)).unwrap());
+        block.load(il::scalar("c", 32), il::expr_scalar("b", 32));
+        block.assign(il::scalar("d", 32), il::expr_scalar("e", 32));
+        block.assign(il::scalar("a", 32), il::expr_const(0, 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    let function = il::Function::new(0, control_flow_graph);
+
+    let sources = vec![il::scalar("a", 32)].into_iter().collect();
+    let taint = taint_analysis(&function, &sources).unwrap();
+
+    let tainted_after = |instruction_index: u64| {
+        let block = function.block(0).unwrap();
+        let location = il::RefProgramLocation::new(
+            &function,
+            il::RefFunctionLocation::Instruction(block, block.instruction(instruction_index).unwrap())
+        );
+        taint[&location.into()].iter()
+                               .map(|scalar| scalar.name())
+                               .collect::<Vec<&str>>()
+    };
+
+    assert_eq!(tainted_after(0), vec!["a", "b"]);
+    assert_eq!(tainted_after(1), vec!["a", "b", "c"]);
+    assert_eq!(tainted_after(2), vec!["a", "b", "c"]);
+    assert_eq!(tainted_after(3), vec!["b", "c"]);
+
+    assert!(!il::expr_scalar("c", 32).is_tainted(&sources));
+    assert!(il::Expression::add(il::expr_scalar("c", 32), il::expr_scalar("a", 32))
+        .unwrap()
+        .is_tainted(&sources));
+}
+
+
+#[test]
+fn taint_analysis_entry_loop() {
+    /*
+    entry: b = a
+           a = 0
+           goto entry
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    let index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("b", 32), il::expr_scalar("a", 32));
+        block.assign(il::scalar("a", 32), il::expr_const(0, 32));
+        block.index()
+    };
+    control_flow_graph.unconditional_edge(index, index).unwrap();
+    control_flow_graph.set_entry(index).unwrap();
+    let function = il::Function::new(0, control_flow_graph);
+
+    let sources = vec![il::scalar("a", 32)].into_iter().collect();
+    let taint = taint_analysis(&function, &sources).unwrap();
+
+    let tainted_after = |instruction_index: u64| {
+        let block = function.block(index).unwrap();
+        let location = il::RefProgramLocation::new(
+            &function,
+            il::RefFunctionLocation::Instruction(block, block.instruction(instruction_index).unwrap())
+        );
+        taint[&location.into()].iter()
+                               .map(|scalar| scalar.name())
+                               .collect::<Vec<&str>>()
+    };
+
+    assert_eq!(tainted_after(0), vec!["a", "b"]);
+    assert_eq!(tainted_after(1), vec!["b"]);
+}
@@ -1,28 +1,26 @@
 use analysis::{LocationSet, reaching_definitions};
 use error::*;
 use il;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 
 #[allow(dead_code)]
 /// Compute use definition chains for the given function.
 pub fn use_def<'r>(function: &'r il::Function)
 -> Result<HashMap<il::RefProgramLocation<'r>, LocationSet<'r>>> {
-    let rd = reaching_definitions::reaching_definitions(function)?;
+    let rd = reaching_definitions::reaching_definitions_by_scalar(function)?;
 
     let mut ud = HashMap::new();
 
     for (location, _) in &rd {
+        // the definitions reaching each location are read before it executes
+        let reaching = reaching_definitions::reaching_before(&rd, location)?;
         let defs = match *location.function_location() {
             il::RefFunctionLocation::Instruction(_, ref instruction) => {
                 let mut defs = LocationSet::new();
                 for scalar_read in instruction.operation().scalars_read() {
-                    for rd in rd[&location].locations() {
-                        if rd.instruction()
-                             .unwrap()
-                             .operation()
-                             .scalar_written()
-                             .unwrap() == scalar_read {
+                    if let Some(definitions) = reaching.get(scalar_read) {
+                        for rd in definitions.locations() {
                             defs.insert(rd.clone());
                         }
                     }
@@ -33,12 +31,8 @@ pub fn use_def<'r>(function: &'r il::Function)
                 let mut defs = LocationSet::new();
                 if let Some(ref condition) = *edge.condition() {
                     for scalar_read in condition.scalars() {
-                        for rd in rd[&location].locations() {
-                            if rd.instruction()
-                                 .unwrap()
-                                 .operation()
-                                 .scalar_written()
-                                 .unwrap() == scalar_read {
+                        if let Some(definitions) = reaching.get(scalar_read) {
+                            for rd in definitions.locations() {
                                 defs.insert(rd.clone());
                             }
                         }
@@ -56,6 +50,114 @@ pub fn use_def<'r>(function: &'r il::Function)
 
 
 
+type Chains = BTreeMap<il::ProgramLocation, BTreeMap<il::Scalar, BTreeSet<il::ProgramLocation>>>;
+
+
+/// Use-definition and definition-use chains for a function, keyed by the
+/// scalar each use reads.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UseDefChains {
+    definitions: Chains,
+    uses: Chains
+}
+
+
+impl UseDefChains {
+    /// Compute the chains for the given function.
+    pub fn new(function: &il::Function) -> Result<UseDefChains> {
+        let rd = reaching_definitions::reaching_definitions_by_scalar(function)?;
+
+        let mut definitions: Chains = BTreeMap::new();
+        let mut uses: Chains = BTreeMap::new();
+
+        for location in rd.keys() {
+            let reaching = reaching_definitions::reaching_before(&rd, location)?;
+            let scalars_read = match *location.function_location() {
+                il::RefFunctionLocation::Instruction(_, ref instruction) =>
+                    instruction.operation().scalars_read(),
+                il::RefFunctionLocation::Edge(ref edge) => match *edge.condition() {
+                    Some(ref condition) => condition.scalars(),
+                    None => Vec::new()
+                },
+                il::RefFunctionLocation::EmptyBlock(_) => Vec::new()
+            };
+
+            let use_location: il::ProgramLocation = location.clone().into();
+            for scalar_read in scalars_read {
+                let scalar_definitions = match reaching.get(scalar_read) {
+                    Some(scalar_definitions) => scalar_definitions,
+                    None => continue
+                };
+                for rd in scalar_definitions.locations() {
+                    let def_location: il::ProgramLocation = rd.clone().into();
+                    definitions.entry(use_location.clone())
+                               .or_insert_with(BTreeMap::new)
+                               .entry(scalar_read.clone())
+                               .or_insert_with(BTreeSet::new)
+                               .insert(def_location.clone());
+                    uses.entry(def_location)
+                        .or_insert_with(BTreeMap::new)
+                        .entry(scalar_read.clone())
+                        .or_insert_with(BTreeSet::new)
+                        .insert(use_location.clone());
+                }
+            }
+        }
+
+        Ok(UseDefChains {
+            definitions: definitions,
+            uses: uses
+        })
+    }
+
+    /// Get the definitions reaching the given location, of any scalar read
+    /// there.
+    pub fn definitions_of(&self, location: &il::ProgramLocation) -> Vec<il::ProgramLocation> {
+        UseDefChains::all(&self.definitions, location)
+    }
+
+    /// Get the definitions of the given scalar reaching the given location.
+    pub fn scalar_definitions_of(&self, location: &il::ProgramLocation, scalar: &il::Scalar)
+        -> Vec<il::ProgramLocation> {
+
+        UseDefChains::scalar(&self.definitions, location, scalar)
+    }
+
+    /// Get the uses reached by the definition at the given location, of any
+    /// scalar it defines.
+    pub fn uses_of(&self, location: &il::ProgramLocation) -> Vec<il::ProgramLocation> {
+        UseDefChains::all(&self.uses, location)
+    }
+
+    /// Get the uses of the given scalar reached by the definition at the
+    /// given location.
+    pub fn scalar_uses_of(&self, location: &il::ProgramLocation, scalar: &il::Scalar)
+        -> Vec<il::ProgramLocation> {
+
+        UseDefChains::scalar(&self.uses, location, scalar)
+    }
+
+    fn all(chains: &Chains, location: &il::ProgramLocation) -> Vec<il::ProgramLocation> {
+        let mut locations = BTreeSet::new();
+        if let Some(scalars) = chains.get(location) {
+            for scalar_locations in scalars.values() {
+                locations.extend(scalar_locations.iter().cloned());
+            }
+        }
+        locations.into_iter().collect()
+    }
+
+    fn scalar(chains: &Chains, location: &il::ProgramLocation, scalar: &il::Scalar)
+        -> Vec<il::ProgramLocation> {
+
+        chains.get(location)
+              .and_then(|scalars| scalars.get(scalar))
+              .map(|locations| locations.iter().cloned().collect())
+              .unwrap_or_default()
+    }
+}
+
+
 #[test]
 fn use_def_test() {
     /*
@@ -199,4 +301,163 @@ fn use_def_test() {
             block.instruction(0).unwrap()
         )
     )].len() == 2);
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn use_def_chains() {
+    /*
+    a = in
+    if a < 10 {
+        c = a
+    }
+    else {
+        c = 4
+    }
+    d = c + a
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("a", 32), il::expr_scalar("in", 32));
+        block.index()
+    };
+
+    let lt_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("c", 32), il::expr_scalar("a", 32));
+        block.index()
+    };
+
+    let gt_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("c", 32), il::expr_const(4, 32));
+        block.index()
+    };
+
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("d", 32),
+                     il::Expression::add(il::expr_scalar("c", 32),
+                                         il::expr_scalar("a", 32)).unwrap());
+        block.index()
+    };
+
+    let condition = il::Expression::cmpltu(
+        il::expr_scalar("a", 32),
+        il::expr_const(10, 32)
+    ).unwrap();
+
+    control_flow_graph.conditional_edge(head_index, lt_index, condition.clone()).unwrap();
+    control_flow_graph.conditional_edge(head_index, gt_index,
+        il::Expression::cmpeq(condition, il::expr_const(0, 1)).unwrap()
+    ).unwrap();
+
+    control_flow_graph.unconditional_edge(lt_index, tail_index).unwrap();
+    control_flow_graph.unconditional_edge(gt_index, tail_index).unwrap();
+
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+
+    let chains = UseDefChains::new(&function).unwrap();
+
+    let location = |index| {
+        let block = function.control_flow_graph().block(index).unwrap();
+        il::ProgramLocation::from(il::RefProgramLocation::new(
+            &function,
+            il::RefFunctionLocation::Instruction(block, block.instruction(0).unwrap())
+        ))
+    };
+
+    let merge = location(tail_index);
+
+    let c_definitions = chains.scalar_definitions_of(&merge, &il::scalar("c", 32));
+    assert_eq!(c_definitions.len(), 2);
+    assert!(c_definitions.contains(&location(lt_index)));
+    assert!(c_definitions.contains(&location(gt_index)));
+
+    assert_eq!(chains.scalar_definitions_of(&merge, &il::scalar("a", 32)),
+               vec![location(head_index)]);
+    assert_eq!(chains.definitions_of(&merge).len(), 3);
+
+    assert_eq!(chains.uses_of(&location(lt_index)), vec![merge.clone()]);
+    assert_eq!(chains.uses_of(&location(gt_index)), vec![merge.clone()]);
+
+    // a is read by both edge conditions, the copy into c, and the merge
+    assert!(chains.uses_of(&location(head_index)).contains(&merge));
+    assert_eq!(chains.scalar_uses_of(&location(head_index), &il::scalar("a", 32)).len(), 4);
+    assert!(chains.scalar_uses_of(&location(head_index), &il::scalar("c", 32)).is_empty());
+}
+
+
+#[test]
+fn use_def_chains_redefinition() {
+    /*
+    a = 0
+    a = a + 1
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("a", 32), il::expr_const(0, 32));
+        block.assign(il::scalar("a", 32),
+                     il::Expression::add(il::expr_scalar("a", 32), il::expr_const(1, 32)).unwrap());
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    let function = il::Function::new(0, control_flow_graph);
+
+    let chains = UseDefChains::new(&function).unwrap();
+
+    let location = |index| il::ProgramLocation::new(None, il::FunctionLocation::Instruction(0, index));
+    assert_eq!(chains.scalar_definitions_of(&location(1), &il::scalar("a", 32)),
+               vec![location(0)]);
+    assert_eq!(chains.uses_of(&location(0)), vec![location(1)]);
+    assert!(chains.uses_of(&location(1)).is_empty());
+}
+
+
+#[test]
+fn use_def_chains_intrinsic_results() {
+    /*
+    eax, ebx = cpuid()
+    eax = 0
+    [0] = eax
+    [4] = ebx
+    */
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.intrinsic("cpuid", Vec::new(), vec![il::scalar("eax", 32), il::scalar("ebx", 32)]);
+        block.assign(il::scalar("eax", 32), il::expr_const(0, 32));
+        block.store(il::expr_const(0, 32), il::expr_scalar("eax", 32));
+        block.store(il::expr_const(4, 32), il::expr_scalar("ebx", 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    let function = il::Function::new(0, control_flow_graph);
+
+    let location = |index| il::ProgramLocation::new(None, il::FunctionLocation::Instruction(0, index));
+
+    // Overwriting eax kills the intrinsic's definition of eax, but not of ebx
+    let chains = UseDefChains::new(&function).unwrap();
+    assert_eq!(chains.scalar_definitions_of(&location(2), &il::scalar("eax", 32)),
+               vec![location(1)]);
+    assert_eq!(chains.scalar_definitions_of(&location(3), &il::scalar("ebx", 32)),
+               vec![location(0)]);
+    assert_eq!(chains.uses_of(&location(0)), vec![location(3)]);
+
+    let block = function.block(0).unwrap();
+    let ref_location = |index| il::RefProgramLocation::new(&function,
+        il::RefFunctionLocation::Instruction(block, block.instruction(index).unwrap()));
+
+    let ud = use_def(&function).unwrap();
+    let definitions = ud[&ref_location(2)].locations();
+    assert_eq!(definitions.len(), 1);
+    assert!(definitions.contains(&ref_location(1)));
+
+    let du = ::analysis::def_use(&function).unwrap();
+    let uses = du[&ref_location(0)].locations();
+    assert_eq!(uses.len(), 1);
+    assert!(uses.contains(&ref_location(3)));
+}
@@ -0,0 +1,655 @@
+//! A value-set analysis which bounds the values of scalars by strided
+//! intervals.
+
+use analysis::edge_constraints::edge_constraints;
+use analysis::fixed_point;
+use error::*;
+use il;
+use std::cell::RefCell;
+use std::cmp::{Ordering, PartialOrd};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+
+/// The number of times a location in a loop header is visited before its
+/// values are widened.
+const WIDEN_AFTER: usize = 3;
+
+/// The number of times any location is visited before its values are widened,
+/// for cycles without a natural loop header.
+const WIDEN_ALWAYS_AFTER: usize = 64;
+
+
+/// Bound the values of scalars after each location in the function.
+///
+/// Scalars which are not in the map for a location, such as arguments, may
+/// hold any value. Conditions on edges which compare a scalar to a constant
+/// narrow the values of that scalar, and locations which are only reached
+/// along edges whose conditions can not hold are not in the result.
+pub fn value_sets(function: &il::Function)
+-> Result<BTreeMap<il::ProgramLocation, BTreeMap<il::Scalar, StridedInterval>>> {
+    let loop_headers = function.control_flow_graph()
+                               .natural_loops()?
+                               .iter()
+                               .map(|natural_loop| natural_loop.header())
+                               .collect();
+
+    let analysis = ValueSetAnalysis {
+        constraints: edge_constraints(function),
+        loop_headers: loop_headers,
+        visits: RefCell::new(HashMap::new())
+    };
+    let states = fixed_point::fixed_point_forward_options(analysis, function, true)?;
+
+    Ok(states.into_iter()
+             .filter_map(|(location, state)| match state {
+                 ValueSetState::Values(values) => Some((location.into(), values)),
+                 ValueSetState::Unreachable => None
+             })
+             .collect())
+}
+
+
+fn gcd(lhs: u64, rhs: u64) -> u64 {
+    if rhs == 0 { lhs } else { gcd(rhs, lhs % rhs) }
+}
+
+
+fn mask(bits: usize) -> u64 {
+    if bits >= 64 { u64::max_value() } else { (1 << bits) - 1 }
+}
+
+
+/// A set of unsigned values of `bits` bits, from `lower` to `upper` in steps
+/// of `stride`.
+///
+/// Arithmetic wraps at `bits`, as in Falcon IL. A result which may wrap can
+/// not be represented by one interval, and is `Top`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum StridedInterval {
+    /// Any value of the given number of bits.
+    Top(usize),
+    /// The values `lower`, `lower + stride`, ..., `upper`. The stride is 0
+    /// when `lower == upper`.
+    Interval { stride: u64, lower: u64, upper: u64, bits: usize }
+}
+
+
+impl StridedInterval {
+    /// Create a `StridedInterval`.
+    ///
+    /// `upper` is rounded down to `lower` plus a multiple of `stride`. Invalid
+    /// bounds, or bounds which include every value of `bits` bits, give
+    /// `Top`.
+    pub fn new(stride: u64, lower: u64, upper: u64, bits: usize) -> StridedInterval {
+        if bits > 64 || lower > upper || upper > mask(bits) {
+            return StridedInterval::Top(bits);
+        }
+        let stride = if stride == 0 { 1 } else { stride };
+        let upper = lower + (upper - lower) / stride * stride;
+        let stride = if lower == upper { 0 } else { stride };
+        if stride == 1 && lower == 0 && upper == mask(bits) {
+            return StridedInterval::Top(bits);
+        }
+        StridedInterval::Interval {
+            stride: stride,
+            lower: lower,
+            upper: upper,
+            bits: bits
+        }
+    }
+
+    /// Create a `StridedInterval` holding only the given constant.
+    pub fn constant(constant: &il::Constant) -> StridedInterval {
+        match constant.value_u64() {
+            Ok(value) => StridedInterval::new(0, value, value, constant.bits()),
+            Err(_) => StridedInterval::Top(constant.bits())
+        }
+    }
+
+    /// Get the size of the values of this `StridedInterval` in bits.
+    pub fn bits(&self) -> usize {
+        match *self {
+            StridedInterval::Top(bits) |
+            StridedInterval::Interval { bits, .. } => bits
+        }
+    }
+
+    /// Returns true if this `StridedInterval` may hold any value.
+    pub fn is_top(&self) -> bool {
+        match *self {
+            StridedInterval::Top(_) => true,
+            StridedInterval::Interval { .. } => false
+        }
+    }
+
+    /// Returns true if the given value is in this `StridedInterval`.
+    pub fn contains(&self, value: u64) -> bool {
+        match *self {
+            StridedInterval::Top(bits) => value <= mask(bits),
+            StridedInterval::Interval { stride, lower, upper, .. } =>
+                value >= lower
+                && value <= upper
+                && (stride == 0 || (value - lower) % stride == 0)
+        }
+    }
+
+    /// Returns the bounds of this `StridedInterval`, treating `Top` as every
+    /// value in steps of 1.
+    fn bounds(&self) -> (u64, u64, u64) {
+        match *self {
+            StridedInterval::Top(bits) => (1, 0, mask(bits)),
+            StridedInterval::Interval { stride, lower, upper, .. } => (stride, lower, upper)
+        }
+    }
+
+    /// Returns the value of this `StridedInterval` if it holds only one.
+    fn singleton(&self) -> Option<u64> {
+        match *self {
+            StridedInterval::Interval { lower, upper, .. } if lower == upper => Some(lower),
+            _ => None
+        }
+    }
+
+    /// Returns true if every value of this `StridedInterval` is in other.
+    pub fn is_subset_of(&self, other: &StridedInterval) -> bool {
+        match (self, other) {
+            (_, &StridedInterval::Top(_)) => true,
+            (&StridedInterval::Top(_), _) => false,
+            (&StridedInterval::Interval { stride, lower, upper, .. },
+             &StridedInterval::Interval { stride: ostride, lower: olower, upper: oupper, .. }) =>
+                lower >= olower
+                && upper <= oupper
+                && if ostride == 0 {
+                    lower == olower && upper == oupper
+                }
+                else {
+                    stride % ostride == 0 && (lower - olower) % ostride == 0
+                }
+        }
+    }
+
+    /// Join two `StridedInterval` into the smallest one holding both.
+    pub fn join(&self, other: &StridedInterval) -> StridedInterval {
+        if self.is_top() || other.is_top() {
+            return StridedInterval::Top(self.bits());
+        }
+        let (stride, lower, upper) = self.bounds();
+        let (ostride, olower, oupper) = other.bounds();
+        let difference = if lower > olower { lower - olower } else { olower - lower };
+        StridedInterval::new(gcd(gcd(stride, ostride), difference),
+                             lower.min(olower),
+                             upper.max(oupper),
+                             self.bits())
+    }
+
+    /// Join other into this `StridedInterval`, moving any bound which changes
+    /// to the furthest value with the same stride.
+    pub fn widen(&self, other: &StridedInterval) -> StridedInterval {
+        let joined = self.join(other);
+        let (stride, lower, upper) = joined.bounds();
+        if stride == 0 || joined.is_top() {
+            return joined;
+        }
+        let (_, previous_lower, previous_upper) = self.bounds();
+        let lower = if lower < previous_lower { lower % stride } else { lower };
+        let upper = if upper > previous_upper { mask(self.bits()) } else { upper };
+        StridedInterval::new(stride, lower, upper, self.bits())
+    }
+
+    /// Keep only the values of this `StridedInterval` from lower to upper, or
+    /// return `None` if no values remain.
+    fn narrow(&self, lower: u64, upper: u64) -> Option<StridedInterval> {
+        let (stride, self_lower, self_upper) = self.bounds();
+        let step = u128::from(stride.max(1));
+        let new_lower = if self_lower >= lower {
+            u128::from(self_lower)
+        }
+        else {
+            let steps = (u128::from(lower - self_lower) + step - 1) / step;
+            u128::from(self_lower) + steps * step
+        };
+        let new_upper = u128::from(self_upper.min(upper));
+        if new_lower > new_upper {
+            return None;
+        }
+        Some(StridedInterval::new(stride, new_lower as u64, new_upper as u64, self.bits()))
+    }
+
+    /// Apply op to two singletons, wrapping the result at bits.
+    fn wrapping<F>(&self, other: &StridedInterval, op: F) -> Option<StridedInterval>
+    where F: Fn(u64, u64) -> u64 {
+        match (self.singleton(), other.singleton()) {
+            (Some(lhs), Some(rhs)) => {
+                let value = op(lhs, rhs) & mask(self.bits());
+                Some(StridedInterval::new(0, value, value, self.bits()))
+            },
+            _ => None
+        }
+    }
+
+    /// Add two `StridedInterval`.
+    pub fn add(&self, other: &StridedInterval) -> StridedInterval {
+        if let Some(result) = self.wrapping(other, u64::wrapping_add) {
+            return result;
+        }
+        if self.is_top() || other.is_top() {
+            return StridedInterval::Top(self.bits());
+        }
+        let (stride, lower, upper) = self.bounds();
+        let (ostride, olower, oupper) = other.bounds();
+        if u128::from(upper) + u128::from(oupper) > u128::from(mask(self.bits())) {
+            return StridedInterval::Top(self.bits());
+        }
+        StridedInterval::new(gcd(stride, ostride), lower + olower, upper + oupper, self.bits())
+    }
+
+    /// Subtract other from this `StridedInterval`.
+    pub fn sub(&self, other: &StridedInterval) -> StridedInterval {
+        if let Some(result) = self.wrapping(other, u64::wrapping_sub) {
+            return result;
+        }
+        if self.is_top() || other.is_top() {
+            return StridedInterval::Top(self.bits());
+        }
+        let (stride, lower, upper) = self.bounds();
+        let (ostride, olower, oupper) = other.bounds();
+        if lower < oupper {
+            return StridedInterval::Top(self.bits());
+        }
+        StridedInterval::new(gcd(stride, ostride), lower - oupper, upper - olower, self.bits())
+    }
+
+    /// Multiply two `StridedInterval`.
+    pub fn mul(&self, other: &StridedInterval) -> StridedInterval {
+        if let Some(result) = self.wrapping(other, u64::wrapping_mul) {
+            return result;
+        }
+        if self.is_top() || other.is_top() {
+            return StridedInterval::Top(self.bits());
+        }
+        let (stride, lower, upper) = self.bounds();
+        let (ostride, olower, oupper) = other.bounds();
+        if u128::from(upper) * u128::from(oupper) > u128::from(mask(self.bits())) {
+            return StridedInterval::Top(self.bits());
+        }
+        let stride = match (self.singleton(), other.singleton()) {
+            (_, Some(value)) => stride * value,
+            (Some(value), _) => ostride * value,
+            (None, None) => 1
+        };
+        StridedInterval::new(stride, lower * olower, upper * oupper, self.bits())
+    }
+
+    /// Negate this `StridedInterval`, in two's complement.
+    ///
+    /// Negating 0 gives 0, but any other value wraps to the top of the range,
+    /// so an interval holding 0 and other values gives `Top`.
+    pub fn neg(&self) -> StridedInterval {
+        if self.is_top() {
+            return StridedInterval::Top(self.bits());
+        }
+        let (stride, lower, upper) = self.bounds();
+        if upper == 0 {
+            self.clone()
+        }
+        else if lower == 0 {
+            StridedInterval::Top(self.bits())
+        }
+        else {
+            let mask = mask(self.bits());
+            StridedInterval::new(stride, mask - upper + 1, mask - lower + 1, self.bits())
+        }
+    }
+
+    /// Evaluate an expression, given the values of its scalars.
+    ///
+    /// Scalars not in values may hold any value. Only `Add`, `Sub`, `Mul`,
+    /// `And` and `Neg` are evaluated, and other operators give `Top`.
+    pub fn eval(expression: &il::Expression, values: &BTreeMap<il::Scalar, StridedInterval>)
+    -> StridedInterval {
+        let eval = |expression| StridedInterval::eval(expression, values);
+        match *expression {
+            il::Expression::Scalar(ref scalar) =>
+                values.get(scalar)
+                      .cloned()
+                      .unwrap_or_else(|| StridedInterval::Top(scalar.bits())),
+            il::Expression::Constant(ref constant) => StridedInterval::constant(constant),
+            il::Expression::Add(ref lhs, ref rhs) => eval(lhs).add(&eval(rhs)),
+            il::Expression::Sub(ref lhs, ref rhs) => eval(lhs).sub(&eval(rhs)),
+            il::Expression::Mul(ref lhs, ref rhs) => eval(lhs).mul(&eval(rhs)),
+            il::Expression::And(ref lhs, ref rhs) => eval(lhs).and(&eval(rhs)),
+            il::Expression::Neg(ref src) => eval(src).neg(),
+            _ => StridedInterval::Top(expression.bits())
+        }
+    }
+
+    /// Get every value of this `StridedInterval`, or `None` if there are more
+    /// than max.
+    pub fn values(&self, max: usize) -> Option<Vec<u64>> {
+        let (stride, lower, upper) = self.bounds();
+        let count = if stride == 0 { 1 } else { ((upper - lower) / stride).saturating_add(1) };
+        if count > max as u64 {
+            return None;
+        }
+        Some((0..count).map(|n| lower + n * stride).collect())
+    }
+
+    /// And two `StridedInterval`.
+    ///
+    /// The result is no greater than either operand.
+    pub fn and(&self, other: &StridedInterval) -> StridedInterval {
+        if let Some(result) = self.wrapping(other, |lhs, rhs| lhs & rhs) {
+            return result;
+        }
+        let (_, _, upper) = self.bounds();
+        let (_, _, oupper) = other.bounds();
+        StridedInterval::new(1, 0, upper.min(oupper), self.bits())
+    }
+}
+
+
+/// The values of scalars at a location. Scalars not in the map may hold any
+/// value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ValueSetState {
+    /// No path to this location is feasible.
+    Unreachable,
+    Values(BTreeMap<il::Scalar, StridedInterval>)
+}
+
+
+impl ValueSetState {
+    /// Get the values of a scalar.
+    fn get(&self, scalar: &il::Scalar) -> StridedInterval {
+        match *self {
+            ValueSetState::Values(ref values) => values.get(scalar).cloned(),
+            ValueSetState::Unreachable => None
+        }.unwrap_or_else(|| StridedInterval::Top(scalar.bits()))
+    }
+
+    /// Set the values of a scalar, unless this location is unreachable.
+    fn set(&mut self, scalar: il::Scalar, values: StridedInterval) {
+        if let ValueSetState::Values(ref mut state_values) = *self {
+            state_values.insert(scalar, values);
+        }
+    }
+
+    /// Returns true if every scalar may hold at least the values in self in
+    /// other.
+    fn is_subset_of(&self, other: &ValueSetState) -> bool {
+        match (self, other) {
+            (&ValueSetState::Unreachable, _) => true,
+            (_, &ValueSetState::Unreachable) => false,
+            (_, &ValueSetState::Values(ref other_values)) =>
+                other_values.iter()
+                            .all(|(scalar, values)| self.get(scalar).is_subset_of(values))
+        }
+    }
+
+    /// Combine the values of scalars in both states with f. Scalars in only
+    /// one state may hold any value.
+    fn combine<F>(&self, other: &ValueSetState, f: F) -> ValueSetState
+    where F: Fn(&StridedInterval, &StridedInterval) -> StridedInterval {
+        match (self, other) {
+            (&ValueSetState::Unreachable, _) => other.clone(),
+            (_, &ValueSetState::Unreachable) => self.clone(),
+            (&ValueSetState::Values(ref values), &ValueSetState::Values(ref other_values)) =>
+                ValueSetState::Values(
+                    values.iter()
+                          .filter_map(|(scalar, lhs)| {
+                              other_values.get(scalar)
+                                          .map(|rhs| (scalar.clone(), f(lhs, rhs)))
+                          })
+                          .collect())
+        }
+    }
+}
+
+
+impl PartialOrd for ValueSetState {
+    fn partial_cmp(&self, other: &ValueSetState) -> Option<Ordering> {
+        match (self.is_subset_of(other), other.is_subset_of(self)) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None
+        }
+    }
+}
+
+
+struct ValueSetAnalysis {
+    constraints: BTreeMap<(u64, u64), Vec<il::Expression>>,
+    loop_headers: BTreeSet<u64>,
+    // The number of visits to, and the last state after, each location
+    visits: RefCell<HashMap<il::ProgramLocation, (usize, ValueSetState)>>
+}
+
+
+impl ValueSetAnalysis {
+    /// Narrow the values of scalars in state to those for which condition
+    /// evaluates to holds.
+    fn narrow(&self, state: &mut ValueSetState, condition: &il::Expression, holds: bool) {
+        use il::Expression::*;
+
+        let mut narrow_scalar = |scalar: &il::Scalar, lower: u64, upper: u64| {
+            match state.get(scalar).narrow(lower, upper) {
+                Some(values) => state.set(scalar.clone(), values),
+                None => *state = ValueSetState::Unreachable
+            }
+        };
+
+        match *condition {
+            Scalar(ref scalar) if scalar.bits() == 1 => {
+                let value = if holds { 1 } else { 0 };
+                narrow_scalar(scalar, value, value);
+            },
+            Cmpltu(ref lhs, ref rhs) => match (&**lhs, &**rhs) {
+                (&Scalar(ref scalar), &Constant(ref constant)) => {
+                    let value = match constant.value_u64() { Ok(value) => value, Err(_) => return };
+                    if holds && value > 0 {
+                        narrow_scalar(scalar, 0, value - 1);
+                    }
+                    else if !holds {
+                        narrow_scalar(scalar, value, mask(scalar.bits()));
+                    }
+                },
+                (&Constant(ref constant), &Scalar(ref scalar)) => {
+                    let value = match constant.value_u64() { Ok(value) => value, Err(_) => return };
+                    if holds && value < mask(scalar.bits()) {
+                        narrow_scalar(scalar, value + 1, mask(scalar.bits()));
+                    }
+                    else if !holds {
+                        narrow_scalar(scalar, 0, value);
+                    }
+                },
+                _ => {}
+            },
+            Cmpeq(ref lhs, ref rhs) | Cmpneq(ref lhs, ref rhs) => {
+                let equal = match *condition { Cmpeq(_, _) => holds, _ => !holds };
+                match (&**lhs, &**rhs) {
+                    (&Scalar(ref scalar), &Constant(ref constant)) |
+                    (&Constant(ref constant), &Scalar(ref scalar)) => {
+                        if let (true, Ok(value)) = (equal, constant.value_u64()) {
+                            narrow_scalar(scalar, value, value);
+                        }
+                    },
+                    // A one-bit condition compared to 0 is its negation
+                    (inner, &Constant(ref constant)) if inner.bits() == 1 => {
+                        let inner_holds = (constant.value() == 1) == equal;
+                        self.narrow(state, inner, inner_holds);
+                    },
+                    _ => {}
+                }
+            },
+            Xor(ref lhs, ref rhs) if condition.bits() == 1 => match **rhs {
+                Constant(ref constant) => self.narrow(state, lhs, holds != (constant.value() == 1)),
+                _ => {}
+            },
+            And(ref lhs, ref rhs) if condition.bits() == 1 && holds => {
+                self.narrow(state, lhs, true);
+                self.narrow(state, rhs, true);
+            },
+            Or(ref lhs, ref rhs) if condition.bits() == 1 && !holds => {
+                self.narrow(state, lhs, false);
+                self.narrow(state, rhs, false);
+            },
+            _ => {}
+        }
+    }
+
+    /// Widen state against the last state after this location, once it has
+    /// been visited often enough.
+    fn widen(&self, location: &il::RefProgramLocation, state: ValueSetState) -> ValueSetState {
+        let in_loop_header = location.block()
+                                     .map(|block| self.loop_headers.contains(&block.index()))
+                                     .unwrap_or(false);
+
+        let key: il::ProgramLocation = location.clone().into();
+        let mut visits = self.visits.borrow_mut();
+        let (count, state) = match visits.get(&key) {
+            Some(&(count, ref previous)) => {
+                let widen = (in_loop_header && count >= WIDEN_AFTER)
+                            || count >= WIDEN_ALWAYS_AFTER;
+                if widen {
+                    (count + 1, previous.combine(&state, StridedInterval::widen))
+                }
+                else {
+                    (count + 1, state)
+                }
+            },
+            None => (1, state)
+        };
+        visits.insert(key, (count, state.clone()));
+        state
+    }
+}
+
+
+impl<'f> fixed_point::FixedPointAnalysis<'f, ValueSetState> for ValueSetAnalysis {
+    fn trans(&self, location: il::RefProgramLocation<'f>, state: Option<ValueSetState>)
+        -> Result<ValueSetState> {
+
+        let mut state = match state {
+            Some(state) => state,
+            None => ValueSetState::Values(BTreeMap::new())
+        };
+
+        match *location.function_location() {
+            il::RefFunctionLocation::Instruction(_, instruction) => match *instruction.operation() {
+                il::Operation::Assign { ref dst, ref src } => {
+                    if let ValueSetState::Values(ref mut values) = state {
+                        let value = StridedInterval::eval(src, values);
+                        values.insert(dst.clone(), value);
+                    }
+                },
+                _ => for scalar in instruction.scalars_written() {
+                    state.set(scalar.clone(), StridedInterval::Top(scalar.bits()));
+                }
+            },
+            il::RefFunctionLocation::Edge(edge) => {
+                if let Some(constraints) = self.constraints.get(&(edge.head(), edge.tail())) {
+                    for constraint in constraints {
+                        self.narrow(&mut state, constraint, true);
+                    }
+                }
+            },
+            il::RefFunctionLocation::EmptyBlock(_) => {}
+        }
+
+        Ok(self.widen(&location, state))
+    }
+
+    fn join(&self, state0: ValueSetState, state1: &ValueSetState) -> Result<ValueSetState> {
+        Ok(state0.combine(state1, StridedInterval::join))
+    }
+}
+
+
+#[test]
+fn strided_interval() {
+    let a = StridedInterval::new(4, 0, 40, 32);
+    let b = StridedInterval::constant(&il::const_(2, 32));
+    assert_eq!(a.add(&b), StridedInterval::new(4, 2, 42, 32));
+    assert_eq!(a.mul(&b), StridedInterval::new(8, 0, 80, 32));
+    assert_eq!(a.sub(&b), StridedInterval::Top(32));
+    assert_eq!(a.join(&b), StridedInterval::new(2, 0, 40, 32));
+    assert_eq!(StridedInterval::Top(32).and(&StridedInterval::constant(&il::const_(0xff, 32))),
+               StridedInterval::new(1, 0, 0xff, 32));
+    assert!(a.contains(36) && !a.contains(37) && !a.contains(44));
+
+    // Singletons wrap
+    let max = StridedInterval::constant(&il::const_(0xffff_ffff, 32));
+    assert_eq!(max.add(&StridedInterval::constant(&il::const_(1, 32))),
+               StridedInterval::constant(&il::const_(0, 32)));
+    assert_eq!(max.add(&a), StridedInterval::Top(32));
+
+    let negated = il::Expression::neg(il::expr_scalar("x", 32)).unwrap();
+    let mut values = BTreeMap::new();
+    values.insert(il::scalar("x", 32), StridedInterval::new(4, 4, 12, 32));
+    assert_eq!(StridedInterval::eval(&negated, &values),
+               StridedInterval::new(4, 0xffff_fff4, 0xffff_fffc, 32));
+    values.insert(il::scalar("x", 32), a.clone());
+    assert_eq!(StridedInterval::eval(&negated, &values), StridedInterval::Top(32));
+    assert_eq!(b.neg(), StridedInterval::constant(&il::const_(0xffff_fffe, 32)));
+}
+
+
+#[test]
+fn value_sets_test() {
+    /*
+    entry:  i = 0
+            j = x + 1
+    header: if i <u 40 goto body, else fall through to exit
+    body:   i = i + 4
+            goto header
+    exit:   k = i
+    */
+    let i = il::scalar("i", 32);
+    let mut control_flow_graph = il::ControlFlowGraph::new();
+
+    let entry_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(i.clone(), il::expr_const(0, 32));
+        block.assign(il::scalar("j", 32),
+            il::Expression::add(il::expr_scalar("x", 32), il::expr_const(1, 32)).unwrap());
+        block.index()
+    };
+
+    let header_index = control_flow_graph.new_block().unwrap().index();
+
+    let body_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(i.clone(), il::Expression::add(i.clone().into(), il::expr_const(4, 32)).unwrap());
+        block.index()
+    };
+
+    let exit_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(il::scalar("k", 32), i.clone().into());
+        block.index()
+    };
+
+    let condition = il::Expression::cmpltu(i.clone().into(), il::expr_const(40, 32)).unwrap();
+    control_flow_graph.unconditional_edge(entry_index, header_index).unwrap();
+    control_flow_graph.conditional_edge(header_index, body_index, condition).unwrap();
+    control_flow_graph.unconditional_edge(header_index, exit_index).unwrap();
+    control_flow_graph.unconditional_edge(body_index, header_index).unwrap();
+    control_flow_graph.set_entry(entry_index).unwrap();
+
+    let function = il::Function::new(0, control_flow_graph);
+    let value_sets = value_sets(&function).unwrap();
+
+    let values_after = |block_index: u64, scalar: &str| {
+        let block = function.block(block_index).unwrap();
+        let location = il::RefProgramLocation::new(
+            &function,
+            il::RefFunctionLocation::Instruction(block, block.instructions().last().unwrap())
+        );
+        value_sets[&location.into()][&il::scalar(scalar, 32)].clone()
+    };
+
+    assert_eq!(values_after(body_index, "i"), StridedInterval::new(4, 4, 40, 32));
+    assert_eq!(values_after(exit_index, "k"), StridedInterval::new(4, 40, 0xffff_fffc, 32));
+    assert_eq!(values_after(entry_index, "j"), StridedInterval::Top(32));
+}
@@ -1,18 +1,6 @@
 use error::*;
 use il;
 
-fn sign_extend(constant: &il::Constant) -> i64 {
-    let value: u64 = constant.value();
-    let mut mask: u64 = 0xffffffffffffffff;
-    mask <<= constant.bits();
-    if constant.value() & (1 << (constant.bits() - 1)) != 0 {
-        (value | mask) as i64
-    }
-    else {
-        value as i64
-    }
-}
-
 /// Evaluate an `il::Expression` where all terminals are `il::Constant`, and
 /// return the resulting `il::Constant`.
 pub fn eval(expr: &il::Expression) -> Result<il::Constant> {
@@ -22,84 +10,72 @@ pub fn eval(expr: &il::Expression) -> Result<il::Constant> {
             return Err(ErrorKind::ExecutorScalar(scalar.name().to_string()).into());
         },
         il::Expression::Constant(ref constant) => Ok(constant.clone()),
-        il::Expression::Add(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value() + eval(rhs)?.value();
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
-        il::Expression::Sub(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value().wrapping_sub(eval(rhs)?.value());
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
-        il::Expression::Mul(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value().wrapping_mul(eval(rhs)?.value());
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
+        il::Expression::Add(ref lhs, ref rhs) => eval(lhs)?.add(&eval(rhs)?),
+        il::Expression::Sub(ref lhs, ref rhs) => eval(lhs)?.sub(&eval(rhs)?),
+        il::Expression::Mul(ref lhs, ref rhs) => eval(lhs)?.mul(&eval(rhs)?),
         il::Expression::Divu(ref lhs, ref rhs) => {
             let rhs = eval(rhs)?;
-            if rhs.value() == 0 {
+            if rhs.is_zero() {
                 return Err(ErrorKind::Arithmetic("Division by zero".to_string()).into());
             }
-            let r = eval(lhs)?.value() / rhs.value();
-            Ok(il::Constant::new(r, lhs.bits()))
+            let lhs = eval(lhs)?;
+            if lhs.bits() <= 64 {
+                Ok(il::Constant::new(lhs.value() / rhs.value(), lhs.bits()))
+            }
+            else {
+                Ok(il::Constant::new_big(lhs.value_big() / rhs.value_big(), lhs.bits()))
+            }
         },
         il::Expression::Modu(ref lhs, ref rhs) => {
             let rhs = eval(rhs)?;
-            if rhs.value() == 0 {
+            if rhs.is_zero() {
                 return Err(ErrorKind::Arithmetic("Division by zero".to_string()).into());
             }
-            let r = eval(lhs)?.value() % rhs.value();
-            Ok(il::Constant::new(r, lhs.bits()))
+            let lhs = eval(lhs)?;
+            if lhs.bits() <= 64 {
+                Ok(il::Constant::new(lhs.value() % rhs.value(), lhs.bits()))
+            }
+            else {
+                Ok(il::Constant::new_big(lhs.value_big() % rhs.value_big(), lhs.bits()))
+            }
         },
         il::Expression::Divs(ref lhs, ref rhs) => {
             let rhs = eval(rhs)?;
-            if rhs.value() == 0 {
+            if rhs.is_zero() {
                 return Err(ErrorKind::Arithmetic("Division by zero".to_string()).into());
             }
-            let r = sign_extend(&eval(lhs)?) / sign_extend(&rhs);
-            Ok(il::Constant::new(r as u64, lhs.bits()))
-        },
-        il::Expression::Mods(ref lhs, ref rhs) => {
-            let rhs = eval(rhs)?;
-            if rhs.value() == 0 {
-                return Err(ErrorKind::Arithmetic("Division by zero".to_string()).into());
-            }
-            let r = sign_extend(&eval(lhs)?) % sign_extend(&rhs);
-            Ok(il::Constant::new(r as u64, lhs.bits()))
-        },
-        il::Expression::And(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value() & eval(rhs)?.value();
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
-        il::Expression::Or(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value() | eval(rhs)?.value();
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
-        il::Expression::Xor(ref lhs, ref rhs) => {
-            let r = eval(lhs)?.value() ^ eval(rhs)?.value();
-            Ok(il::Constant::new(r, lhs.bits()))
-        },
-        il::Expression::Shl(ref lhs, ref rhs) => {
-            let rhs = eval(rhs)?;
-            if rhs.value() > lhs.bits() as u64 {
-                Ok(il::Constant::new(0, lhs.bits()))
+            let lhs = eval(lhs)?;
+            if lhs.bits() <= 64 {
+                let r = lhs.value_i64().wrapping_div(rhs.value_i64());
+                Ok(il::Constant::new(r as u64, lhs.bits()))
             }
             else {
-                let r = eval(lhs)?.value().wrapping_shl(rhs.value() as u32);
-                Ok(il::Constant::new(r, lhs.bits()))
+                let r = lhs.value_big_signed() / rhs.value_big_signed();
+                Ok(il::Constant::new_big_signed(r, lhs.bits()))
             }
         },
-        il::Expression::Shr(ref lhs, ref rhs) => {
+        il::Expression::Mods(ref lhs, ref rhs) => {
             let rhs = eval(rhs)?;
-            if rhs.value() > lhs.bits() as u64 {
-                Ok(il::Constant::new(0, lhs.bits()))
+            if rhs.is_zero() {
+                return Err(ErrorKind::Arithmetic("Division by zero".to_string()).into());
+            }
+            let lhs = eval(lhs)?;
+            if lhs.bits() <= 64 {
+                let r = lhs.value_i64().wrapping_rem(rhs.value_i64());
+                Ok(il::Constant::new(r as u64, lhs.bits()))
             }
             else {
-                let r = eval(lhs)?.value().wrapping_shr(rhs.value() as u32);
-                Ok(il::Constant::new(r, lhs.bits()))
+                let r = lhs.value_big_signed() % rhs.value_big_signed();
+                Ok(il::Constant::new_big_signed(r, lhs.bits()))
             }
         },
+        il::Expression::And(ref lhs, ref rhs) => eval(lhs)?.and(&eval(rhs)?),
+        il::Expression::Or(ref lhs, ref rhs) => eval(lhs)?.or(&eval(rhs)?),
+        il::Expression::Xor(ref lhs, ref rhs) => eval(lhs)?.xor(&eval(rhs)?),
+        il::Expression::Shl(ref lhs, ref rhs) => eval(lhs)?.shl(&eval(rhs)?),
+        il::Expression::Shr(ref lhs, ref rhs) => eval(lhs)?.shr(&eval(rhs)?),
         il::Expression::Cmpeq(ref lhs, ref rhs) => {
-            if eval(lhs)?.value() == eval(rhs)?.value() {
+            if eval(lhs)? == eval(rhs)? {
                 Ok(il::Constant::new(1, 1))
             }
             else {
@@ -107,7 +83,7 @@ pub fn eval(expr: &il::Expression) -> Result<il::Constant> {
             }
         },
         il::Expression::Cmpneq(ref lhs, ref rhs) => {
-            if eval(lhs)?.value() != eval(rhs)?.value() {
+            if eval(lhs)? != eval(rhs)? {
                 Ok(il::Constant::new(1, 1))
             }
             else {
@@ -115,7 +91,14 @@ pub fn eval(expr: &il::Expression) -> Result<il::Constant> {
             }
         },
         il::Expression::Cmplts(ref lhs, ref rhs) => {
-            if sign_extend(&eval(lhs)?) < sign_extend(&eval(rhs)?) {
+            let (lhs, rhs) = (eval(lhs)?, eval(rhs)?);
+            let less = if lhs.bits() <= 64 {
+                lhs.value_i64() < rhs.value_i64()
+            }
+            else {
+                lhs.value_big_signed() < rhs.value_big_signed()
+            };
+            if less {
                 Ok(il::Constant::new(1, 1))
             }
             else {
@@ -123,26 +106,29 @@ pub fn eval(expr: &il::Expression) -> Result<il::Constant> {
             }
         },
         il::Expression::Cmpltu(ref lhs, ref rhs) => {
-            if eval(lhs)?.value() < eval(rhs)?.value() {
+            let (lhs, rhs) = (eval(lhs)?, eval(rhs)?);
+            let less = if lhs.bits() <= 64 {
+                lhs.value() < rhs.value()
+            }
+            else {
+                lhs.value_big() < rhs.value_big()
+            };
+            if less {
                 Ok(il::Constant::new(1, 1))
             }
             else {
                 Ok(il::Constant::new(0, 1))
             }
         },
-        il::Expression::Zext(bits, ref rhs) |
-        il::Expression::Trun(bits, ref rhs) => {
-            Ok(il::Constant::new(eval(rhs)?.value(), bits))
-        },
-        il::Expression::Sext(bits, ref rhs) => {
-            let rhs = eval(rhs)?;
-            if rhs.value() >> (rhs.bits() - 1) == 1 {
-                let mask = !((1 << rhs.bits()) - 1);
-                Ok(il::Constant::new(rhs.value() | mask, bits))
-            }
-            else {
-                Ok(il::Constant::new(rhs.value(), bits))
-            }
+        il::Expression::Zext(bits, ref rhs) => eval(rhs)?.zext(bits),
+        il::Expression::Sext(bits, ref rhs) => eval(rhs)?.sext(bits),
+        il::Expression::Trun(bits, ref rhs) => eval(rhs)?.trun(bits),
+        il::Expression::Popcnt(ref src) => Ok(eval(src)?.popcnt()),
+        il::Expression::Clz(ref src) => Ok(eval(src)?.clz()),
+        il::Expression::Ctz(ref src) => Ok(eval(src)?.ctz()),
+        il::Expression::Neg(ref src) => {
+            let src = eval(src)?;
+            il::Constant::new(0, src.bits()).sub(&src)
         }
     }
 }
@@ -172,4 +158,34 @@ fn cmplts() {
     let rhs = il::expr_const(0xffffffff, 32);
     let expr = il::Expression::cmplts(lhs, rhs).unwrap();
     assert_eq!(eval(&expr).unwrap(), il::const_(0, 1));
-}
\ No newline at end of file
+}
+
+#[test]
+fn signed_128() {
+    let constant = |value: i64| il::Constant::new_big_signed(::num_bigint::BigInt::from(value), 128);
+    let expr = |value: i64| il::Expression::constant(constant(value));
+
+    let quotient = il::Expression::divs(expr(-7), expr(2)).unwrap();
+    assert_eq!(eval(&quotient).unwrap(), constant(-3));
+    let remainder = il::Expression::mods(expr(-7), expr(2)).unwrap();
+    assert_eq!(eval(&remainder).unwrap(), constant(-1));
+
+    // 2^100 is positive, though its low 64 bits are 0.
+    let large = il::Expression::constant(
+        il::Constant::new_big(::num_bigint::BigUint::from(1u64) << 100, 128));
+    let expr_cmplts = il::Expression::cmplts(large.clone(), expr(1)).unwrap();
+    assert_eq!(eval(&expr_cmplts).unwrap(), il::const_(0, 1));
+    let expr_cmplts = il::Expression::cmplts(expr(-1), large.clone()).unwrap();
+    assert_eq!(eval(&expr_cmplts).unwrap(), il::const_(1, 1));
+
+    let quotient = il::Expression::divs(large.clone(), large.clone()).unwrap();
+    assert_eq!(eval(&quotient).unwrap(), constant(1));
+    let quotient = il::Expression::divs(expr(-1), expr(0)).unwrap();
+    assert!(eval(&quotient).is_err());
+
+    // The most negative value divided by -1 wraps.
+    let min = il::Expression::constant(
+        il::Constant::new_big(::num_bigint::BigUint::from(1u64) << 127, 128));
+    let quotient = il::Expression::divs(min.clone(), expr(-1)).unwrap();
+    assert_eq!(eval(&quotient).unwrap(), eval(&min).unwrap());
+}
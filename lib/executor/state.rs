@@ -114,6 +114,14 @@ impl<'e> State<'e> {
                 il::Expression::sext(bits, self.symbolize_expression(src)?)?,
             il::Expression::Trun(bits, ref src) => 
                 il::Expression::trun(bits, self.symbolize_expression(src)?)?,
+            il::Expression::Popcnt(ref src) =>
+                il::Expression::popcnt(self.symbolize_expression(src)?)?,
+            il::Expression::Clz(ref src) =>
+                il::Expression::clz(self.symbolize_expression(src)?)?,
+            il::Expression::Ctz(ref src) =>
+                il::Expression::ctz(self.symbolize_expression(src)?)?,
+            il::Expression::Neg(ref src) =>
+                il::Expression::neg(self.symbolize_expression(src)?)?,
         })
     }
 
@@ -155,13 +163,18 @@ impl<'e> State<'e> {
                     }
                 }
             },
-            il::Operation::Branch { ref target } => {
+            il::Operation::Branch { ref target } |
+            il::Operation::Call { ref target, .. } => {
                 let target = self.symbolize_and_eval(target)?;
                 Successor::new(self, SuccessorType::Branch(target.value()))
             },
             il::Operation::Raise { ref expr } => {
                 Successor::new(self, SuccessorType::Raise(expr.clone()))
-            }
+            },
+            il::Operation::Intrinsic { ref name, .. } => {
+                bail!("Can not execute intrinsic {}", name);
+            },
+            il::Operation::Nop => Successor::new(self, SuccessorType::FallThrough)
         })
     }
 }
\ No newline at end of file
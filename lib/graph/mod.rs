@@ -78,8 +78,11 @@ pub struct Graph<V, E> {
     head: Option<u64>,
     vertices: BTreeMap<u64, V>,
     edges: BTreeMap<(u64, u64), E>,
-    edges_out: BTreeMap<u64, Vec<E>>,
-    edges_in: BTreeMap<u64, Vec<E>>
+    // The tails of the edges out of, and heads of the edges into, each
+    // vertex. The edges themselves are only kept in edges, so that changes
+    // through edge_mut are seen by edges_out and edges_in.
+    edges_out: BTreeMap<u64, Vec<u64>>,
+    edges_in: BTreeMap<u64, Vec<u64>>
 }
 
 
@@ -135,14 +138,17 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
         // find all edges that deal with this vertex
         let mut edges = Vec::new();
-        if let Some(edges_out) = self.edges_out.get(&index) {
-            for edge in edges_out {
-                edges.push((edge.head(), edge.tail()));
+        if let Some(tails) = self.edges_out.get(&index) {
+            for tail in tails {
+                edges.push((index, *tail));
             }
         };
-        if let Some(edges_in) = self.edges_in.get(&index) {
-            for edge in edges_in {
-                edges.push((edge.head(), edge.tail()));
+        if let Some(heads) = self.edges_in.get(&index) {
+            for head in heads {
+                // self-loops were already found in edges_out
+                if *head != index {
+                    edges.push((*head, index));
+                }
             }
         };
 
@@ -166,31 +172,8 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
         self.edges.remove(&(head, tail));
 
-        // find the index of this edge in edges_out
-        let mut index = None;
-        let edges_out = self.edges_out.get_mut(&head).unwrap();
-        for (i, edge) in edges_out.iter().enumerate() {
-            if edge.head() == head && edge.tail() == tail {
-                index = Some(i);
-                break;
-            }
-        }
-        
-        // remove this edge by index in edges_out
-        edges_out.remove(index.unwrap());
-
-        // find the index of this edge in edges_in
-        let mut index = None;
-        let edges_in = self.edges_in.get_mut(&tail).unwrap();
-        for (i, edge) in edges_in.iter().enumerate() {
-            if edge.head() == head && edge.tail() == tail {
-                index = Some(i);
-                break;
-            }
-        }
-
-        // remove this edge by index in edges_in
-        edges_in.remove(index.unwrap());
+        self.edges_out.get_mut(&head).unwrap().retain(|index| *index != tail);
+        self.edges_in.get_mut(&tail).unwrap().retain(|index| *index != head);
 
         Ok(())
     }
@@ -219,8 +202,8 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
         }
 
         self.edges.insert((edge.head(), edge.tail()), edge.clone());
-        self.edges_out.get_mut(&edge.head()).map(|v| v.push(edge.clone()));
-        self.edges_in.get_mut(&edge.tail()).map(|v| v.push(edge.clone()));
+        self.edges_out.get_mut(&edge.head()).map(|v| v.push(edge.tail()));
+        self.edges_in.get_mut(&edge.tail()).map(|v| v.push(edge.head()));
 
         Ok(())
     }
@@ -234,7 +217,7 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
         let vertices = self.edges_out[&index]
                            .iter()
-                           .map(|e| self.vertex(e.tail()));
+                           .map(|tail| self.vertex(*tail));
 
         Ok(vertices.fold(Vec::new(), |mut v, vx| {
             v.push(vx.unwrap());
@@ -251,7 +234,7 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
         let vertices = self.edges_in[&index]
                            .iter()
-                           .map(|e| self.vertex(e.head()));
+                           .map(|head| self.vertex(*head));
 
         Ok(vertices.fold(Vec::new(), |mut v, vx| {
             v.push(vx.unwrap());
@@ -278,9 +261,9 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
             let vertex_index: u64 = *vertex.0;
 
             if self.edges_in[&vertex_index].len() >= 2 {
-                for edge in &self.edges_in[&vertex_index] {
-                    let mut runner = edge.head();
-                    while idoms.contains_key(&edge.head()) && runner != idoms[&edge.head()] {
+                for head in &self.edges_in[&vertex_index] {
+                    let mut runner = *head;
+                    while idoms.contains_key(head) && runner != idoms[head] {
                         df.get_mut(&runner).unwrap().insert(vertex_index);
                         if !idoms.contains_key(&runner) {
                             break;
@@ -351,8 +334,8 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
         // add all successors of start vertex to queue
         let mut queue = VecDeque::new();
-        for edge in &self.edges_out[&start_index] {
-            queue.push_back(edge.tail());
+        for tail in &self.edges_out[&start_index] {
+            queue.push_back(*tail);
         }
 
         let dag = self.compute_acyclic(start_index)?;
@@ -379,16 +362,14 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
             // this vertex's dominators are the intersection of all
             // immediate predecessors' dominators, plus itself
-            let mut doms: BTreeSet<u64> = match dag.edges_in(vertex_index)
-                                                   .unwrap()
-                                                   .first() {
-                Some(predecessor_edge) => dominators[&predecessor_edge.head()].clone(),
+            let mut doms: BTreeSet<u64> = match dag.edges_in[&vertex_index].first() {
+                Some(predecessor) => dominators[predecessor].clone(),
                 None => BTreeSet::new()
             };
 
-            for edge in &self.edges_in[&vertex_index] {
-                if predecessors[&vertex_index].contains(&edge.head()) {
-                    doms = &doms & &dominators[&edge.head()];
+            for head in &self.edges_in[&vertex_index] {
+                if predecessors[&vertex_index].contains(head) {
+                    doms = &doms & &dominators[head];
                 }
             }
 
@@ -397,9 +378,9 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
             dominators.insert(vertex_index, doms.clone());
 
             // add successors to the queue
-            for edge in &dag.edges_out[&vertex_index] {
-                if !queue.contains(&edge.tail()) {
-                    queue.push_back(edge.tail());
+            for tail in &dag.edges_out[&vertex_index] {
+                if !queue.contains(tail) {
+                    queue.push_back(*tail);
                 }
             }
         }
@@ -421,8 +402,8 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
         // initial population
         for vertex in &self.vertices {
             let mut preds = BTreeSet::new();
-            for edge in self.edges_in(*vertex.0).unwrap() {
-                preds.insert(edge.head());
+            for head in &self.edges_in[vertex.0] {
+                preds.insert(*head);
             }
             predecessors.insert(*vertex.0, preds);
             queue.push_back(*vertex.0);
@@ -453,8 +434,8 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
             }
 
             if !to_add.is_empty() {
-                for successor in self.edges_out(vertex_index).unwrap() {
-                    queue.push_back(successor.tail());
+                for successor in &self.edges_out[&vertex_index] {
+                    queue.push_back(*successor);
                 }
             }
         }
@@ -463,6 +444,196 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
     }
 
 
+    /// Computes the strongly connected components of the graph.
+    ///
+    /// Each component is a sorted list of vertex indices which are all
+    /// reachable from each other. A single vertex is only a component when it
+    /// has an edge to itself.
+    pub fn compute_strongly_connected_components(&self) -> Vec<Vec<u64>> {
+        // Tarjan's algorithm, with an explicit stack in place of recursion.
+        let mut next_index = 0;
+        let mut indices: BTreeMap<u64, usize> = BTreeMap::new();
+        let mut lowlinks: BTreeMap<u64, usize> = BTreeMap::new();
+        let mut stack: Vec<u64> = Vec::new();
+        let mut on_stack: BTreeSet<u64> = BTreeSet::new();
+        let mut components = Vec::new();
+
+        for root in self.vertices.keys() {
+            if indices.contains_key(root) {
+                continue;
+            }
+
+            indices.insert(*root, next_index);
+            lowlinks.insert(*root, next_index);
+            next_index += 1;
+            stack.push(*root);
+            on_stack.insert(*root);
+
+            // (vertex, position of the next edge out of vertex to follow)
+            let mut call_stack = vec![(*root, 0)];
+
+            while let Some((vertex, position)) = call_stack.pop() {
+                let edges_out = &self.edges_out[&vertex];
+
+                if position < edges_out.len() {
+                    call_stack.push((vertex, position + 1));
+                    let successor = edges_out[position];
+                    if !indices.contains_key(&successor) {
+                        indices.insert(successor, next_index);
+                        lowlinks.insert(successor, next_index);
+                        next_index += 1;
+                        stack.push(successor);
+                        on_stack.insert(successor);
+                        call_stack.push((successor, 0));
+                    }
+                    else if on_stack.contains(&successor) {
+                        let lowlink = lowlinks[&vertex].min(indices[&successor]);
+                        lowlinks.insert(vertex, lowlink);
+                    }
+                    continue;
+                }
+
+                // every successor of this vertex has been visited
+                if lowlinks[&vertex] == indices[&vertex] {
+                    let mut component = Vec::new();
+                    loop {
+                        let index = stack.pop().unwrap();
+                        on_stack.remove(&index);
+                        component.push(index);
+                        if index == vertex {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || self.edges.contains_key(&(vertex, vertex)) {
+                        component.sort();
+                        components.push(component);
+                    }
+                }
+
+                if let Some(&(parent, _)) = call_stack.last() {
+                    let lowlink = lowlinks[&parent].min(lowlinks[&vertex]);
+                    lowlinks.insert(parent, lowlink);
+                }
+            }
+        }
+
+        components
+    }
+
+
+    /// Returns the indices of all vertices reachable from the vertex at
+    /// `start`, including `start`.
+    /// # Error
+    /// The vertex at `start` does not exist.
+    pub fn bfs_reachable(&self, start: u64) -> Result<BTreeSet<u64>> {
+        if !self.vertices.contains_key(&start) {
+            bail!("Vertex {} does not exist", start);
+        }
+
+        let mut reachable = BTreeSet::new();
+        let mut queue = VecDeque::new();
+        reachable.insert(start);
+        queue.push_back(start);
+
+        while let Some(index) = queue.pop_front() {
+            for tail in &self.edges_out[&index] {
+                if reachable.insert(*tail) {
+                    queue.push_back(*tail);
+                }
+            }
+        }
+
+        Ok(reachable)
+    }
+
+
+    /// Returns the indices of the vertices on a path with the fewest edges
+    /// from the vertex at `from` to the vertex at `to`, including both, or
+    /// `None` if `to` is not reachable from `from`.
+    /// # Error
+    /// The vertex at `from` or at `to` does not exist.
+    pub fn shortest_path(&self, from: u64, to: u64) -> Result<Option<Vec<u64>>> {
+        if !self.vertices.contains_key(&from) {
+            bail!("Vertex {} does not exist", from);
+        }
+        if !self.vertices.contains_key(&to) {
+            bail!("Vertex {} does not exist", to);
+        }
+
+        // the vertex through which each visited vertex was first reached
+        let mut parents: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        parents.insert(from, from);
+        queue.push_back(from);
+
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                let mut path = vec![to];
+                let mut index = to;
+                while index != from {
+                    index = parents[&index];
+                    path.push(index);
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            for tail in &self.edges_out[&index] {
+                if !parents.contains_key(tail) {
+                    parents.insert(*tail, index);
+                    queue.push_back(*tail);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+
+    /// Returns the indices of the vertices on one cycle in the graph, in the
+    /// order the cycle's edges visit them, or `None` if the graph is acyclic.
+    ///
+    /// A vertex with an edge to itself is a cycle of one vertex.
+    pub fn find_cycle(&self) -> Option<Vec<u64>> {
+        // vertices whose successors have all been searched
+        let mut finished: BTreeSet<u64> = BTreeSet::new();
+
+        for root in self.vertices.keys() {
+            if finished.contains(root) {
+                continue;
+            }
+
+            // the current path from root, and for each vertex on it the
+            // position of the next edge out of that vertex to follow
+            let mut path: Vec<u64> = vec![*root];
+            let mut positions: Vec<usize> = vec![0];
+
+            while let Some(vertex) = path.last().cloned() {
+                let position = *positions.last().unwrap();
+                let edges_out = &self.edges_out[&vertex];
+
+                if position == edges_out.len() {
+                    finished.insert(vertex);
+                    path.pop();
+                    positions.pop();
+                    continue;
+                }
+
+                *positions.last_mut().unwrap() += 1;
+                let successor = edges_out[position];
+                if let Some(start) = path.iter().position(|index| *index == successor) {
+                    return Some(path[start..].to_vec());
+                }
+                if !finished.contains(&successor) {
+                    path.push(successor);
+                    positions.push(0);
+                }
+            }
+        }
+
+        None
+    }
+
+
     /// Creates an acyclic graph with NullVertex and NullEdge
     pub fn compute_acyclic(&self, start_index: u64) -> Result<Graph<NullVertex, NullEdge>> {
         let mut graph = Graph::new();
@@ -483,17 +654,17 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
             let vertex_predecessors = &predecessors[&vertex_index];
 
-            for edge in &self.edges_out[&vertex_index] {
+            for tail in &self.edges_out[&vertex_index] {
                 // skip edges that would create a loop
-                if visited.contains(&edge.tail()) && vertex_predecessors.contains(&edge.tail()) {
+                if visited.contains(tail) && vertex_predecessors.contains(tail) {
                     continue;
                 }
                 // successors we haven't seen yet get added to the queue
-                if !visited.contains(&edge.tail()) && !queue.contains(&edge.tail()) {
-                    queue.push_back(edge.tail());
+                if !visited.contains(tail) && !queue.contains(tail) {
+                    queue.push_back(*tail);
                 }
 
-                graph.insert_edge(NullEdge::new(edge.head(), edge.tail()))?;
+                graph.insert_edge(NullEdge::new(vertex_index, *tail))?;
             }
         }
 
@@ -555,14 +726,22 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 
 
     /// Return all edges out for a vertex
-    pub fn edges_out(&self, index: u64) -> Option<&Vec<E>> {
-        self.edges_out.get(&index)
+    pub fn edges_out(&self, index: u64) -> Option<Vec<&E>> {
+        self.edges_out.get(&index).map(|tails| {
+            tails.iter()
+                 .map(|tail| &self.edges[&(index, *tail)])
+                 .collect()
+        })
     }
 
 
     /// Return all edges in for a vertex
-    pub fn edges_in(&self, index: u64) -> Option<&Vec<E>> {
-        self.edges_in.get(&index)
+    pub fn edges_in(&self, index: u64) -> Option<Vec<&E>> {
+        self.edges_in.get(&index).map(|heads| {
+            heads.iter()
+                 .map(|head| &self.edges[&(*head, index)])
+                 .collect()
+        })
     }
 
 
@@ -588,3 +767,59 @@ impl<V, E> Graph<V, E> where V: Sync + Vertex, E: Edge + Sync {
 }
 
 
+
+
+#[cfg(test)]
+fn test_graph() -> Graph<NullVertex, NullEdge> {
+    // 0 -> 1 -> 2 -> 3, 0 -> 2, 3 -> 1, and 4 -> 0
+    let mut graph = Graph::new();
+    for index in 0..5 {
+        graph.insert_vertex(NullVertex::new(index)).unwrap();
+    }
+    for &(head, tail) in &[(0, 1), (1, 2), (2, 3), (0, 2), (3, 1), (4, 0)] {
+        graph.insert_edge(NullEdge::new(head, tail)).unwrap();
+    }
+    graph
+}
+
+
+#[test]
+fn bfs_reachable() {
+    let graph = test_graph();
+    assert_eq!(graph.bfs_reachable(0).unwrap(), vec![0, 1, 2, 3].into_iter().collect());
+    assert_eq!(graph.bfs_reachable(3).unwrap(), vec![1, 2, 3].into_iter().collect());
+    assert_eq!(graph.bfs_reachable(4).unwrap().len(), 5);
+    assert!(graph.bfs_reachable(5).is_err());
+}
+
+
+#[test]
+fn shortest_path() {
+    let graph = test_graph();
+    assert_eq!(graph.shortest_path(0, 3).unwrap(), Some(vec![0, 2, 3]));
+    assert_eq!(graph.shortest_path(3, 2).unwrap(), Some(vec![3, 1, 2]));
+    assert_eq!(graph.shortest_path(1, 1).unwrap(), Some(vec![1]));
+    assert_eq!(graph.shortest_path(0, 4).unwrap(), None);
+    assert!(graph.shortest_path(0, 5).is_err());
+}
+
+
+#[test]
+fn find_cycle() {
+    let mut graph: Graph<NullVertex, NullEdge> = Graph::new();
+    for index in 0..6 {
+        graph.insert_vertex(NullVertex::new(index)).unwrap();
+    }
+    // 0 -> 1 -> 2, 0 -> 2, and a disconnected 3 -> 4 -> 5
+    for &(head, tail) in &[(0, 1), (1, 2), (0, 2), (3, 4), (4, 5)] {
+        graph.insert_edge(NullEdge::new(head, tail)).unwrap();
+    }
+    assert_eq!(graph.find_cycle(), None);
+
+    graph.insert_edge(NullEdge::new(5, 3)).unwrap();
+    assert_eq!(graph.find_cycle(), Some(vec![3, 4, 5]));
+
+    graph.remove_edge(5, 3).unwrap();
+    graph.insert_edge(NullEdge::new(2, 2)).unwrap();
+    assert_eq!(graph.find_cycle(), Some(vec![2]));
+}
@@ -7,6 +7,7 @@
 //!
 //! To create a `Block`, call `ControlFlowGraph::new_block`.
 
+use std::collections::BTreeMap;
 use std::fmt;
 use il::*;
 
@@ -59,6 +60,17 @@ impl Block {
     }
 
 
+    /// Appends an `Instruction` to the end of this `Block`, keeping its
+    /// operation, comment and address.
+    ///
+    /// The instruction is given a new index in this `Block`, which is returned.
+    pub fn append_instruction(&mut self, instruction: Instruction) -> u64 {
+        let index = self.new_instruction_index();
+        self.push(instruction.clone_new_index(index));
+        index
+    }
+
+
     /// Returns the index of this `Block`
     pub fn index(&self) -> u64 {
         self.index
@@ -77,12 +89,37 @@ impl Block {
     }
 
 
+    /// Returns the instructions of this `Block` whose `Operation` is of the
+    /// given kind.
+    pub fn instructions_of_kind<'b>(&'b self, kind: OperationKind)
+        -> impl Iterator<Item=&'b Instruction> + 'b {
+
+        self.instructions
+            .iter()
+            .filter(move |instruction| instruction.operation().kind() == kind)
+    }
+
+
     /// Returns try if this `Block` is empty, meaning it has no `Instruction`
     pub fn is_empty(&self) -> bool {
         self.instructions.is_empty()
     }
 
 
+    /// Returns the lowest and highest addresses of the `Instruction`s in this
+    /// `Block`, ignoring `Instruction`s without an address, or `None` if no
+    /// `Instruction` has an address.
+    pub fn address_range(&self) -> Option<(u64, u64)> {
+        let addresses = self.instructions
+                            .iter()
+                            .filter_map(|instruction| instruction.address());
+        addresses.fold(None, |range, address| match range {
+            Some((low, high)) => Some((low.min(address), high.max(address))),
+            None => Some((address, address))
+        })
+    }
+
+
     /// Returns an `Instruction` by index, or `None` if the instruction does not
     /// exist.
     pub fn instruction(&self, index: u64) -> Option<&Instruction> {
@@ -107,8 +144,30 @@ impl Block {
     }
 
 
-    /// Deletes an `Instruction` by its index.
-    pub fn remove_instruction(&mut self, index: u64) -> Result<()> {
+    /// Inserts a new `Instruction` for the given `Operation` at the given
+    /// position in this `Block`, and returns the index of the new
+    /// `Instruction`.
+    ///
+    /// # Error
+    /// position is greater than the number of instructions in this `Block`.
+    pub fn insert_instruction(&mut self, position: usize, operation: Operation)
+        -> Result<u64> {
+
+        if position > self.instructions.len() {
+            bail!("Position {} out of range for block 0x{:X} with {} instructions",
+                position, self.index, self.instructions.len());
+        }
+        let index = self.new_instruction_index();
+        self.instructions.insert(position, Instruction::new(index, operation));
+        Ok(index)
+    }
+
+
+    /// Deletes an `Instruction` by its index, and returns the deleted
+    /// `Instruction`.
+    ///
+    /// The indices of the remaining instructions are unchanged.
+    pub fn remove_instruction(&mut self, index: u64) -> Result<Instruction> {
         let mut vec_index = None;
         for i in 0..self.instructions.len() {
             if self.instructions[i].index() == index {
@@ -117,15 +176,33 @@ impl Block {
             }
         }
         match vec_index {
-            Some(index) => {
-                self.instructions.remove(index);
-                Ok(())
-            },
+            Some(index) => Ok(self.instructions.remove(index)),
             None => Err(format!("No instruction with index {} found", index).into()),
         }
     }
 
 
+    /// Renumbers the instructions of this `Block` from 0, in their current
+    /// order, and returns a mapping from each old index to its new index.
+    ///
+    /// Instruction indices are otherwise never reused or renumbered, so
+    /// locations referring to this `Block` must be updated by the caller.
+    pub fn reindex(&mut self) -> BTreeMap<u64, u64> {
+        let mut mapping = BTreeMap::new();
+        let instructions = self.instructions
+                               .iter()
+                               .enumerate()
+                               .map(|(new_index, instruction)| {
+                                   mapping.insert(instruction.index(), new_index as u64);
+                                   instruction.clone_new_index(new_index as u64)
+                               })
+                               .collect::<Vec<Instruction>>();
+        self.next_instruction_index = instructions.len() as u64;
+        self.instructions = instructions;
+        mapping
+    }
+
+
     /// Clone this block and set a new index.
     pub(crate) fn clone_new_index(&self, index: u64) -> Block {
         let mut clone = self.clone();
@@ -143,20 +220,44 @@ impl Block {
 
     /// Adds an assign operation to the end of this block.
     pub fn assign(&mut self, dst: Scalar, src: Expression) {
+        self.assign_at(dst, src, None);
+    }
+
+    /// Adds an assign operation with the given address to the end of this
+    /// block.
+    pub fn assign_at(&mut self, dst: Scalar, src: Expression, address: Option<u64>) {
         let index = self.new_instruction_index();
-        self.push(Instruction::assign(index, dst, src));
+        let mut instruction = Instruction::assign(index, dst, src);
+        instruction.set_address(address);
+        self.push(instruction);
     }
 
     /// Adds a store operation to the end of this block.
     pub fn store(&mut self, address: Expression, src: Expression) {
-        let index = self.new_instruction_index();
-        self.push(Instruction::store(index, address, src))
+        self.store_at(address, src, None);
+    }
+
+    /// Adds a store operation with the given instruction address to the end
+    /// of this block.
+    pub fn store_at(&mut self, index: Expression, src: Expression, address: Option<u64>) {
+        let instruction_index = self.new_instruction_index();
+        let mut instruction = Instruction::store(instruction_index, index, src);
+        instruction.set_address(address);
+        self.push(instruction);
     }
 
     /// Adds a load operation to the end of this block.
     pub fn load(&mut self, dst: Scalar, address: Expression) {
-        let index = self.new_instruction_index();
-        self.push(Instruction::load(index, dst, address));
+        self.load_at(dst, address, None);
+    }
+
+    /// Adds a load operation with the given instruction address to the end of
+    /// this block.
+    pub fn load_at(&mut self, dst: Scalar, index: Expression, address: Option<u64>) {
+        let instruction_index = self.new_instruction_index();
+        let mut instruction = Instruction::load(instruction_index, dst, index);
+        instruction.set_address(address);
+        self.push(instruction);
     }
 
     /// Adds a conditional branch operation to the end of this block.
@@ -165,11 +266,31 @@ impl Block {
         self.push(Instruction::branch(index, dst));
     }
 
+    /// Adds a call operation to the end of this block.
+    pub fn call(&mut self, target: Expression, result: Option<Scalar>) {
+        let index = self.new_instruction_index();
+        self.push(Instruction::call(index, target, result));
+    }
+
     /// Adds a raise operation to the end of this block.
     pub fn raise(&mut self, expr: Expression) {
         let index = self.new_instruction_index();
         self.push(Instruction::raise(index, expr));
     }
+
+    /// Adds an intrinsic operation to the end of this block.
+    pub fn intrinsic<S>(&mut self, name: S, arguments: Vec<Expression>, results: Vec<Scalar>)
+        where S: Into<String> {
+
+        let index = self.new_instruction_index();
+        self.push(Instruction::intrinsic(index, name, arguments, results));
+    }
+
+    /// Adds a nop operation to the end of this block.
+    pub fn nop(&mut self) {
+        let index = self.new_instruction_index();
+        self.push(Instruction::nop(index));
+    }
 }
 
 
@@ -187,4 +308,158 @@ impl fmt::Display for Block {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn insert_instruction() {
+    let mut block = Block::new(0);
+    block.assign(scalar("a", 32), expr_const(1, 32));
+    block.assign(scalar("c", 32), expr_const(3, 32));
+
+    let operation = Operation::assign(scalar("b", 32), expr_const(2, 32));
+    let index = block.insert_instruction(1, operation).unwrap();
+    assert_eq!(index, 2);
+
+    let operation = Operation::assign(scalar("d", 32), expr_const(4, 32));
+    assert_eq!(block.insert_instruction(3, operation).unwrap(), 3);
+
+    let written = block.instructions()
+                       .iter()
+                       .map(|instruction| instruction.scalar_written().unwrap().name())
+                       .collect::<Vec<&str>>();
+    assert_eq!(written, vec!["a", "b", "c", "d"]);
+    assert_eq!(block.instructions()[1].index(), index);
+
+    let operation = Operation::assign(scalar("e", 32), expr_const(5, 32));
+    assert!(block.insert_instruction(5, operation).is_err());
+}
+
+
+#[test]
+fn remove_instruction() {
+    let mut block = Block::new(0);
+    block.assign(scalar("a", 32), expr_const(1, 32));
+    block.assign(scalar("b", 32), expr_const(2, 32));
+    block.assign(scalar("c", 32), expr_const(3, 32));
+
+    let removed = block.remove_instruction(1).unwrap();
+    assert_eq!(removed.index(), 1);
+    assert_eq!(removed.scalar_written().unwrap().name(), "b");
+
+    let indices = block.instructions()
+                       .iter()
+                       .map(|instruction| instruction.index())
+                       .collect::<Vec<u64>>();
+    assert_eq!(indices, vec![0, 2]);
+    assert_eq!(block.instruction(2).unwrap().scalar_written().unwrap().name(), "c");
+
+    assert!(block.remove_instruction(1).is_err());
+}
+
+
+#[test]
+fn assign_at() {
+    let mut block = Block::new(0);
+    block.assign_at(scalar("a", 32), expr_const(1, 32), Some(0x1000));
+    block.store_at(expr_scalar("sp", 32), expr_scalar("a", 32), Some(0x1004));
+    block.load_at(scalar("b", 32), expr_scalar("sp", 32), None);
+    block.assign(scalar("c", 32), expr_const(1, 32));
+
+    let addresses = block.instructions()
+                         .iter()
+                         .map(|instruction| instruction.address())
+                         .collect::<Vec<Option<u64>>>();
+    assert_eq!(addresses, vec![Some(0x1000), Some(0x1004), None, None]);
+    assert!(block.instructions()[1].is_store());
+    assert!(block.instructions()[2].is_load());
+}
+
+
+#[test]
+fn append_instruction() {
+    let mut source = Block::new(0);
+    source.assign(scalar("a", 32), expr_const(1, 32));
+    source.nop();
+    source.instructions_mut()[1].set_address(Some(0x1000));
+    let nop_index = source.instructions()[1].index();
+
+    let mut destination = Block::new(1);
+    destination.nop();
+    destination.nop();
+
+    let instruction = source.remove_instruction(nop_index)
+                            .unwrap()
+                            .with_comment("nop");
+    let index = destination.append_instruction(instruction);
+
+    assert_eq!(source.instructions().len(), 1);
+    let instruction = destination.instruction(index).unwrap();
+    assert_eq!(index, 2);
+    assert!(instruction.is_nop());
+    assert_eq!(instruction.comment(), &Some("nop".to_string()));
+    assert_eq!(instruction.address(), Some(0x1000));
+}
+
+
+#[test]
+fn address_range() {
+    let mut block = Block::new(0);
+    assert_eq!(block.address_range(), None);
+
+    block.assign(scalar("a", 32), expr_const(1, 32));
+    assert_eq!(block.address_range(), None);
+
+    block.assign_at(scalar("b", 32), expr_const(1, 32), Some(0x1008));
+    block.nop();
+    block.store_at(expr_scalar("sp", 32), expr_scalar("a", 32), Some(0x1000));
+    block.load_at(scalar("c", 32), expr_scalar("sp", 32), Some(0x1004));
+    assert_eq!(block.address_range(), Some((0x1000, 0x1008)));
+}
+
+
+#[test]
+fn instructions_of_kind() {
+    let mut block = Block::new(0);
+    block.assign(scalar("a", 32), expr_const(1, 32));
+    block.branch(expr_scalar("a", 32));
+    block.store(expr_scalar("sp", 32), expr_scalar("a", 32));
+    block.call(expr_const(0x1000, 32), None);
+    block.branch(expr_const(0x2000, 32));
+
+    let branches = block.instructions_of_kind(OperationKind::Branch)
+                        .map(|instruction| instruction.index())
+                        .collect::<Vec<u64>>();
+    assert_eq!(branches, vec![1, 4]);
+    assert!(block.instructions_of_kind(OperationKind::Branch).all(|i| i.is_branch()));
+    assert_eq!(block.instructions_of_kind(OperationKind::Store).count(), 1);
+    assert_eq!(block.instructions_of_kind(OperationKind::Nop).count(), 0);
+}
+
+
+#[test]
+fn reindex() {
+    let mut block = Block::new(0);
+    for value in 0..5 {
+        block.assign(scalar("a", 32), expr_const(value, 32));
+    }
+    block.remove_instruction(0).unwrap();
+    block.remove_instruction(2).unwrap();
+    block.insert_instruction(1, Operation::nop()).unwrap();
+
+    let operations = block.instructions()
+                          .iter()
+                          .map(|instruction| instruction.operation().clone())
+                          .collect::<Vec<Operation>>();
+    let mapping = block.reindex();
+
+    let indices = block.instructions()
+                       .iter()
+                       .map(|instruction| instruction.index())
+                       .collect::<Vec<u64>>();
+    assert_eq!(indices, vec![0, 1, 2, 3]);
+    assert_eq!(mapping, vec![(1, 0), (5, 1), (3, 2), (4, 3)].into_iter().collect());
+    for (instruction, operation) in block.instructions().iter().zip(operations) {
+        assert_eq!(instruction.operation(), &operation);
+    }
+    assert_eq!(block.append_instruction(Instruction::nop(9)), 4);
+}
@@ -0,0 +1,99 @@
+//! A `CachedExpression` is an `Expression` with its metadata computed once.
+//!
+//! `Expression::bits`, `Expression::scalars` and `Expression::node_count` each
+//! walk the expression. Passes which query the same large `Expression`
+//! repeatedly can wrap it in a `CachedExpression` instead. The wrapped
+//! `Expression` remains the canonical type, and is unchanged.
+
+use il::*;
+
+
+/// An `Expression` with its bits, scalars and node count precomputed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CachedExpression {
+    expression: Expression,
+    bits: usize,
+    scalars: Vec<Scalar>,
+    node_count: usize
+}
+
+
+impl CachedExpression {
+    /// Create a new `CachedExpression`, computing the metadata of the given
+    /// `Expression`.
+    pub fn new(expression: Expression) -> CachedExpression {
+        let bits = expression.bits();
+        let scalars = expression.scalars()
+                                .into_iter()
+                                .cloned()
+                                .collect::<Vec<Scalar>>();
+        let node_count = expression.node_count();
+        CachedExpression {
+            expression: expression,
+            bits: bits,
+            scalars: scalars,
+            node_count: node_count
+        }
+    }
+
+    /// Get the wrapped `Expression`.
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    /// Consume this `CachedExpression`, returning the wrapped `Expression`.
+    pub fn into_expression(self) -> Expression {
+        self.expression
+    }
+
+    /// The bitness of the wrapped `Expression`, as in `Expression::bits`.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The `Scalar`s used in the wrapped `Expression`, as in
+    /// `Expression::scalars`.
+    pub fn scalars(&self) -> &Vec<Scalar> {
+        &self.scalars
+    }
+
+    /// The number of nodes in the wrapped `Expression`, as in
+    /// `Expression::node_count`.
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+}
+
+
+impl From<Expression> for CachedExpression {
+    fn from(expression: Expression) -> CachedExpression {
+        CachedExpression::new(expression)
+    }
+}
+
+
+#[test]
+fn cached_expression() {
+    let mut expression = expr_scalar("a", 32);
+    for i in 0..64 {
+        expression = Expression::add(expression, expr_scalar(format!("t{}", i), 32)).unwrap();
+    }
+    let expression = Expression::cmpeq(expression, expr_const(0, 32)).unwrap();
+
+    let cached = CachedExpression::new(expression.clone());
+    assert_eq!(cached.bits(), 1);
+    assert_eq!(cached.node_count(), expression.node_count());
+    assert_eq!(cached.scalars().iter().collect::<Vec<&Scalar>>(), expression.scalars());
+
+    // Repeated queries return the stored metadata, rather than walking the
+    // expression again.
+    let scalars = cached.scalars().as_ptr();
+    for _ in 0..1024 {
+        assert_eq!(cached.bits(), 1);
+        assert_eq!(cached.node_count(), 131);
+        assert_eq!(cached.scalars().len(), 65);
+        assert_eq!(cached.scalars().as_ptr(), scalars);
+    }
+
+    assert_eq!(cached.into_expression(), expression);
+}
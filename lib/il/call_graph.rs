@@ -0,0 +1,69 @@
+//! A `CallGraph` is a directed graph of calls between the `Function` of a
+//! `Program`.
+//!
+//! To create a `CallGraph`, call `Program::call_graph`.
+
+use error::*;
+use graph::{self, Edge, NullEdge, NullVertex};
+use std::u64;
+
+
+/// A directed graph where each vertex is the index of a `Function` in a
+/// `Program`, and each edge is from a caller to a callee.
+///
+/// Calls which can not be resolved to a `Function` are edges to the vertex
+/// with index `CallGraph::unknown_index()`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct CallGraph {
+    graph: graph::Graph<NullVertex, NullEdge>
+}
+
+
+impl CallGraph {
+    pub(crate) fn new() -> CallGraph {
+        let mut graph = graph::Graph::new();
+        graph.insert_vertex(NullVertex::new(CallGraph::unknown_index())).unwrap();
+        CallGraph { graph: graph }
+    }
+
+    /// The index of the vertex for unresolved call targets.
+    pub fn unknown_index() -> u64 {
+        u64::MAX
+    }
+
+    /// Get the underlying graph.
+    pub fn graph(&self) -> &graph::Graph<NullVertex, NullEdge> {
+        &self.graph
+    }
+
+    pub(crate) fn add_function(&mut self, index: u64) -> Result<()> {
+        self.graph.insert_vertex(NullVertex::new(index))
+    }
+
+    pub(crate) fn add_call(&mut self, caller: u64, callee: u64) -> Result<()> {
+        if self.graph.edge(caller, callee).is_none() {
+            self.graph.insert_edge(NullEdge::new(caller, callee))?;
+        }
+        Ok(())
+    }
+
+    /// Get the indices of every `Function` called by the given `Function`.
+    pub fn callees(&self, index: u64) -> Result<Vec<u64>> {
+        Ok(self.graph
+               .edges_out(index)
+               .ok_or(format!("Function {} not in call graph", index))?
+               .iter()
+               .map(|edge| edge.tail())
+               .collect())
+    }
+
+    /// Get the indices of every `Function` which calls the given `Function`.
+    pub fn callers(&self, index: u64) -> Result<Vec<u64>> {
+        Ok(self.graph
+               .edges_in(index)
+               .ok_or(format!("Function {} not in call graph", index))?
+               .iter()
+               .map(|edge| edge.head())
+               .collect())
+    }
+}
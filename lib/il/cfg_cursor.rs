@@ -0,0 +1,231 @@
+//! A `CfgCursor` edits the instructions of a `ControlFlowGraph` from a
+//! position.
+//!
+//! To create a `CfgCursor`, call `ControlFlowGraph::cursor`.
+
+use il::*;
+
+
+/// A position in a `Block` of a `ControlFlowGraph`, from which instructions
+/// can be inserted, replaced and removed.
+///
+/// The cursor is either at an `Instruction`, or at the end of its `Block`,
+/// after the last `Instruction`. Edits never leave the cursor at an
+/// `Instruction` which no longer exists.
+#[derive(Debug)]
+pub struct CfgCursor<'c> {
+    control_flow_graph: &'c mut ControlFlowGraph,
+    block_index: u64,
+    position: usize
+}
+
+
+impl<'c> CfgCursor<'c> {
+    pub(crate) fn new(control_flow_graph: &'c mut ControlFlowGraph, block_index: u64)
+        -> Result<CfgCursor<'c>> {
+
+        if control_flow_graph.block(block_index).is_none() {
+            bail!("Block 0x{:X} not found", block_index);
+        }
+        Ok(CfgCursor {
+            control_flow_graph,
+            block_index,
+            position: 0
+        })
+    }
+
+
+    fn block(&self) -> &Block {
+        self.control_flow_graph
+            .block(self.block_index)
+            .expect("CfgCursor block was removed")
+    }
+
+
+    fn block_mut(&mut self) -> &mut Block {
+        self.control_flow_graph
+            .block_mut(self.block_index)
+            .expect("CfgCursor block was removed")
+    }
+
+
+    /// Get the index of the `Block` this cursor is in.
+    pub fn block_index(&self) -> u64 {
+        self.block_index
+    }
+
+
+    /// Get the `Instruction` at this cursor, or `None` if the cursor is at the
+    /// end of its `Block`.
+    pub fn instruction(&self) -> Option<&Instruction> {
+        self.block().instructions().get(self.position)
+    }
+
+
+    /// Get the index of the `Instruction` at this cursor, or `None` if the
+    /// cursor is at the end of its `Block`.
+    pub fn instruction_index(&self) -> Option<u64> {
+        self.instruction().map(|instruction| instruction.index())
+    }
+
+
+    /// Move this cursor to the `Instruction` with the given index in the
+    /// given `Block`.
+    ///
+    /// # Error
+    /// The `Block` or `Instruction` does not exist. The cursor is not moved.
+    pub fn goto(&mut self, block_index: u64, instruction_index: u64) -> Result<()> {
+        let position = self.control_flow_graph
+                           .block(block_index)
+                           .ok_or(format!("Block 0x{:X} not found", block_index))?
+                           .instructions()
+                           .iter()
+                           .position(|instruction| instruction.index() == instruction_index)
+                           .ok_or(format!("No instruction with index {} found in block 0x{:X}",
+                                          instruction_index, block_index))?;
+        self.block_index = block_index;
+        self.position = position;
+        Ok(())
+    }
+
+
+    /// Move this cursor to the next `Instruction` in its `Block`, and return
+    /// true if there is one. Otherwise the cursor is left at the end of the
+    /// `Block`, and false is returned.
+    pub fn next(&mut self) -> bool {
+        let len = self.block().instructions().len();
+        if self.position < len {
+            self.position += 1;
+        }
+        self.position < len
+    }
+
+
+    /// Insert a new `Instruction` for the given `Operation` after the cursor,
+    /// and return its index. At the end of the `Block`, the `Instruction` is
+    /// appended.
+    ///
+    /// The cursor stays at its current `Instruction`.
+    pub fn insert_after(&mut self, operation: Operation) -> Result<u64> {
+        let position = self.position;
+        match self.instruction() {
+            Some(_) => self.block_mut().insert_instruction(position + 1, operation),
+            None => {
+                self.position += 1;
+                self.block_mut().insert_instruction(position, operation)
+            }
+        }
+    }
+
+
+    /// Replace the `Operation` of the `Instruction` at this cursor, and return
+    /// the replaced `Operation`.
+    ///
+    /// # Error
+    /// The cursor is at the end of its `Block`.
+    pub fn replace(&mut self, operation: Operation) -> Result<Operation> {
+        let position = self.position;
+        let block_index = self.block_index;
+        let instruction = self.block_mut()
+                              .instructions_mut()
+                              .get_mut(position)
+                              .ok_or(format!("Cursor is at the end of block 0x{:X}",
+                                             block_index))?;
+        let replaced = instruction.operation().clone();
+        instruction.replace_operation(operation);
+        Ok(replaced)
+    }
+
+
+    /// Remove the `Instruction` at this cursor, and return it.
+    ///
+    /// The cursor moves to the `Instruction` which followed the removed
+    /// `Instruction`, or to the end of the `Block`.
+    ///
+    /// # Error
+    /// The cursor is at the end of its `Block`.
+    pub fn remove(&mut self) -> Result<Instruction> {
+        if self.instruction().is_none() {
+            bail!("Cursor is at the end of block 0x{:X}", self.block_index);
+        }
+        let position = self.position;
+        Ok(self.block_mut().instructions_mut().remove(position))
+    }
+}
+
+
+#[test]
+fn cfg_cursor() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    let (head, tail) = {
+        let head = {
+            let block = control_flow_graph.new_block().unwrap();
+            block.assign(scalar("a", 32), expr_const(0, 32));
+            block.assign(scalar("b", 32), expr_const(1, 32));
+            block.assign(scalar("c", 32), expr_const(2, 32));
+            block.index()
+        };
+        let tail = control_flow_graph.new_block().unwrap().index();
+        (head, tail)
+    };
+
+    let operations = |control_flow_graph: &ControlFlowGraph, index: u64| {
+        control_flow_graph.block(index)
+                          .unwrap()
+                          .instructions()
+                          .iter()
+                          .map(|instruction| instruction.operation().clone())
+                          .collect::<Vec<Operation>>()
+    };
+
+    {
+        let mut cursor = control_flow_graph.cursor(head).unwrap();
+        assert_eq!(cursor.instruction_index(), Some(0));
+
+        let inserted = cursor.insert_after(Operation::nop()).unwrap();
+        assert_eq!(inserted, 3);
+        assert_eq!(cursor.instruction_index(), Some(0));
+        assert!(cursor.next());
+        assert_eq!(cursor.instruction_index(), Some(3));
+
+        let replaced = cursor.replace(Operation::assign(scalar("d", 32), expr_const(3, 32)))
+                             .unwrap();
+        assert_eq!(replaced, Operation::nop());
+        assert_eq!(cursor.instruction_index(), Some(3));
+
+        // Removing moves to the following instruction.
+        cursor.goto(head, 1).unwrap();
+        assert_eq!(cursor.remove().unwrap().index(), 1);
+        assert_eq!(cursor.instruction_index(), Some(2));
+
+        // Removing the last instruction leaves the cursor at the end.
+        assert_eq!(cursor.remove().unwrap().index(), 2);
+        assert_eq!(cursor.instruction_index(), None);
+        assert!(cursor.remove().is_err());
+        assert!(cursor.replace(Operation::nop()).is_err());
+        assert!(!cursor.next());
+
+        // Inserting at the end appends, and keeps the cursor at the end.
+        assert_eq!(cursor.insert_after(Operation::nop()).unwrap(), 4);
+        assert_eq!(cursor.instruction_index(), None);
+
+        assert!(cursor.goto(head, 1).is_err());
+        assert!(cursor.goto(tail + 1, 0).is_err());
+        assert_eq!(cursor.block_index(), head);
+
+        assert!(cursor.goto(tail, 0).is_err());
+        assert!(cursor.goto(head, 4).is_ok());
+        assert!(!cursor.next());
+    }
+
+    assert_eq!(operations(&control_flow_graph, head), vec![
+        Operation::assign(scalar("a", 32), expr_const(0, 32)),
+        Operation::assign(scalar("d", 32), expr_const(3, 32)),
+        Operation::nop()
+    ]);
+
+    let mut cursor = control_flow_graph.cursor(tail).unwrap();
+    assert_eq!(cursor.instruction_index(), None);
+    assert_eq!(cursor.insert_after(Operation::nop()).unwrap(), 0);
+    assert!(control_flow_graph.cursor(tail + 1).is_err());
+}
@@ -0,0 +1,174 @@
+//! A `CfgDiff` is the structural difference between two `ControlFlowGraph`.
+//!
+//! To compute a `CfgDiff`, call `ControlFlowGraph::diff`.
+
+use il::*;
+use std::collections::BTreeMap;
+use std::fmt;
+
+
+/// The blocks, instructions and edges which differ between an old and a new
+/// `ControlFlowGraph`.
+///
+/// Blocks are matched by index, instructions by operation, and edges by head,
+/// tail and condition.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CfgDiff {
+    added_blocks: Vec<u64>,
+    removed_blocks: Vec<u64>,
+    added_operations: BTreeMap<u64, Vec<Operation>>,
+    removed_operations: BTreeMap<u64, Vec<Operation>>,
+    added_edges: Vec<Edge>,
+    removed_edges: Vec<Edge>
+}
+
+
+/// Returns the operations in `lhs` which are not matched by an operation in
+/// `rhs`, where each operation in `rhs` matches at most once.
+fn unmatched(lhs: &Block, rhs: &Block) -> Vec<Operation> {
+    let mut rhs = rhs.instructions()
+                     .iter()
+                     .map(|instruction| instruction.operation())
+                     .collect::<Vec<&Operation>>();
+    let mut unmatched = Vec::new();
+    for instruction in lhs.instructions() {
+        match rhs.iter().position(|operation| *operation == instruction.operation()) {
+            Some(position) => { rhs.remove(position); },
+            None => unmatched.push(instruction.operation().clone())
+        }
+    }
+    unmatched
+}
+
+
+/// Returns the edges in `lhs` with no edge of the same head, tail and
+/// condition in `rhs`.
+fn unmatched_edges(lhs: &ControlFlowGraph, rhs: &ControlFlowGraph) -> Vec<Edge> {
+    lhs.edges()
+       .into_iter()
+       .filter(|edge| match rhs.edge(edge.head(), edge.tail()) {
+           Some(rhs_edge) => rhs_edge.condition() != edge.condition(),
+           None => true
+       })
+       .cloned()
+       .collect()
+}
+
+
+impl CfgDiff {
+    pub(crate) fn new(old: &ControlFlowGraph, new: &ControlFlowGraph) -> CfgDiff {
+        let mut diff = CfgDiff::default();
+
+        for block in old.blocks() {
+            match new.block(block.index()) {
+                Some(new_block) => {
+                    let removed = unmatched(block, new_block);
+                    if !removed.is_empty() {
+                        diff.removed_operations.insert(block.index(), removed);
+                    }
+                    let added = unmatched(new_block, block);
+                    if !added.is_empty() {
+                        diff.added_operations.insert(block.index(), added);
+                    }
+                },
+                None => diff.removed_blocks.push(block.index())
+            }
+        }
+
+        for block in new.blocks() {
+            if old.block(block.index()).is_none() {
+                diff.added_blocks.push(block.index());
+            }
+        }
+
+        diff.removed_edges = unmatched_edges(old, new);
+        diff.added_edges = unmatched_edges(new, old);
+
+        diff
+    }
+
+    /// Get the indices of blocks only in the new `ControlFlowGraph`.
+    pub fn added_blocks(&self) -> &Vec<u64> {
+        &self.added_blocks
+    }
+
+    /// Get the indices of blocks only in the old `ControlFlowGraph`.
+    pub fn removed_blocks(&self) -> &Vec<u64> {
+        &self.removed_blocks
+    }
+
+    /// Get, by block index, the operations only in the new version of a block
+    /// in both `ControlFlowGraph`.
+    pub fn added_operations(&self) -> &BTreeMap<u64, Vec<Operation>> {
+        &self.added_operations
+    }
+
+    /// Get, by block index, the operations only in the old version of a block
+    /// in both `ControlFlowGraph`.
+    pub fn removed_operations(&self) -> &BTreeMap<u64, Vec<Operation>> {
+        &self.removed_operations
+    }
+
+    /// Get the edges only in the new `ControlFlowGraph`.
+    pub fn added_edges(&self) -> &Vec<Edge> {
+        &self.added_edges
+    }
+
+    /// Get the edges only in the old `ControlFlowGraph`.
+    pub fn removed_edges(&self) -> &Vec<Edge> {
+        &self.removed_edges
+    }
+
+    /// Get the total number of differences.
+    pub fn len(&self) -> usize {
+        self.added_blocks.len()
+            + self.removed_blocks.len()
+            + self.added_operations.values().map(|operations| operations.len()).sum::<usize>()
+            + self.removed_operations.values().map(|operations| operations.len()).sum::<usize>()
+            + self.added_edges.len()
+            + self.removed_edges.len()
+    }
+
+    /// Returns true if the two `ControlFlowGraph` do not differ.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+impl fmt::Display for CfgDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for index in &self.removed_blocks {
+            writeln!(f, "- block 0x{:X}", index)?;
+        }
+        for index in &self.added_blocks {
+            writeln!(f, "+ block 0x{:X}", index)?;
+        }
+        let mut indices = self.removed_operations
+                              .keys()
+                              .chain(self.added_operations.keys())
+                              .collect::<Vec<&u64>>();
+        indices.sort();
+        indices.dedup();
+        for index in indices {
+            writeln!(f, "block 0x{:X}", index)?;
+            if let Some(operations) = self.removed_operations.get(index) {
+                for operation in operations {
+                    writeln!(f, "  - {}", operation)?;
+                }
+            }
+            if let Some(operations) = self.added_operations.get(index) {
+                for operation in operations {
+                    writeln!(f, "  + {}", operation)?;
+                }
+            }
+        }
+        for edge in &self.removed_edges {
+            writeln!(f, "- edge {}", edge)?;
+        }
+        for edge in &self.added_edges {
+            writeln!(f, "+ edge {}", edge)?;
+        }
+        Ok(())
+    }
+}
@@ -1,25 +1,67 @@
 //! A `Constant` holds a single value.
+//!
+//! Constants of up to 64 bits keep their value in a `u64`. Wider constants
+//! also keep their full value, which is available through
+//! `Constant::value_big`.
 
+use num_bigint::{BigInt, BigUint, Sign};
 use std::fmt;
 use il::*;
+use types::Endian;
 
 
 /// A constant value for Falcon IL
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Constant {
+    // The value of this constant, truncated to 64 bits.
     value: u64,
-    bits: usize
+    bits: usize,
+    // The full value, as little-endian bytes, of constants wider than 64 bits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wide: Option<Vec<u8>>
 }
 
 
 impl Constant {
     /// Create a new `Constant` with the given value and bitness.
     pub fn new(value: u64, bits: usize) -> Constant {
-        Constant { value: Constant::trim_value(value, bits), bits: bits }
+        if bits > 64 {
+            return Constant::new_big(BigUint::from(value), bits);
+        }
+        Constant {
+            value: Constant::trim_value(value, bits),
+            bits: bits,
+            wide: None
+        }
+    }
+
+    /// Create a new `Constant` of any bitness from a `BigUint`.
+    pub fn new_big(value: BigUint, bits: usize) -> Constant {
+        if bits <= 64 {
+            return Constant::new(Constant::low_u64(&value.to_bytes_le()), bits);
+        }
+        let mut bytes = (value & Constant::mask(bits)).to_bytes_le();
+        bytes.resize((bits + 7) / 8, 0);
+        Constant {
+            value: Constant::low_u64(&bytes),
+            bits: bits,
+            wide: Some(bytes)
+        }
+    }
+
+    /// Create a new `Constant` of any bitness from a `BigInt`, in two's
+    /// complement and truncated to the given bitness.
+    pub fn new_big_signed(value: BigInt, bits: usize) -> Constant {
+        let modulus = BigInt::from_biguint(Sign::Plus, BigUint::from(1u64) << bits);
+        let mut value = value % modulus.clone();
+        if value.sign() == Sign::Minus {
+            value = value + modulus;
+        }
+        Constant::new_big(value.to_biguint().unwrap(), bits)
     }
 
     fn trim_value(value: u64, bits: usize) -> u64 {
-        if bits == 64 {
+        if bits >= 64 {
             value
         }
         else {
@@ -27,21 +69,343 @@ impl Constant {
         }
     }
 
-    /// Get the value of this `Constant`.
+    /// The lowest 64 bits of a little-endian byte buffer.
+    fn low_u64(bytes: &[u8]) -> u64 {
+        bytes.iter()
+             .take(8)
+             .enumerate()
+             .fold(0, |value, (i, byte)| value | ((*byte as u64) << (i * 8)))
+    }
+
+    /// A mask of the given number of bits.
+    fn mask(bits: usize) -> BigUint {
+        (BigUint::from(1u64) << bits) - BigUint::from(1u64)
+    }
+
+    /// Create a new `Constant` from bytes in the given endianness.
+    ///
+    /// # Error
+    /// The number of bytes given is not the number of bytes required to hold
+    /// a value of the given bitness.
+    pub fn from_bytes(bytes: &[u8], bits: usize, endian: Endian) -> Result<Constant> {
+        let length = (bits + 7) / 8;
+        if bytes.len() != length {
+            bail!("Expected {} bytes for a {}-bit constant, got {}",
+                length, bits, bytes.len());
+        }
+
+        if bits > 64 {
+            let value = match endian {
+                Endian::Big => BigUint::from_bytes_be(bytes),
+                Endian::Little => BigUint::from_bytes_le(bytes)
+            };
+            return Ok(Constant::new_big(value, bits));
+        }
+
+        let mut value: u64 = 0;
+        for i in 0..length {
+            let byte = match endian {
+                Endian::Big => bytes[i],
+                Endian::Little => bytes[length - i - 1]
+            };
+            value = (value << 8) | byte as u64;
+        }
+
+        Ok(Constant::new(value, bits))
+    }
+
+    /// Get the value of this `Constant`, truncated to 64 bits.
     pub fn value(&self) -> u64 {
         self.value
     }
 
+    /// Get the value of this `Constant` as a `u64`.
+    /// # Error
+    /// The value of this `Constant` does not fit in 64 bits.
+    pub fn value_u64(&self) -> Result<u64> {
+        if self.wide.is_some() && self.value_big().bits() > 64 {
+            bail!("Value of {}-bit constant does not fit in 64 bits", self.bits);
+        }
+        Ok(self.value)
+    }
+
+    /// Get the full value of this `Constant`.
+    pub fn value_big(&self) -> BigUint {
+        match self.wide {
+            Some(ref bytes) => BigUint::from_bytes_le(bytes),
+            None => BigUint::from(self.value)
+        }
+    }
+
+    /// Get the value of this `Constant` as a `BigInt`, sign-extended from its
+    /// bitness.
+    pub fn value_big_signed(&self) -> BigInt {
+        let value = BigInt::from_biguint(Sign::Plus, self.value_big());
+        if self.sign_bit() {
+            value - BigInt::from_biguint(Sign::Plus, BigUint::from(1u64) << self.bits)
+        }
+        else {
+            value
+        }
+    }
+
+    /// Returns true if the full value of this `Constant` is 0.
+    pub fn is_zero(&self) -> bool {
+        match self.wide {
+            Some(ref bytes) => bytes.iter().all(|byte| *byte == 0),
+            None => self.value == 0
+        }
+    }
+
+    /// Get the value of this `Constant`, sign-extended from its bitness to an
+    /// `i64`.
+    ///
+    /// Constants wider than 64 bits are truncated to 64 bits.
+    pub fn value_i64(&self) -> i64 {
+        if self.bits == 0 || self.bits >= 64 {
+            return self.value as i64;
+        }
+        let shift = 64 - self.bits;
+        ((self.value << shift) as i64) >> shift
+    }
+
     /// Get the number of bits for this `Constant`.
     pub fn bits(&self) -> usize {
         self.bits
     }
+
+    /// Format this `Constant` as lowercase hexadecimal followed by its
+    /// bitness, such as `0xff:8`.
+    ///
+    /// This is the `Display` form of a `Constant` with lowercase digits, for
+    /// dumps which are compared to or written by hand.
+    pub fn display_typed(&self) -> String {
+        format!("0x{}:{}", self.value_big().to_str_radix(16), self.bits)
+    }
+
+    /// Returns true if the most significant bit of this `Constant` is set.
+    fn sign_bit(&self) -> bool {
+        if self.bits == 0 {
+            return false;
+        }
+        let bit = self.bits - 1;
+        match self.wide {
+            Some(ref bytes) => bytes[bit / 8] & (1 << (bit % 8)) != 0,
+            None => self.value & (1 << bit) != 0
+        }
+    }
+
+    /// Get the bytes of this `Constant` in the given endianness.
+    ///
+    /// The number of bytes returned is the bitness of this `Constant` divided
+    /// by 8, rounded up.
+    pub fn to_bytes(&self, endian: Endian) -> Vec<u8> {
+        let mut bytes = match self.wide {
+            Some(ref bytes) => bytes.clone(),
+            None => {
+                let length = (self.bits + 7) / 8;
+                let mut bytes = Vec::new();
+                for i in 0..length {
+                    bytes.push((self.value >> (i * 8)) as u8);
+                }
+                bytes
+            }
+        };
+        if endian == Endian::Big {
+            bytes.reverse();
+        }
+        bytes
+    }
+
+    /// Zero-extend this `Constant` to the given number of bits.
+    /// # Error
+    /// bits is not greater than the bitness of this `Constant`.
+    pub fn zext(&self, bits: usize) -> Result<Constant> {
+        if bits <= self.bits || self.bits == 0 {
+            return Err(ErrorKind::Sort("zext".to_string(), self.bits, bits).into());
+        }
+        Ok(Constant::new_big(self.value_big(), bits))
+    }
+
+    /// Sign-extend this `Constant` to the given number of bits.
+    /// # Error
+    /// bits is not greater than the bitness of this `Constant`.
+    pub fn sext(&self, bits: usize) -> Result<Constant> {
+        if bits <= self.bits || self.bits == 0 {
+            return Err(ErrorKind::Sort("sext".to_string(), self.bits, bits).into());
+        }
+        if bits <= 64 {
+            return Ok(Constant::new(self.value_i64() as u64, bits));
+        }
+        if !self.sign_bit() {
+            return Ok(Constant::new_big(self.value_big(), bits));
+        }
+        let extension = Constant::mask(bits) - Constant::mask(self.bits);
+        Ok(Constant::new_big(self.value_big() | extension, bits))
+    }
+
+    /// Truncate this `Constant` to the given number of bits.
+    /// # Error
+    /// bits is zero, or not less than the bitness of this `Constant`.
+    pub fn trun(&self, bits: usize) -> Result<Constant> {
+        if bits >= self.bits || bits == 0 {
+            return Err(ErrorKind::Sort("trun".to_string(), self.bits, bits).into());
+        }
+        Ok(Constant::new_big(self.value_big(), bits))
+    }
+
+    /// Get the bits of this `Constant` which are set, from least significant.
+    fn set_bits(&self) -> Vec<usize> {
+        self.to_bytes(Endian::Little)
+            .iter()
+            .enumerate()
+            .flat_map(|(index, byte)| {
+                (0..8).filter(move |bit| byte & (1 << bit) != 0)
+                      .map(move |bit| index * 8 + bit)
+            })
+            .filter(|bit| *bit < self.bits)
+            .collect()
+    }
+
+    /// Count the bits set in this `Constant`, as a `Constant` of the same
+    /// bitness.
+    pub fn popcnt(&self) -> Constant {
+        Constant::new(self.set_bits().len() as u64, self.bits)
+    }
+
+    /// Count the zero bits above the most significant one bit of this
+    /// `Constant`, as a `Constant` of the same bitness.
+    pub fn clz(&self) -> Constant {
+        let zeros = match self.set_bits().last() {
+            Some(bit) => self.bits - bit - 1,
+            None => self.bits
+        };
+        Constant::new(zeros as u64, self.bits)
+    }
+
+    /// Count the zero bits below the least significant one bit of this
+    /// `Constant`, as a `Constant` of the same bitness.
+    pub fn ctz(&self) -> Constant {
+        let zeros = match self.set_bits().first() {
+            Some(bit) => *bit,
+            None => self.bits
+        };
+        Constant::new(zeros as u64, self.bits)
+    }
+
+    /// Apply a binary operation to two `Constant` of the same bitness.
+    ///
+    /// `narrow` is used when both constants fit in 64 bits, `wide` otherwise.
+    /// The result of either is truncated to the bitness of the constants.
+    /// `operator` names the operation in the returned error.
+    fn binop<N, W>(&self, operator: &str, other: &Constant, narrow: N, wide: W)
+        -> Result<Constant>
+        where N: Fn(u64, u64) -> u64, W: Fn(BigUint, BigUint) -> BigUint {
+
+        if self.bits != other.bits {
+            return Err(ErrorKind::Sort(operator.to_string(), self.bits, other.bits).into());
+        }
+        if self.bits <= 64 {
+            Ok(Constant::new(narrow(self.value, other.value), self.bits))
+        }
+        else {
+            Ok(Constant::new_big(wide(self.value_big(), other.value_big()), self.bits))
+        }
+    }
+
+    /// Add two `Constant`, wrapping on overflow.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn add(&self, other: &Constant) -> Result<Constant> {
+        self.binop("add", other, |l, r| l.wrapping_add(r), |l, r| l + r)
+    }
+
+    /// Subtract `other` from this `Constant`, wrapping on underflow.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn sub(&self, other: &Constant) -> Result<Constant> {
+        self.binop("sub", other,
+                   |l, r| l.wrapping_sub(r),
+                   |l, r| l + (BigUint::from(1u64) << self.bits) - r)
+    }
+
+    /// Multiply two `Constant`, wrapping on overflow.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn mul(&self, other: &Constant) -> Result<Constant> {
+        self.binop("mul", other, |l, r| l.wrapping_mul(r), |l, r| l * r)
+    }
+
+    /// Bitwise-and two `Constant`.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn and(&self, other: &Constant) -> Result<Constant> {
+        self.binop("and", other, |l, r| l & r, |l, r| l & r)
+    }
+
+    /// Bitwise-or two `Constant`.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn or(&self, other: &Constant) -> Result<Constant> {
+        self.binop("or", other, |l, r| l | r, |l, r| l | r)
+    }
+
+    /// Bitwise-xor two `Constant`.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn xor(&self, other: &Constant) -> Result<Constant> {
+        self.binop("xor", other, |l, r| l ^ r, |l, r| l ^ r)
+    }
+
+    /// The shift amount given by `other`, or `None` if it shifts out every
+    /// bit of this `Constant`.
+    fn shift_amount(&self, other: &Constant) -> Option<usize> {
+        match other.value_u64() {
+            Ok(amount) => if amount < self.bits as u64 { Some(amount as usize) } else { None },
+            Err(_) => None
+        }
+    }
+
+    /// Shift this `Constant` left by `other`.
+    ///
+    /// Shifting by the bitness of this `Constant` or more gives 0. The shift
+    /// amount is not masked, as some architectures do in hardware.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn shl(&self, other: &Constant) -> Result<Constant> {
+        match self.shift_amount(other) {
+            Some(amount) => self.binop("shl", other,
+                                       |l, _| l << amount,
+                                       |l, _| l << amount),
+            None => self.binop("shl", other, |_, _| 0, |_, _| BigUint::from(0u64))
+        }
+    }
+
+    /// Logically shift this `Constant` right by `other`.
+    ///
+    /// Shifting by the bitness of this `Constant` or more gives 0. The shift
+    /// amount is not masked, as some architectures do in hardware.
+    /// # Error
+    /// The bitness of the two constants differs.
+    pub fn shr(&self, other: &Constant) -> Result<Constant> {
+        match self.shift_amount(other) {
+            Some(amount) => self.binop("shr", other,
+                                       |l, _| l >> amount,
+                                       |l, _| l >> amount),
+            None => self.binop("shr", other, |_, _| 0, |_, _| BigUint::from(0u64))
+        }
+    }
 }
 
 
 impl fmt::Display for Constant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "0x{:X}:{}", self.value(), self.bits)
+        match self.wide {
+            Some(_) => write!(f, "0x{}:{}",
+                              self.value_big().to_str_radix(16).to_uppercase(),
+                              self.bits),
+            None => write!(f, "0x{:X}:{}", self.value(), self.bits)
+        }
     }
 }
 
@@ -50,4 +414,93 @@ impl Into<Expression> for Constant {
     fn into(self) -> Expression {
         Expression::constant(self)
     }
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn value_i64() {
+    assert_eq!(Constant::new(1, 1).value_i64(), -1);
+    assert_eq!(Constant::new(0, 1).value_i64(), 0);
+    assert_eq!(Constant::new(0xff, 8).value_i64(), -1);
+    assert_eq!(Constant::new(0x7f, 8).value_i64(), 127);
+    assert_eq!(Constant::new(0x8000, 16).value_i64(), -32768);
+    assert_eq!(Constant::new(0x7fff, 16).value_i64(), 32767);
+    assert_eq!(Constant::new(0xfffffff0, 32).value_i64(), -16);
+    assert_eq!(Constant::new(0x10, 32).value_i64(), 16);
+    assert_eq!(Constant::new(0xffffffffffffffff, 64).value_i64(), -1);
+    assert_eq!(Constant::new(0x7fffffffffffffff, 64).value_i64(), 0x7fffffffffffffff);
+}
+
+
+#[test]
+fn extension() {
+    assert_eq!(Constant::new(0x80, 8).zext(32).unwrap(), Constant::new(0x80, 32));
+    assert_eq!(Constant::new(0x80, 8).sext(32).unwrap(), Constant::new(0xffffff80, 32));
+    assert_eq!(Constant::new(0x7f, 8).sext(32).unwrap(), Constant::new(0x7f, 32));
+    assert_eq!(Constant::new(1, 1).sext(64).unwrap(), Constant::new(0xffffffffffffffff, 64));
+    assert_eq!(Constant::new(0x12345678, 32).trun(8).unwrap(), Constant::new(0x78, 8));
+
+    assert!(Constant::new(0x80, 8).zext(8).is_err());
+    assert!(Constant::new(0x80, 32).sext(16).is_err());
+    assert!(Constant::new(0x80, 8).trun(16).is_err());
+    assert!(Constant::new(0x80, 8).trun(0).is_err());
+}
+
+
+#[test]
+fn bytes() {
+    let constant = Constant::new(0x11223344, 32);
+
+    let bytes = constant.to_bytes(Endian::Big);
+    assert_eq!(bytes, vec![0x11, 0x22, 0x33, 0x44]);
+    assert_eq!(Constant::from_bytes(&bytes, 32, Endian::Big).unwrap(), constant);
+
+    let bytes = constant.to_bytes(Endian::Little);
+    assert_eq!(bytes, vec![0x44, 0x33, 0x22, 0x11]);
+    assert_eq!(Constant::from_bytes(&bytes, 32, Endian::Little).unwrap(), constant);
+
+    assert_eq!(Constant::new(1, 1).to_bytes(Endian::Little), vec![1]);
+    assert!(Constant::from_bytes(&[0x11, 0x22], 32, Endian::Big).is_err());
+}
+
+#[test]
+fn wide() {
+    let max = Constant::new_big(Constant::mask(128), 128);
+    assert_eq!(max.bits(), 128);
+    assert_eq!(max.value(), 0xffffffffffffffff);
+    assert!(max.value_u64().is_err());
+    assert_eq!(max.value_big(), Constant::mask(128));
+    assert_eq!(Constant::new(7, 128).value_u64().unwrap(), 7);
+
+    let one = Constant::new(1, 128);
+    assert_eq!(max.add(&one).unwrap(), Constant::new(0, 128));
+    assert_eq!(Constant::new(0, 128).sub(&one).unwrap(), max);
+
+    let low = Constant::new(0xffffffffffffffff, 128);
+    let carried = low.add(&one).unwrap();
+    assert_eq!(carried.value(), 0);
+    assert_eq!(carried.value_big(), BigUint::from(1u64) << 64);
+    assert_eq!(carried.shr(&Constant::new(64, 128)).unwrap(), one);
+    assert_eq!(one.shl(&Constant::new(128, 128)).unwrap(), Constant::new(0, 128));
+
+    assert_eq!(Constant::new(0x80, 8).sext(128).unwrap(),
+               Constant::new_big(Constant::mask(128) - BigUint::from(0x7fu64), 128));
+    assert_eq!(carried.trun(64).unwrap(), Constant::new(0, 64));
+    assert_eq!(low.zext(256).unwrap().trun(64).unwrap(), Constant::new(0xffffffffffffffff, 64));
+
+    let bytes = carried.to_bytes(Endian::Big);
+    assert_eq!(bytes.len(), 16);
+    assert_eq!(Constant::from_bytes(&bytes, 128, Endian::Big).unwrap(), carried);
+
+    assert!(one.add(&Constant::new(1, 64)).is_err());
+}
+
+#[test]
+fn display_typed() {
+    assert_eq!(Constant::new(0xff, 8).display_typed(), "0xff:8");
+    assert_eq!(Constant::new(0xff, 32).display_typed(), "0xff:32");
+    assert!(Constant::new(0xff, 8).display_typed() != Constant::new(0xff, 32).display_typed());
+    assert_eq!(Constant::new(0, 1).display_typed(), "0x0:1");
+    assert_eq!(Constant::new_big(BigUint::from(1u64) << 64, 128).display_typed(),
+               "0x10000000000000000:128");
+}
@@ -1,6 +1,6 @@
 //! A `ControlFlowGraph` is a directed `Graph` of `Block` and `Edge`.
 
-use std::collections::{BTreeMap};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt;
 use il::*;
 
@@ -68,6 +68,37 @@ impl ControlFlowGraph {
         Err("Index does not exist for set_exit".into())
     }
 
+    /// Sets the entry of this `ControlFlowGraph` to the only `Block` without
+    /// predecessors, and the exit to the only `Block` without successors.
+    ///
+    /// # Error
+    /// There is not exactly one `Block` without predecessors, or not exactly
+    /// one `Block` without successors.
+    pub fn recompute_entry_exit(&mut self) -> Result<()> {
+        let mut entries = Vec::new();
+        let mut exits = Vec::new();
+        for block in self.blocks() {
+            if self.predecessor_indices(block.index())?.is_empty() {
+                entries.push(block.index());
+            }
+            if self.successor_indices(block.index())?.is_empty() {
+                exits.push(block.index());
+            }
+        }
+
+        if entries.len() != 1 {
+            bail!("Expected one block without predecessors, found {}", entries.len());
+        }
+        if exits.len() != 1 {
+            bail!("Expected one block without successors, found {}", exits.len());
+        }
+
+        self.entry = Some(entries[0]);
+        self.exit = Some(exits[0]);
+
+        Ok(())
+    }
+
     /// Get the entry `Block` index for this `ControlFlowGraph`.
     pub fn entry(&self) -> Option<u64> {
         self.entry
@@ -119,15 +150,271 @@ impl ControlFlowGraph {
     }
 
     /// Get every incoming edge to a block
-    pub fn edges_in(&self, index: u64) -> Option<&Vec<Edge>> {
+    pub fn edges_in(&self, index: u64) -> Option<Vec<&Edge>> {
         self.graph.edges_in(index)
     }
 
     /// Get every outgoing edge from a block
-    pub fn edges_out(&self, index: u64) -> Option<&Vec<Edge>> {
+    pub fn edges_out(&self, index: u64) -> Option<Vec<&Edge>> {
         self.graph.edges_out(index)
     }
 
+    /// Get the indices of every `Block` with an edge to the given block.
+    pub fn predecessor_indices(&self, index: u64) -> Result<Vec<u64>> {
+        Ok(self.graph
+               .edges_in(index)
+               .ok_or(format!("Block 0x{:X} not found", index))?
+               .iter()
+               .map(|edge| edge.head())
+               .collect())
+    }
+
+    /// Get the indices of every `Block` with an edge from the given block.
+    pub fn successor_indices(&self, index: u64) -> Result<Vec<u64>> {
+        Ok(self.graph
+               .edges_out(index)
+               .ok_or(format!("Block 0x{:X} not found", index))?
+               .iter()
+               .map(|edge| edge.tail())
+               .collect())
+    }
+
+
+    /// Get the strongly connected components of this `ControlFlowGraph`.
+    ///
+    /// Each component is a sorted list of `Block` indices which are all
+    /// reachable from each other. A single `Block` is only a component when it
+    /// has an edge to itself.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<u64>> {
+        self.graph.compute_strongly_connected_components()
+    }
+
+
+    /// Get the natural loops of this `ControlFlowGraph`.
+    ///
+    /// A back edge is an edge whose tail dominates its head. Back edges which
+    /// share a header form one `Loop`, and nested loops are returned as
+    /// separate `Loop`s. Loops are ordered by the index of their header.
+    ///
+    /// # Error
+    /// The entry of this `ControlFlowGraph` is not set.
+    pub fn natural_loops(&self) -> Result<Vec<Loop>> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => bail!("entry not set for ControlFlowGraph::natural_loops")
+        };
+
+        let dominators = self.graph.compute_dominators(entry)?;
+
+        let mut back_edges: BTreeMap<u64, Vec<Edge>> = BTreeMap::new();
+        for edge in self.edges() {
+            let is_back_edge = dominators.get(&edge.head())
+                                         .map(|doms| doms.contains(&edge.tail()))
+                                         .unwrap_or(false);
+            if is_back_edge {
+                back_edges.entry(edge.tail()).or_insert(Vec::new()).push(edge.clone());
+            }
+        }
+
+        let mut loops = Vec::new();
+        for (header, back_edges) in back_edges {
+            // the body is the header, and every block which reaches a back
+            // edge without passing through the header
+            let mut body = BTreeSet::new();
+            body.insert(header);
+            let mut queue = back_edges.iter()
+                                      .map(|edge| edge.head())
+                                      .collect::<VecDeque<u64>>();
+            while let Some(index) = queue.pop_front() {
+                if body.insert(index) {
+                    queue.extend(self.predecessor_indices(index)?);
+                }
+            }
+            loops.push(Loop::new(header, body, back_edges));
+        }
+
+        Ok(loops)
+    }
+
+
+    /// Compute the immediate post-dominators of the blocks in this
+    /// `ControlFlowGraph`, including a virtual exit of index `u64::MAX`.
+    ///
+    /// Every block with no successors, and the exit if it is set, is joined to
+    /// the virtual exit. Blocks which do not reach the virtual exit are
+    /// omitted.
+    fn immediate_post_dominators(&self) -> Result<BTreeMap<u64, u64>> {
+        let virtual_exit = u64::max_value();
+
+        let mut reverse = graph::Graph::new();
+        reverse.insert_vertex(graph::NullVertex::new(virtual_exit))?;
+        for block in self.blocks() {
+            reverse.insert_vertex(graph::NullVertex::new(block.index()))?;
+        }
+        for edge in self.edges() {
+            reverse.insert_edge(graph::NullEdge::new(edge.tail(), edge.head()))?;
+        }
+        for block in self.blocks() {
+            if    self.successor_indices(block.index())?.is_empty()
+               || self.exit == Some(block.index()) {
+                reverse.insert_edge(graph::NullEdge::new(virtual_exit, block.index()))?;
+            }
+        }
+
+        // only blocks which reach the virtual exit have post-dominators
+        let reachable = reverse.bfs_reachable(virtual_exit)?;
+        let mut post_dominated = graph::Graph::new();
+        for index in &reachable {
+            post_dominated.insert_vertex(graph::NullVertex::new(*index))?;
+        }
+        for edge in reverse.edges() {
+            let (head, tail) = (graph::Edge::head(edge), graph::Edge::tail(edge));
+            if reachable.contains(&head) && reachable.contains(&tail) {
+                post_dominated.insert_edge(edge.clone())?;
+            }
+        }
+
+        post_dominated.compute_immediate_dominators(virtual_exit)
+    }
+
+
+    /// Compute the post-dominator tree of this `ControlFlowGraph`, mapping the
+    /// index of each `Block` to the index of its immediate post-dominator.
+    ///
+    /// Every block with no successors, and the exit if it is set, is treated
+    /// as an exit. Blocks immediately post-dominated only by leaving the
+    /// `ControlFlowGraph`, and blocks which never leave it, are omitted.
+    pub fn post_dominator_tree(&self) -> Result<BTreeMap<u64, u64>> {
+        let virtual_exit = u64::max_value();
+        Ok(self.immediate_post_dominators()?
+               .into_iter()
+               .filter(|&(_, post_dominator)| post_dominator != virtual_exit)
+               .collect())
+    }
+
+
+    /// Compute the control dependences of this `ControlFlowGraph`, mapping the
+    /// index of each `Block` to the indices of the blocks it is control
+    /// dependent on.
+    ///
+    /// A block is control dependent on a branching block when one edge out of
+    /// the branching block leads to it always executing, and another may not.
+    pub fn control_dependences(&self) -> Result<BTreeMap<u64, BTreeSet<u64>>> {
+        let post_dominators = self.immediate_post_dominators()?;
+
+        let mut dependences: BTreeMap<u64, BTreeSet<u64>> = BTreeMap::new();
+        for block in self.blocks() {
+            dependences.insert(block.index(), BTreeSet::new());
+        }
+
+        for edge in self.edges() {
+            let stop = post_dominators.get(&edge.head()).cloned();
+            // every block from the tail up the post-dominator tree, until the
+            // head's immediate post-dominator, depends on the head
+            let mut runner = Some(edge.tail());
+            while let Some(index) = runner {
+                if Some(index) == stop || !dependences.contains_key(&index) {
+                    break;
+                }
+                dependences.get_mut(&index).unwrap().insert(edge.head());
+                runner = post_dominators.get(&index).cloned();
+            }
+        }
+
+        Ok(dependences)
+    }
+
+
+    /// Get the indices of every `Block` in topological order.
+    ///
+    /// If the entry of this `ControlFlowGraph` is set, it is ordered first.
+    ///
+    /// # Error
+    /// This `ControlFlowGraph` has a cycle. The error identifies the `Block`s
+    /// on one cycle.
+    pub fn topological_sort(&self) -> Result<Vec<u64>> {
+        let mut in_degrees: BTreeMap<u64, usize> = BTreeMap::new();
+        for block in self.blocks() {
+            in_degrees.insert(block.index(), self.predecessor_indices(block.index())?.len());
+        }
+
+        let mut queue = VecDeque::new();
+        if let Some(entry) = self.entry {
+            if in_degrees[&entry] == 0 {
+                queue.push_back(entry);
+            }
+        }
+        for (index, in_degree) in &in_degrees {
+            if *in_degree == 0 && Some(*index) != self.entry {
+                queue.push_back(*index);
+            }
+        }
+
+        let mut order = Vec::new();
+        while let Some(index) = queue.pop_front() {
+            order.push(index);
+            for successor in self.successor_indices(index)? {
+                let in_degree = in_degrees.get_mut(&successor).unwrap();
+                *in_degree -= 1;
+                if *in_degree == 0 {
+                    queue.push_back(successor);
+                }
+            }
+        }
+
+        if order.len() == in_degrees.len() {
+            return Ok(order);
+        }
+
+        let cycle = self.graph.find_cycle()
+                        .unwrap()
+                        .into_iter()
+                        .map(|index| format!("0x{:X}", index))
+                        .collect::<Vec<String>>();
+        bail!("ControlFlowGraph has a cycle through blocks {}", cycle.join(", "))
+    }
+
+
+    /// Create a `CfgCursor` at the first `Instruction` of the `Block` with the
+    /// given index.
+    ///
+    /// # Error
+    /// The `Block` does not exist.
+    pub fn cursor<'c>(&'c mut self, block_index: u64) -> Result<CfgCursor<'c>> {
+        CfgCursor::new(self, block_index)
+    }
+
+
+    /// Compute the structural differences from this `ControlFlowGraph` to
+    /// `other`.
+    ///
+    /// Blocks, instructions and edges only in `other` are reported as added.
+    pub fn diff(&self, other: &ControlFlowGraph) -> CfgDiff {
+        CfgDiff::new(self, other)
+    }
+
+
+    /// Serialize this `ControlFlowGraph` to a JSON document in which blocks
+    /// are keyed by a label of their content and position in the graph,
+    /// rather than by index.
+    ///
+    /// Structurally identical `ControlFlowGraph` give the same document, no
+    /// matter how their blocks are indexed, which makes documents suitable for
+    /// diffing. Temporary scalars are named by block index, and so are not
+    /// stable. Serializing with serde is unaffected.
+    pub fn serialize_stable(&self) -> Result<String> {
+        stable::serialize(self)
+    }
+
+
+    /// Deserialize a `ControlFlowGraph` from a document created by
+    /// `ControlFlowGraph::serialize_stable`.
+    ///
+    /// Blocks are indexed from 0, in the order of their labels.
+    pub fn deserialize_stable(document: &str) -> Result<ControlFlowGraph> {
+        stable::deserialize(document)
+    }
+
 
     /// Sets the address for all instructions in this `ControlFlowGraph`.
     ///
@@ -184,6 +471,221 @@ impl ControlFlowGraph {
     }
 
 
+    /// Splits a `Block` in two, and returns the index of the new `Block`.
+    ///
+    /// The new `Block` receives the `Instruction` with index
+    /// `at_instruction_index`, every `Instruction` after it, and all outgoing
+    /// edges of the original `Block`. An unconditional edge is created from the
+    /// original `Block` to the new `Block`. If the original `Block` was the
+    /// exit of this `ControlFlowGraph`, the new `Block` becomes the exit.
+    ///
+    /// Splitting at the first `Instruction` leaves the original `Block` empty.
+    pub fn split_block(&mut self, block_index: u64, at_instruction_index: u64)
+        -> Result<u64> {
+
+        let instructions = {
+            let block = self.block_mut(block_index)
+                            .ok_or(format!("Block 0x{:X} not found", block_index))?;
+            let position = block.instructions()
+                                .iter()
+                                .position(|instruction|
+                                    instruction.index() == at_instruction_index)
+                                .ok_or(format!("Instruction {} not found in block 0x{:X}",
+                                    at_instruction_index, block_index))?;
+            block.instructions_mut().split_off(position)
+        };
+
+        let mut tail = Block::new(block_index);
+        *tail.instructions_mut() = instructions;
+
+        let new_index = {
+            let new_block = self.new_block()?;
+            new_block.append(&tail);
+            new_block.index()
+        };
+
+        for tail in self.successor_indices(block_index)? {
+            let mut edge = self.graph
+                               .edge(block_index, tail)
+                               .ok_or(format!("Edge 0x{:X} -> 0x{:X} not found", block_index, tail))?
+                               .clone();
+            self.graph.remove_edge(block_index, tail)?;
+            edge.set_head(new_index);
+            self.graph.insert_edge(edge)?;
+        }
+
+        self.unconditional_edge(block_index, new_index)?;
+
+        if self.exit == Some(block_index) {
+            self.exit = Some(new_index);
+        }
+
+        Ok(new_index)
+    }
+
+
+    /// Ensures every `Block` with a conditional outgoing edge has exactly two
+    /// outgoing edges, with complementary conditions.
+    ///
+    /// Conditions are complementary when one is the other negated by `xor`
+    /// with 1, by comparison of a 1-bit `Expression` with 0, or by exchanging
+    /// `Cmpeq` and `Cmpneq`, ignoring the order of operands to commutative
+    /// operations. Other complementary conditions are not recognized.
+    /// # Error
+    /// The first `Block` found with conditional outgoing edges which do not
+    /// meet these requirements.
+    pub fn verify_conditional_branches(&self) -> Result<()> {
+        for block in self.blocks() {
+            let mut edges = Vec::new();
+            for tail in self.successor_indices(block.index())? {
+                edges.push(self.edge(block.index(), tail).ok_or("Could not find edge")?);
+            }
+            if edges.iter().all(|edge| edge.condition().is_none()) {
+                continue;
+            }
+            if edges.len() != 2 {
+                bail!("Block 0x{:X} has a conditional edge and {} outgoing edges",
+                      block.index(), edges.len());
+            }
+            let complementary = match (edges[0].condition(), edges[1].condition()) {
+                (&Some(ref lhs), &Some(ref rhs)) => complementary(lhs, rhs),
+                _ => false
+            };
+            if !complementary {
+                bail!("Block 0x{:X} has edges {} and {} which are not complementary",
+                      block.index(), edges[0], edges[1]);
+            }
+        }
+        Ok(())
+    }
+
+
+    /// Splits every critical edge, and returns the number of edges split.
+    ///
+    /// An edge is critical when its head has more than one successor and its
+    /// tail has more than one predecessor. A critical edge is replaced by an
+    /// edge, with the original condition and comment, to a new empty `Block`,
+    /// and an unconditional edge from that `Block` to the original tail.
+    pub fn split_critical_edges(&mut self) -> Result<usize> {
+        let mut critical = Vec::new();
+        for edge in self.edges() {
+            if self.successor_indices(edge.head())?.len() > 1
+                && self.predecessor_indices(edge.tail())?.len() > 1 {
+                critical.push(edge.clone());
+            }
+        }
+
+        for edge in &critical {
+            self.graph.remove_edge(edge.head(), edge.tail())?;
+            let index = self.new_block()?.index();
+            let mut head_edge = Edge::new(edge.head(), index, edge.condition().clone());
+            head_edge.set_comment(edge.comment().clone());
+            self.graph.insert_edge(head_edge)?;
+            self.unconditional_edge(index, edge.tail())?;
+        }
+
+        Ok(critical.len())
+    }
+
+
+    /// Removes every `Block`, and its edges, which is not reachable from the
+    /// entry of this `ControlFlowGraph`.
+    ///
+    /// # Error
+    /// The entry of this `ControlFlowGraph` is not set.
+    pub fn remove_unreachable(&mut self) -> Result<()> {
+        let entry = match self.entry {
+            Some(entry) => entry,
+            None => bail!("entry not set for ControlFlowGraph::remove_unreachable")
+        };
+
+        let reachable = self.graph.bfs_reachable(entry)?;
+
+        let unreachable = self.blocks()
+                              .iter()
+                              .map(|block| block.index())
+                              .filter(|index| !reachable.contains(index))
+                              .collect::<Vec<u64>>();
+
+        for index in unreachable {
+            self.graph.remove_vertex(index)?;
+            if self.exit == Some(index) {
+                self.exit = None;
+            }
+        }
+
+        Ok(())
+    }
+
+
+    /// Merges the `Block` `successor` into the `Block` `predecessor`.
+    ///
+    /// The instructions of `successor` are appended to `predecessor`, the
+    /// outgoing edges of `successor` become outgoing edges of `predecessor`,
+    /// and `successor` is removed.
+    ///
+    /// # Error
+    /// The edge from `predecessor` to `successor` is not the only outgoing
+    /// edge of `predecessor`, is conditional, or is not the only incoming edge
+    /// of `successor`.
+    pub fn merge_block(&mut self, predecessor: u64, successor: u64) -> Result<()> {
+        if predecessor == successor {
+            bail!("Can not merge block 0x{:X} into itself", predecessor);
+        }
+
+        match self.edge(predecessor, successor) {
+            Some(edge) => if edge.condition().is_some() {
+                bail!("Edge 0x{:X}->0x{:X} is conditional", predecessor, successor);
+            },
+            None => bail!("No edge 0x{:X}->0x{:X}", predecessor, successor)
+        }
+
+        if self.graph.edges_out(predecessor).map(|edges| edges.len()) != Some(1) {
+            bail!("Block 0x{:X} has more than one successor", predecessor);
+        }
+
+        if self.graph.edges_in(successor).map(|edges| edges.len()) != Some(1) {
+            bail!("Block 0x{:X} has more than one predecessor", successor);
+        }
+
+        // merge the blocks
+        let successor_block = self.graph
+                                  .vertex(successor)
+                                  .ok_or("Could not find block")?
+                                  .clone();
+        self.graph
+            .vertex_mut(predecessor)
+            .ok_or("Could not find block")?
+            .append(&successor_block);
+
+        // all of successor's successors become predecessor's successors
+        let mut new_edges = Vec::new();
+        for edge in self.graph.edges_out(successor).unwrap() {
+            let head = predecessor;
+            let tail = edge.tail();
+            let condition = edge.condition().clone();
+            let edge = Edge::new(head, tail, condition);
+            new_edges.push(edge);
+        }
+
+        // remove the block we just merged
+        self.graph.remove_vertex(successor)?;
+
+        for edge in new_edges {
+            self.graph.insert_edge(edge)?;
+        }
+
+        if self.entry == Some(successor) {
+            self.entry = Some(predecessor);
+        }
+        if self.exit == Some(successor) {
+            self.exit = Some(predecessor);
+        }
+
+        Ok(())
+    }
+
+
     /// Merge `Block`s.
     ///
     /// When a `Block` as only one successor, and that successor has only one predecessor, we
@@ -212,6 +714,11 @@ impl ControlFlowGraph {
                     None => bail!("successor not found")
                 };
 
+                // a block which loops to itself can not be merged
+                if successor == block.index() {
+                    continue;
+                }
+
                 // get all predecessors for this successor
                 let predecessors = self.graph.edges_in(successor).unwrap();
 
@@ -233,31 +740,7 @@ impl ControlFlowGraph {
                     None => bail!("merge_index set, but not successor_index")
                 };
 
-                // merge the blocks
-                let successor_block = self.graph
-                                          .vertex(successor_index)
-                                          .ok_or("Could not find block")?
-                                          .clone();
-                self.graph
-                    .vertex_mut(merge_index)
-                    .ok_or("Could not find block")?
-                    .append(&successor_block);
-
-                // all of successor's successors become merge_block's successors
-                let mut new_edges = Vec::new();
-                for edge in self.graph.edges_out(successor_index).unwrap() {
-                    let head = merge_index;
-                    let tail = edge.tail();
-                    let condition = edge.condition().clone();
-                    let edge = Edge::new(head, tail, condition);
-                    new_edges.push(edge);
-                }
-                for edge in new_edges {
-                    self.graph.insert_edge(edge)?;
-                }
-
-                // remove the block we just merged
-                self.graph.remove_vertex(successor_index)?;
+                self.merge_block(merge_index, successor_index)?;
             } else {
                 break;
             }
@@ -266,25 +749,196 @@ impl ControlFlowGraph {
     }
 
 
-    /// Appends a control flow graph to this control flow graph.
+    /// Merges `Block`s with identical instructions and identical outgoing
+    /// edges, and returns the number of `Block`s removed.
     ///
-    /// In order for this to work, the entry and exit of boths graphs must be
-    /// set, which should be the case for all conformant translators. You can
-    /// also append to an empty ControlFlowGraph.
-    pub fn append(&mut self, other: &ControlFlowGraph) -> Result<()> {
-        let is_empty = match self.graph.num_vertices() {
-            0 => true,
-            _ => false
-        };
+    /// Instructions are compared by their operations. The incoming edges of a
+    /// removed `Block` are redirected to the `Block` it is merged into, keeping
+    /// their conditions. Where both are reached from the same predecessor, the
+    /// two conditions are joined with `Expression::or`, or the edge is left
+    /// unconditional if either was. Merging repeats until no two `Block`s are
+    /// identical, and the entry and exit are kept when merging.
+    pub fn tail_merge(&mut self) -> Result<usize> {
+        let mut merges = 0;
+        while let Some((survivor, duplicate)) = self.tail_merge_candidate()? {
+            self.redirect_block(duplicate, survivor)?;
+            merges += 1;
+        }
+        Ok(merges)
+    }
+
+
+    /// Find two identical `Block`s, the one to keep and the one to remove.
+    fn tail_merge_candidate(&self) -> Result<Option<(u64, u64)>> {
+        // Visiting the entry and exit first keeps them when they are merged.
+        // Each block is visited once, even when it is both entry and exit.
+        let mut indices = Vec::new();
+        let blocks = self.blocks().into_iter().map(|block| block.index());
+        for index in self.entry.into_iter().chain(self.exit).chain(blocks) {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+
+        let mut seen: BTreeMap<(Vec<&Operation>, Vec<(u64, Option<Expression>)>), u64> =
+            BTreeMap::new();
+        for index in indices {
+            let block = self.block(index).ok_or("Could not find block")?;
+            let operations = block.instructions()
+                                  .iter()
+                                  .map(|instruction| instruction.operation())
+                                  .collect::<Vec<&Operation>>();
+            let mut successors = Vec::new();
+            for tail in self.successor_indices(index)? {
+                let edge = self.edge(index, tail).ok_or("Could not find edge")?;
+                successors.push((tail, edge.condition().clone()));
+            }
+            successors.sort();
+
+            let key = (operations, successors);
+            if let Some(survivor) = seen.get(&key) {
+                return Ok(Some((*survivor, index)));
+            }
+            seen.insert(key, index);
+        }
+
+        Ok(None)
+    }
+
+
+    /// Remove the `Block` duplicate, redirecting its incoming edges to
+    /// survivor.
+    fn redirect_block(&mut self, duplicate: u64, survivor: u64) -> Result<()> {
+        let mut incoming = Vec::new();
+        for head in self.predecessor_indices(duplicate)? {
+            if head != duplicate {
+                incoming.push(self.edge(head, duplicate).ok_or("Could not find edge")?.clone());
+            }
+        }
+
+        self.graph.remove_vertex(duplicate)?;
+
+        for edge in incoming {
+            let head = edge.head();
+            let condition = match self.edge(head, survivor).map(|edge| edge.condition().clone()) {
+                Some(existing) => {
+                    self.graph.remove_edge(head, survivor)?;
+                    match (existing, edge.condition().clone()) {
+                        (Some(existing), Some(condition)) =>
+                            Some(Expression::or(existing, condition)?),
+                        _ => None
+                    }
+                },
+                None => edge.condition().clone()
+            };
+            let mut redirected = Edge::new(head, survivor, condition);
+            redirected.set_comment(edge.comment().clone());
+            self.graph.insert_edge(redirected)?;
+        }
+
+        if self.entry == Some(duplicate) {
+            self.entry = Some(survivor);
+        }
+        if self.exit == Some(duplicate) {
+            self.exit = Some(survivor);
+        }
+
+        Ok(())
+    }
+
+
+    /// Unrolls the natural loop with the given header, placing `times` copies
+    /// of its body in sequence after the original.
+    ///
+    /// The back edges of the original body go to the header of the first
+    /// copy, the back edges of each copy go to the header of the next, and the
+    /// back edges of the last copy go to the original header. Edges leaving
+    /// the loop are copied with each copy of the body.
+    ///
+    /// # Error
+    /// header is not the header of a natural loop.
+    pub fn unroll_loop(&mut self, header: u64, times: usize) -> Result<()> {
+        let natural_loop = self.natural_loops()?
+                               .into_iter()
+                               .find(|natural_loop| natural_loop.header() == header)
+                               .ok_or(format!("Block 0x{:X} is not a loop header", header))?;
+
+        let is_back_edge = |edge: &Edge| edge.tail() == header
+                                         && natural_loop.body().contains(&edge.head());
+
+        // Every edge out of a block in the loop, looked up by head and tail
+        let mut edges = Vec::new();
+        for head in natural_loop.body() {
+            for tail in self.successor_indices(*head)? {
+                edges.push(self.edge(*head, tail).ok_or("Could not find edge")?.clone());
+            }
+        }
+
+        let back_edges = edges.iter()
+                              .filter(|edge| is_back_edge(edge))
+                              .cloned()
+                              .collect::<Vec<Edge>>();
+        for edge in &back_edges {
+            self.graph.remove_edge(edge.head(), edge.tail())?;
+        }
+
+        // The indices of the body blocks in the previous copy
+        let mut previous: BTreeMap<u64, u64> = natural_loop.body()
+                                                           .iter()
+                                                           .map(|index| (*index, *index))
+                                                           .collect();
+
+        for _ in 0..times {
+            let mut copy: BTreeMap<u64, u64> = BTreeMap::new();
+            for index in natural_loop.body() {
+                let block = self.block(*index)
+                                .ok_or("Could not find block")?
+                                .clone_new_index(self.next_index);
+                copy.insert(*index, self.next_index);
+                self.next_index += 1;
+                self.graph.insert_vertex(block)?;
+            }
+
+            for edge in &edges {
+                let tail = if is_back_edge(edge) {
+                    continue;
+                }
+                else {
+                    copy.get(&edge.tail()).cloned().unwrap_or(edge.tail())
+                };
+                let mut new_edge = Edge::new(copy[&edge.head()], tail, edge.condition().clone());
+                new_edge.set_comment(edge.comment().clone());
+                self.graph.insert_edge(new_edge)?;
+            }
+
+            for edge in &back_edges {
+                let mut new_edge = Edge::new(previous[&edge.head()],
+                                             copy[&header],
+                                             edge.condition().clone());
+                new_edge.set_comment(edge.comment().clone());
+                self.graph.insert_edge(new_edge)?;
+            }
 
-        if !is_empty && (self.entry().is_none() || self.exit().is_none()) {
-            return Err("entry/exit not set for dest ControlFlowGraph::append".into());
+            previous = copy;
         }
-        
-        if other.entry().is_none() || other.exit().is_none() {
-            return Err("entry/exit not set for src ControlFlowGraph::append".into());
+
+        for edge in back_edges {
+            let mut new_edge = Edge::new(previous[&edge.head()], header, edge.condition().clone());
+            new_edge.set_comment(edge.comment().clone());
+            self.graph.insert_edge(new_edge)?;
         }
 
+        Ok(())
+    }
+
+
+    /// Appends the blocks and edges of another control flow graph to this
+    /// control flow graph, and returns a map from the indices of the blocks
+    /// in `other` to their new indices in this graph.
+    ///
+    /// No edges are created between the existing blocks and the appended
+    /// blocks. The entry and exit of this control flow graph are unchanged.
+    pub fn append(&mut self, other: &ControlFlowGraph) -> Result<BTreeMap<u64, u64>> {
         // Bring in new blocks
         let mut block_map: BTreeMap<u64, u64> = BTreeMap::new();
         for block in other.graph().vertices() {
@@ -303,24 +957,7 @@ impl ControlFlowGraph {
             self.graph.insert_edge(new_edge)?;
         }
 
-
-        if is_empty {
-            self.entry = Some(block_map[&other.entry().unwrap()]);
-        }
-        else {
-            // Create an edge from the exit of this graph to the head of the other
-            // graph
-            let transition_edge = Edge::new(
-                self.exit.unwrap(),
-                block_map[&(other.entry().unwrap())],
-                None
-            );
-            self.graph.insert_edge(transition_edge)?;
-        }
-
-        self.exit = Some(block_map[&other.exit().unwrap()]);
-
-        Ok(())
+        Ok(block_map)
     }
 
     /// Inserts a control flow graph into this control flow graph, and returns
@@ -336,49 +973,58 @@ impl ControlFlowGraph {
     /// # Warnings
     /// This invalidates the entry and exit of the control flow graph.
     pub fn insert(&mut self, other: &ControlFlowGraph) -> Result<(u64, u64)> {
-        if other.entry().is_none() || other.exit().is_none() {
-            bail!("entry/exit not set on control flow graph");
-        }
+        let (entry, exit) = match (other.entry(), other.exit()) {
+            (Some(entry), Some(exit)) => (entry, exit),
+            _ => bail!("entry/exit not set on control flow graph")
+        };
 
         // our entry and exit our no longer valid
         self.entry = None;
         self.exit = None;
 
-        // Options to store the other graph entry/exit indices
-        let mut entry_index = None;
-        let mut exit_index = None;
-
-        // keep track of mapping between old indices and new indices
-        let mut block_map: BTreeMap<u64, u64> = BTreeMap::new();
+        let block_map = self.append(other)?;
 
-        // insert all the blocks
-        for block in other.graph().vertices() {
-            let new_block = block.clone_new_index(self.next_index);
-            block_map.insert(block.index(), self.next_index);
-            if block.index() == other.entry().unwrap() {
-                entry_index = Some(self.next_index);
-            }
-            if block.index() == other.exit().unwrap() {
-                exit_index = Some(self.next_index);
-            }
-            self.next_index += 1;
-            self.graph.insert_vertex(new_block)?;
-        }
+        Ok((block_map[&entry], block_map[&exit]))
+    }
+}
 
-        // insert edges
-        for edge in other.graph().edges() {
-            let new_head: u64 = block_map[&edge.head()];
-            let new_tail: u64 = block_map[&edge.tail()];
-            let new_edge = Edge::new(new_head, new_tail, edge.condition().clone());
-            self.graph.insert_edge(new_edge)?;
-        }
 
-        if entry_index.is_none() || exit_index.is_none() {
-            bail!("failed to get entry/exit indices");
+/// Returns true if one condition is recognizably the negation of the other.
+fn complementary(lhs: &Expression, rhs: &Expression) -> bool {
+    // The condition with negations removed, and whether an odd number of
+    // negations was removed.
+    fn base(condition: &Expression) -> (Expression, bool) {
+        let is = |expression: &Expression, value: u64|
+            expression.as_constant().map(|constant| constant.value() == value) == Some(true);
+        let is_one = |expression: &Expression| is(expression, 1);
+        let is_zero = |expression: &Expression| is(expression, 0);
+        let (inner, negated) = match *condition {
+            Expression::Xor(ref lhs, ref rhs) if is_one(rhs) => (Some(&**lhs), true),
+            Expression::Xor(ref lhs, ref rhs) if is_one(lhs) => (Some(&**rhs), true),
+            Expression::Cmpeq(ref lhs, ref rhs) if lhs.bits() == 1 && is_zero(rhs) =>
+                (Some(&**lhs), true),
+            Expression::Cmpeq(ref lhs, ref rhs) if rhs.bits() == 1 && is_zero(lhs) =>
+                (Some(&**rhs), true),
+            Expression::Cmpneq(ref lhs, ref rhs) if lhs.bits() == 1 && is_zero(rhs) =>
+                (Some(&**lhs), false),
+            Expression::Cmpneq(ref lhs, ref rhs) if rhs.bits() == 1 && is_zero(lhs) =>
+                (Some(&**rhs), false),
+            Expression::Cmpneq(ref lhs, ref rhs) =>
+                return (Expression::Cmpeq(lhs.clone(), rhs.clone()).canonicalize(), true),
+            _ => (None, false)
+        };
+        match inner {
+            Some(inner) => {
+                let (inner, inner_negated) = base(inner);
+                (inner, inner_negated != negated)
+            },
+            None => (condition.canonicalize(), false)
         }
-
-        Ok((entry_index.unwrap(), exit_index.unwrap()))
     }
+
+    let (lhs, lhs_negated) = base(lhs);
+    let (rhs, rhs_negated) = base(rhs);
+    lhs == rhs && lhs_negated != rhs_negated
 }
 
 
@@ -398,4 +1044,646 @@ impl fmt::Display for ControlFlowGraph {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn split_block() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.assign(scalar("b", 32), expr_const(2, 32));
+        block.assign(scalar("c", 32), expr_const(3, 32));
+        block.index()
+    };
+    let left_index = cfg.new_block().unwrap().index();
+    let right_index = cfg.new_block().unwrap().index();
+
+    cfg.conditional_edge(head_index, left_index, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(head_index, right_index, expr_const(0, 1)).unwrap();
+    cfg.edge_mut(head_index, left_index).unwrap().set_comment(Some("taken".to_string()));
+    cfg.set_entry(head_index).unwrap();
+    cfg.set_exit(head_index).unwrap();
+
+    let split_index = cfg.split_block(head_index, 1).unwrap();
+
+    assert_eq!(cfg.block(head_index).unwrap().instructions().len(), 1);
+    assert_eq!(cfg.block(split_index).unwrap().instructions().len(), 2);
+
+    let successors = cfg.edges_out(head_index).unwrap();
+    assert_eq!(successors.len(), 1);
+    assert_eq!(successors[0].tail(), split_index);
+    assert!(successors[0].condition().is_none());
+
+    let successors = cfg.edges_out(split_index).unwrap();
+    assert_eq!(successors.len(), 2);
+    let left = cfg.edge(split_index, left_index).unwrap();
+    assert_eq!(left.condition(), &Some(expr_const(1, 1)));
+    assert_eq!(left.comment(), &Some("taken".to_string()));
+    let right = cfg.edge(split_index, right_index).unwrap();
+    assert_eq!(right.condition(), &Some(expr_const(0, 1)));
+    assert!(right.comment().is_none());
+    assert_eq!(cfg.exit(), Some(split_index));
+
+    // Splitting at the first instruction moves every instruction
+    let degenerate_index = cfg.split_block(split_index, 0).unwrap();
+    assert!(cfg.block(split_index).unwrap().is_empty());
+    assert_eq!(cfg.block(degenerate_index).unwrap().instructions().len(), 2);
+    assert_eq!(cfg.edges_out(degenerate_index).unwrap().len(), 2);
+
+    assert!(cfg.split_block(head_index, 7).is_err());
+}
+
+
+#[test]
+fn merge_block() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let mut indices = Vec::new();
+    for i in 0..3 {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(i, 32));
+        indices.push(block.index());
+    }
+    cfg.unconditional_edge(indices[0], indices[1]).unwrap();
+    cfg.unconditional_edge(indices[1], indices[2]).unwrap();
+    cfg.set_entry(indices[0]).unwrap();
+    cfg.set_exit(indices[2]).unwrap();
+
+    cfg.merge_block(indices[1], indices[2]).unwrap();
+    cfg.merge_block(indices[0], indices[1]).unwrap();
+
+    assert_eq!(cfg.blocks().len(), 1);
+    assert_eq!(cfg.edges().len(), 0);
+    assert_eq!(cfg.exit(), Some(indices[0]));
+
+    let values = cfg.block(indices[0])
+                    .unwrap()
+                    .instructions()
+                    .iter()
+                    .map(|instruction| match *instruction.operation() {
+                        Operation::Assign { ref src, .. } => src.clone(),
+                        _ => panic!("expected assign")
+                    })
+                    .collect::<Vec<Expression>>();
+    assert_eq!(values, vec![expr_const(0, 32), expr_const(1, 32), expr_const(2, 32)]);
+
+    let mut cfg = ControlFlowGraph::new();
+    let head = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+    cfg.conditional_edge(head, tail, expr_const(1, 1)).unwrap();
+    assert!(cfg.merge_block(head, tail).is_err());
+}
+
+
+#[test]
+fn remove_unreachable() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let entry = cfg.new_block().unwrap().index();
+    let live = cfg.new_block().unwrap().index();
+    let dead = cfg.new_block().unwrap().index();
+    let dead_successor = cfg.new_block().unwrap().index();
+
+    cfg.conditional_edge(entry, live, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(entry, dead, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(dead, dead_successor).unwrap();
+    cfg.unconditional_edge(dead_successor, live).unwrap();
+    cfg.unconditional_edge(dead_successor, dead_successor).unwrap();
+
+    assert!(cfg.remove_unreachable().is_err());
+    cfg.set_entry(entry).unwrap();
+
+    cfg.graph.remove_edge(entry, dead).unwrap();
+    cfg.remove_unreachable().unwrap();
+
+    assert!(cfg.block(entry).is_some());
+    assert!(cfg.block(live).is_some());
+    assert!(cfg.block(dead).is_none());
+    assert!(cfg.block(dead_successor).is_none());
+
+    assert_eq!(cfg.edges().len(), 1);
+    assert_eq!(cfg.edges_in(live).unwrap().len(), 1);
+    assert!(cfg.edge(dead_successor, live).is_none());
+}
+
+
+#[test]
+fn predecessor_successor_indices() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = cfg.new_block().unwrap().index();
+    let left = cfg.new_block().unwrap().index();
+    let right = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+
+    cfg.conditional_edge(head, left, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(head, right, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(left, tail).unwrap();
+    cfg.unconditional_edge(right, tail).unwrap();
+
+    let mut predecessors = cfg.predecessor_indices(tail).unwrap();
+    predecessors.sort();
+    assert_eq!(predecessors, vec![left, right]);
+    assert_eq!(cfg.edges_in(tail).unwrap().len(), 2);
+
+    let mut successors = cfg.successor_indices(head).unwrap();
+    successors.sort();
+    assert_eq!(successors, vec![left, right]);
+    assert_eq!(cfg.successor_indices(tail).unwrap(), Vec::<u64>::new());
+    assert_eq!(cfg.predecessor_indices(head).unwrap(), Vec::<u64>::new());
+
+    assert!(cfg.predecessor_indices(7).is_err());
+    assert!(cfg.successor_indices(7).is_err());
+    assert!(cfg.edges_in(7).is_none());
+}
+
+
+#[test]
+fn topological_sort() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let tail = cfg.new_block().unwrap().index();
+    let left = cfg.new_block().unwrap().index();
+    let right = cfg.new_block().unwrap().index();
+    let head = cfg.new_block().unwrap().index();
+
+    cfg.conditional_edge(head, left, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(head, right, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(left, tail).unwrap();
+    cfg.unconditional_edge(right, tail).unwrap();
+    cfg.unconditional_edge(left, right).unwrap();
+    cfg.set_entry(head).unwrap();
+
+    let order = cfg.topological_sort().unwrap();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], head);
+    let position = |index| order.iter().position(|i| *i == index).unwrap();
+    for edge in cfg.edges() {
+        assert!(position(edge.head()) < position(edge.tail()));
+    }
+}
+
+
+#[test]
+fn topological_sort_cycle() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = cfg.new_block().unwrap().index();
+    let looping = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+
+    cfg.unconditional_edge(head, looping).unwrap();
+    cfg.unconditional_edge(looping, looping).unwrap();
+    cfg.unconditional_edge(looping, tail).unwrap();
+    cfg.set_entry(head).unwrap();
+
+    let error = cfg.topological_sort().unwrap_err();
+    assert!(error.to_string().contains(&format!("0x{:X}", looping)));
+}
+
+
+#[test]
+fn strongly_connected_components() {
+    // entry -> header -> body -> latch -> header
+    //          header -> exit
+    //          spin -> spin
+    let mut cfg = ControlFlowGraph::new();
+
+    let entry = cfg.new_block().unwrap().index();
+    let header = cfg.new_block().unwrap().index();
+    let body = cfg.new_block().unwrap().index();
+    let latch = cfg.new_block().unwrap().index();
+    let exit = cfg.new_block().unwrap().index();
+    let spin = cfg.new_block().unwrap().index();
+
+    cfg.unconditional_edge(entry, header).unwrap();
+    cfg.conditional_edge(header, body, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(header, exit, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(body, latch).unwrap();
+    cfg.unconditional_edge(latch, header).unwrap();
+    cfg.unconditional_edge(exit, spin).unwrap();
+    cfg.unconditional_edge(spin, spin).unwrap();
+
+    let mut components = cfg.strongly_connected_components();
+    components.sort();
+    assert_eq!(components, vec![vec![header, body, latch], vec![spin]]);
+}
+
+
+#[test]
+fn natural_loops() {
+    // entry -> outer -> inner -> inner_latch -> inner
+    //                   inner_latch -> outer_latch -> outer
+    //          outer -> exit
+    let mut cfg = ControlFlowGraph::new();
+
+    let entry = cfg.new_block().unwrap().index();
+    let outer = cfg.new_block().unwrap().index();
+    let inner = cfg.new_block().unwrap().index();
+    let inner_latch = cfg.new_block().unwrap().index();
+    let outer_latch = cfg.new_block().unwrap().index();
+    let exit = cfg.new_block().unwrap().index();
+
+    cfg.unconditional_edge(entry, outer).unwrap();
+    cfg.conditional_edge(outer, inner, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(outer, exit, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(inner, inner_latch).unwrap();
+    cfg.conditional_edge(inner_latch, inner, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(inner_latch, outer_latch, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(outer_latch, outer).unwrap();
+
+    assert!(cfg.natural_loops().is_err());
+    cfg.set_entry(entry).unwrap();
+
+    let loops = cfg.natural_loops().unwrap();
+    assert_eq!(loops.len(), 2);
+
+    assert_eq!(loops[0].header(), outer);
+    assert_eq!(loops[0].body().iter().cloned().collect::<Vec<u64>>(),
+               vec![outer, inner, inner_latch, outer_latch]);
+    assert_eq!(loops[0].back_edges().len(), 1);
+    assert_eq!(loops[0].back_edges()[0].head(), outer_latch);
+
+    assert_eq!(loops[1].header(), inner);
+    assert_eq!(loops[1].body().iter().cloned().collect::<Vec<u64>>(),
+               vec![inner, inner_latch]);
+    assert_eq!(loops[1].back_edges()[0].head(), inner_latch);
+    assert!(!loops[1].contains(outer));
+}
+
+
+#[test]
+fn recompute_entry_exit() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = cfg.new_block().unwrap().index();
+    let left = cfg.new_block().unwrap().index();
+    let right = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+
+    cfg.conditional_edge(head, left, expr_const(1, 1)).unwrap();
+    cfg.conditional_edge(head, right, expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(left, tail).unwrap();
+
+    // right and tail both have no successors
+    assert!(cfg.recompute_entry_exit().is_err());
+    assert_eq!(cfg.entry(), None);
+    assert_eq!(cfg.exit(), None);
+
+    cfg.unconditional_edge(right, tail).unwrap();
+    cfg.recompute_entry_exit().unwrap();
+    assert_eq!(cfg.entry(), Some(head));
+    assert_eq!(cfg.exit(), Some(tail));
+}
+
+
+#[test]
+fn append() {
+    let mut cfg = ControlFlowGraph::new();
+    let head = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+    cfg.unconditional_edge(head, tail).unwrap();
+    cfg.set_entry(head).unwrap();
+    cfg.set_exit(tail).unwrap();
+
+    let mut other = ControlFlowGraph::new();
+    let other_head = other.new_block().unwrap().index();
+    let other_tail = other.new_block().unwrap().index();
+    let condition = Expression::cmpeq(expr_scalar("a", 32), expr_const(0, 32)).unwrap();
+    other.conditional_edge(other_head, other_tail, condition.clone()).unwrap();
+
+    let block_map = cfg.append(&other).unwrap();
+    assert_eq!(block_map.len(), 2);
+    assert!(block_map[&other_head] != head && block_map[&other_head] != tail);
+
+    let edge = cfg.edge(block_map[&other_head], block_map[&other_tail]).unwrap();
+    assert_eq!(edge.condition(), &Some(condition));
+
+    assert_eq!(cfg.blocks().len(), 4);
+    assert_eq!(cfg.edges().len(), 2);
+    assert_eq!(cfg.entry(), Some(head));
+    assert_eq!(cfg.exit(), Some(tail));
+}
+
+
+#[test]
+fn diff() {
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.index()
+    };
+    let tail = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("b", 32), expr_scalar("a", 32));
+        block.index()
+    };
+    cfg.unconditional_edge(head, tail).unwrap();
+    cfg.set_entry(head).unwrap();
+
+    assert!(cfg.diff(&cfg.clone()).is_empty());
+
+    let mut other = cfg.clone();
+    other.block_mut(tail).unwrap().nop();
+
+    let diff = cfg.diff(&other);
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff.added_operations()[&tail], vec![Operation::Nop]);
+    assert_eq!(diff.to_string(), "block 0x1\n  + nop\n");
+
+    let diff = other.diff(&cfg);
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff.removed_operations()[&tail], vec![Operation::Nop]);
+}
+
+
+#[test]
+fn control_dependences() {
+    // head -> then -> merge
+    // head -> merge
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = cfg.new_block().unwrap().index();
+    let then = cfg.new_block().unwrap().index();
+    let merge = cfg.new_block().unwrap().index();
+
+    let condition = expr_scalar("c", 1);
+    cfg.conditional_edge(head, then, condition.clone()).unwrap();
+    cfg.conditional_edge(head, merge,
+        Expression::cmpeq(condition, expr_const(0, 1)).unwrap()).unwrap();
+    cfg.unconditional_edge(then, merge).unwrap();
+    cfg.set_entry(head).unwrap();
+
+    let post_dominator_tree = cfg.post_dominator_tree().unwrap();
+    assert_eq!(post_dominator_tree[&head], merge);
+    assert_eq!(post_dominator_tree[&then], merge);
+    assert!(!post_dominator_tree.contains_key(&merge));
+
+    let dependences = cfg.control_dependences().unwrap();
+    assert_eq!(dependences[&then], vec![head].into_iter().collect());
+    assert!(dependences[&merge].is_empty());
+    assert!(dependences[&head].is_empty());
+}
+
+
+#[test]
+fn control_dependences_loop() {
+    // head -> body -> head
+    // head -> tail
+    let mut cfg = ControlFlowGraph::new();
+
+    let head = cfg.new_block().unwrap().index();
+    let body = cfg.new_block().unwrap().index();
+    let tail = cfg.new_block().unwrap().index();
+
+    let condition = expr_scalar("c", 1);
+    cfg.conditional_edge(head, body, condition.clone()).unwrap();
+    cfg.conditional_edge(head, tail,
+        Expression::cmpeq(condition, expr_const(0, 1)).unwrap()).unwrap();
+    cfg.unconditional_edge(body, head).unwrap();
+    cfg.set_entry(head).unwrap();
+
+    let dependences = cfg.control_dependences().unwrap();
+    assert_eq!(dependences[&body], vec![head].into_iter().collect());
+    assert_eq!(dependences[&head], vec![head].into_iter().collect());
+    assert!(dependences[&tail].is_empty());
+}
+
+
+/// A diamond, head -> left ? (c), head -> right ? (!c), left -> tail and
+/// right -> tail, with its blocks created in the given order of positions.
+///
+/// Each block assigns its position to `a` at `address + position * 4`, so the
+/// same diamond built in two orders differs only by block indices.
+#[cfg(test)]
+pub(crate) fn test_diamond(order: &[usize], address: u64) -> ControlFlowGraph {
+    let mut cfg = ControlFlowGraph::new();
+    let mut indices = [0; 4];
+    for &position in order {
+        let block = cfg.new_block().unwrap();
+        block.assign_at(scalar("a", 32),
+                        expr_const(position as u64, 32),
+                        Some(address + position as u64 * 4));
+        indices[position] = block.index();
+    }
+    let taken = expr_scalar("c", 1);
+    let not_taken = Expression::cmpeq(expr_scalar("c", 1), expr_const(0, 1)).unwrap();
+    cfg.conditional_edge(indices[0], indices[1], taken).unwrap();
+    cfg.conditional_edge(indices[0], indices[2], not_taken).unwrap();
+    cfg.unconditional_edge(indices[1], indices[3]).unwrap();
+    cfg.unconditional_edge(indices[2], indices[3]).unwrap();
+    cfg.set_entry(indices[0]).unwrap();
+    cfg.set_exit(indices[3]).unwrap();
+    cfg
+}
+
+
+#[test]
+fn serialize_stable() {
+    let forward = test_diamond(&[0, 1, 2, 3], 0);
+    let backward = test_diamond(&[3, 2, 1, 0], 0);
+    assert!(forward != backward);
+
+    let document = forward.serialize_stable().unwrap();
+    assert_eq!(document, backward.serialize_stable().unwrap());
+
+    let rebuilt = ControlFlowGraph::deserialize_stable(&document).unwrap();
+    assert_eq!(rebuilt.blocks().len(), 4);
+    assert_eq!(rebuilt.edges().len(), 4);
+    assert_eq!(rebuilt.serialize_stable().unwrap(), document);
+    assert_eq!(ControlFlowGraph::deserialize_stable(&document).unwrap(), rebuilt);
+
+    let mut changed = backward.clone();
+    changed.block_mut(0).unwrap().nop();
+    assert!(changed.serialize_stable().unwrap() != document);
+}
+
+
+#[test]
+fn tail_merge() {
+    /*
+    head -> left ? (c), head -> middle ? (!c), middle -> right
+    left and right are identical, and both go to tail
+    */
+    let mut cfg = ControlFlowGraph::new();
+
+    let head_index = cfg.new_block().unwrap().index();
+    let middle_index = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("b", 32), expr_const(2, 32));
+        block.index()
+    };
+    let duplicate = |cfg: &mut ControlFlowGraph| {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.index()
+    };
+    let left_index = duplicate(&mut cfg);
+    let right_index = duplicate(&mut cfg);
+    let tail_index = cfg.new_block().unwrap().index();
+
+    let taken = expr_scalar("c", 1);
+    let not_taken = Expression::cmpeq(expr_scalar("c", 1), expr_const(0, 1)).unwrap();
+    cfg.conditional_edge(head_index, left_index, taken.clone()).unwrap();
+    cfg.conditional_edge(head_index, middle_index, not_taken.clone()).unwrap();
+    cfg.unconditional_edge(middle_index, right_index).unwrap();
+    cfg.unconditional_edge(left_index, tail_index).unwrap();
+    cfg.unconditional_edge(right_index, tail_index).unwrap();
+    cfg.set_entry(head_index).unwrap();
+    cfg.set_exit(tail_index).unwrap();
+
+    assert_eq!(cfg.tail_merge().unwrap(), 1);
+
+    assert_eq!(cfg.blocks().len(), 4);
+    assert!(cfg.block(right_index).is_none());
+    assert_eq!(cfg.edge(head_index, left_index).unwrap().condition(), &Some(taken));
+    assert_eq!(cfg.edge(head_index, middle_index).unwrap().condition(), &Some(not_taken));
+    assert_eq!(cfg.edge(middle_index, left_index).unwrap().condition(), &None);
+    assert_eq!(cfg.predecessor_indices(left_index).unwrap().len(), 2);
+    assert_eq!(cfg.successor_indices(left_index).unwrap(), vec![tail_index]);
+
+    assert_eq!(cfg.tail_merge().unwrap(), 0);
+
+    // A single block which is both entry and exit is not merged with itself
+    let mut cfg = ControlFlowGraph::new();
+    let index = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.index()
+    };
+    cfg.set_entry(index).unwrap();
+    cfg.set_exit(index).unwrap();
+
+    assert_eq!(cfg.tail_merge().unwrap(), 0);
+    assert_eq!(cfg.blocks().len(), 1);
+    assert!(cfg.block(index).is_some());
+}
+
+
+#[test]
+fn unroll_loop() {
+    // head -> body, body -> body ? (c), body -> tail ? (!c)
+    let mut cfg = ControlFlowGraph::new();
+
+    let head_index = cfg.new_block().unwrap().index();
+    let body_index = {
+        let block = cfg.new_block().unwrap();
+        block.assign(scalar("i", 32), Expression::add(expr_scalar("i", 32), expr_const(1, 32)).unwrap());
+        block.index()
+    };
+    let tail_index = cfg.new_block().unwrap().index();
+
+    let again = expr_scalar("c", 1);
+    let done = Expression::cmpeq(expr_scalar("c", 1), expr_const(0, 1)).unwrap();
+    cfg.unconditional_edge(head_index, body_index).unwrap();
+    cfg.conditional_edge(body_index, body_index, again.clone()).unwrap();
+    cfg.conditional_edge(body_index, tail_index, done.clone()).unwrap();
+    cfg.set_entry(head_index).unwrap();
+
+    assert!(cfg.unroll_loop(head_index, 2).is_err());
+    cfg.unroll_loop(body_index, 2).unwrap();
+
+    let bodies = cfg.blocks()
+                    .into_iter()
+                    .filter(|block| block.instructions() == cfg.block(body_index).unwrap().instructions())
+                    .map(|block| block.index())
+                    .collect::<Vec<u64>>();
+    assert_eq!(bodies.len(), 3);
+    let (first, second) = (bodies[1], bodies[2]);
+
+    // body -> first -> second -> body, each may leave for tail
+    assert_eq!(cfg.edges().len(), 7);
+    assert_eq!(cfg.edge(body_index, first).unwrap().condition(), &Some(again.clone()));
+    assert_eq!(cfg.edge(first, second).unwrap().condition(), &Some(again.clone()));
+    assert_eq!(cfg.edge(second, body_index).unwrap().condition(), &Some(again));
+    assert!(cfg.edge(body_index, body_index).is_none());
+    for index in bodies {
+        assert_eq!(cfg.edge(index, tail_index).unwrap().condition(), &Some(done.clone()));
+    }
+}
+
+
+#[test]
+fn split_critical_edges() {
+    // head -> left ? (c), head -> tail ? (!c), left -> tail
+    let mut cfg = ControlFlowGraph::new();
+
+    let head_index = cfg.new_block().unwrap().index();
+    let left_index = cfg.new_block().unwrap().index();
+    let tail_index = cfg.new_block().unwrap().index();
+
+    let taken = expr_scalar("c", 1);
+    let not_taken = Expression::cmpeq(expr_scalar("c", 1), expr_const(0, 1)).unwrap();
+    cfg.conditional_edge(head_index, left_index, taken.clone()).unwrap();
+    cfg.conditional_edge(head_index, tail_index, not_taken.clone()).unwrap();
+    cfg.unconditional_edge(left_index, tail_index).unwrap();
+    cfg.set_entry(head_index).unwrap();
+
+    assert_eq!(cfg.split_critical_edges().unwrap(), 1);
+    assert_eq!(cfg.split_critical_edges().unwrap(), 0);
+
+    assert!(cfg.edge(head_index, tail_index).is_none());
+    assert_eq!(cfg.blocks().len(), 4);
+    let split_index = cfg.successor_indices(head_index)
+                         .unwrap()
+                         .into_iter()
+                         .find(|index| *index != left_index)
+                         .unwrap();
+    assert!(cfg.block(split_index).unwrap().instructions().is_empty());
+    assert_eq!(cfg.edge(head_index, split_index).unwrap().condition(), &Some(not_taken));
+    assert_eq!(cfg.edge(split_index, tail_index).unwrap().condition(), &None);
+    assert_eq!(cfg.edge(head_index, left_index).unwrap().condition(), &Some(taken));
+    assert_eq!(cfg.edge(left_index, tail_index).unwrap().condition(), &None);
+}
+
+
+#[test]
+fn verify_conditional_branches() {
+    let build = |taken: Expression, not_taken: Expression| {
+        let mut cfg = ControlFlowGraph::new();
+        let head_index = cfg.new_block().unwrap().index();
+        let left_index = cfg.new_block().unwrap().index();
+        let right_index = cfg.new_block().unwrap().index();
+        cfg.conditional_edge(head_index, left_index, taken).unwrap();
+        cfg.conditional_edge(head_index, right_index, not_taken).unwrap();
+        cfg.unconditional_edge(left_index, right_index).unwrap();
+        cfg
+    };
+
+    let c = || expr_scalar("c", 1);
+    let a = || expr_scalar("a", 32);
+    let b = || expr_scalar("b", 32);
+
+    let complementary = vec![
+        (c(), Expression::logical_not(c()).unwrap()),
+        (c(), Expression::cmpeq(c(), expr_const(0, 1)).unwrap()),
+        (Expression::cmpeq(a(), b()).unwrap(), Expression::cmpneq(b(), a()).unwrap()),
+        (Expression::cmpltu(a(), b()).unwrap(),
+         Expression::logical_not(Expression::cmpltu(a(), b()).unwrap()).unwrap())
+    ];
+    for (taken, not_taken) in complementary {
+        let edge = Edge::new(0, 1, Some(taken.clone()));
+        assert!(build(taken.clone(), edge.negated_condition().unwrap())
+                .verify_conditional_branches()
+                .is_ok());
+        assert!(build(taken, not_taken).verify_conditional_branches().is_ok());
+    }
+
+    let mismatched = vec![
+        (c(), c()),
+        (c(), expr_scalar("d", 1)),
+        (Expression::cmpltu(a(), b()).unwrap(), Expression::cmpltu(b(), a()).unwrap())
+    ];
+    for (taken, not_taken) in mismatched {
+        assert!(build(taken, not_taken).verify_conditional_branches().is_err());
+    }
+
+    let mut cfg = build(c(), Expression::logical_not(c()).unwrap());
+    let extra_index = cfg.new_block().unwrap().index();
+    cfg.unconditional_edge(0, extra_index).unwrap();
+    assert!(cfg.verify_conditional_branches().is_err());
+}
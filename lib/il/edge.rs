@@ -42,12 +42,25 @@ impl Edge {
         &mut self.condition
     }
 
+    /// Retrieve the logical negation of the condition for this `Edge`, or
+    /// `None` if this `Edge` is unconditional or its condition is not 1 bit.
+    pub fn negated_condition(&self) -> Option<Expression> {
+        self.condition
+            .clone()
+            .and_then(|condition| Expression::logical_not(condition).ok())
+    }
+
     /// Retrieve the index of the head `Vertex` for this `Edge`.
     pub fn head(&self) -> u64 { self.head }
 
     /// Retrieve the index of the tail `Vertex` for this `Edge`.
     pub fn tail(&self) -> u64 { self.tail }
 
+    /// Move this `Edge` to leave from the `Vertex` with the given index.
+    pub(crate) fn set_head(&mut self, head: u64) {
+        self.head = head;
+    }
+
     /// Set the comment for this `Edge`.
     pub fn set_comment(&mut self, comment: Option<String>) {
         self.comment = comment;
@@ -91,4 +104,13 @@ impl graph::Edge for Edge {
             None => "".to_string()
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn negated_condition() {
+    let edge = Edge::new(0, 1, Some(expr_scalar("c", 1)));
+    assert_eq!(edge.negated_condition(),
+               Some(Expression::xor(expr_scalar("c", 1), expr_const(1, 1)).unwrap()));
+    assert_eq!(Edge::new(0, 1, None).negated_condition(), None);
+    assert_eq!(Edge::new(0, 1, Some(expr_scalar("a", 32))).negated_condition(), None);
+}
@@ -20,7 +20,12 @@
 //!
 //! ## Extension/Truncation
 //! `zext`, `sext`, `trun`
+//!
+//! ## Bit Counting
+//! `popcnt`, `clz`, `ctz`
 
+use num_bigint::BigUint;
+use std::collections::BTreeSet;
 use std::fmt;
 
 use il::*;
@@ -52,6 +57,12 @@ pub enum Expression {
     Zext(usize, Box<Expression>),
     Sext(usize, Box<Expression>),
     Trun(usize, Box<Expression>),
+
+    Popcnt(Box<Expression>),
+    Clz(Box<Expression>),
+    Ctz(Box<Expression>),
+
+    Neg(Box<Expression>),
 }
 
 
@@ -79,24 +90,122 @@ impl Expression {
             Expression::Cmpltu(_, _) => 1,
             Expression::Zext(bits, _) |
             Expression::Sext(bits, _) |
-            Expression::Trun(bits, _) => bits
+            Expression::Trun(bits, _) => bits,
+            Expression::Popcnt(ref src) |
+            Expression::Clz(ref src) |
+            Expression::Ctz(ref src) |
+            Expression::Neg(ref src) => src.bits()
         }
     }
 
 
     /// Ensures the bits of both lhs and rhs are the same. If no_flags is true,
     /// Also ensures this expression doesn't include flags (which have a sort
-    /// of 0)
-    fn ensure_sort(lhs: &Expression, rhs: &Expression, no_flags: bool) -> Result<()> {
+    /// of 0). operator names the operation in the returned error.
+    fn ensure_sort(operator: &str, lhs: &Expression, rhs: &Expression, no_flags: bool)
+        -> Result<()> {
+
         if    lhs.bits() != rhs.bits() 
            || (no_flags && lhs.bits() == 0) {
-            Err(ErrorKind::Sort.into())
+            Err(ErrorKind::Sort(operator.to_string(), lhs.bits(), rhs.bits()).into())
         }
         else {
             Ok(())
         }
     }
 
+    /// Returns the name of the operation at the root of this `Expression`.
+    pub(crate) fn operator(&self) -> &'static str {
+        match *self {
+            Expression::Scalar(_) => "scalar",
+            Expression::Constant(_) => "constant",
+            Expression::Add(_, _) => "add",
+            Expression::Sub(_, _) => "sub",
+            Expression::Mul(_, _) => "mul",
+            Expression::Divu(_, _) => "divu",
+            Expression::Modu(_, _) => "modu",
+            Expression::Divs(_, _) => "divs",
+            Expression::Mods(_, _) => "mods",
+            Expression::And(_, _) => "and",
+            Expression::Or(_, _) => "or",
+            Expression::Xor(_, _) => "xor",
+            Expression::Shl(_, _) => "shl",
+            Expression::Shr(_, _) => "shr",
+            Expression::Cmpeq(_, _) => "cmpeq",
+            Expression::Cmpneq(_, _) => "cmpneq",
+            Expression::Cmplts(_, _) => "cmplts",
+            Expression::Cmpltu(_, _) => "cmpltu",
+            Expression::Zext(_, _) => "zext",
+            Expression::Sext(_, _) => "sext",
+            Expression::Trun(_, _) => "trun",
+            Expression::Popcnt(_) => "popcnt",
+            Expression::Clz(_) => "clz",
+            Expression::Ctz(_) => "ctz",
+            Expression::Neg(_) => "neg"
+        }
+    }
+
+    /// Walks this `Expression`, ensuring the bits of every operand agree with
+    /// the operation applied to it, as the checked constructors would.
+    /// # Error
+    /// Some node in this `Expression` has operands of an invalid sort.
+    pub fn validate(&self) -> Result<()> {
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => Ok(()),
+            Expression::Add(ref lhs, ref rhs) |
+            Expression::Sub(ref lhs, ref rhs) |
+            Expression::Mul(ref lhs, ref rhs) |
+            Expression::Divu(ref lhs, ref rhs) |
+            Expression::Modu(ref lhs, ref rhs) |
+            Expression::Divs(ref lhs, ref rhs) |
+            Expression::Mods(ref lhs, ref rhs) |
+            Expression::And(ref lhs, ref rhs) |
+            Expression::Or(ref lhs, ref rhs) |
+            Expression::Xor(ref lhs, ref rhs) |
+            Expression::Shl(ref lhs, ref rhs) |
+            Expression::Shr(ref lhs, ref rhs) => {
+                lhs.validate()?;
+                rhs.validate()?;
+                Expression::ensure_sort(self.operator(), lhs, rhs, true)
+            },
+            Expression::Cmpeq(ref lhs, ref rhs) |
+            Expression::Cmpneq(ref lhs, ref rhs) |
+            Expression::Cmplts(ref lhs, ref rhs) |
+            Expression::Cmpltu(ref lhs, ref rhs) => {
+                lhs.validate()?;
+                rhs.validate()?;
+                Expression::ensure_sort(self.operator(), lhs, rhs, false)
+            },
+            Expression::Zext(bits, ref src) |
+            Expression::Sext(bits, ref src) => {
+                src.validate()?;
+                if src.bits() >= bits || src.bits() == 0 {
+                    Err(ErrorKind::Sort(self.operator().to_string(), src.bits(), bits).into())
+                }
+                else {
+                    Ok(())
+                }
+            },
+            Expression::Trun(bits, ref src) => {
+                src.validate()?;
+                if src.bits() <= bits || src.bits() == 0 {
+                    Err(ErrorKind::Sort(self.operator().to_string(), src.bits(), bits).into())
+                }
+                else {
+                    Ok(())
+                }
+            },
+            Expression::Popcnt(ref src) |
+            Expression::Clz(ref src) |
+            Expression::Ctz(ref src) |
+            Expression::Neg(ref src) => {
+                src.validate()?;
+                Expression::ensure_sort(self.operator(), src, src, true)
+            }
+        }
+    }
+
     /// Returns all `Scalars` used in this `Expression`
     pub fn scalars(&self) -> Vec<&Scalar> {
         let mut scalars: Vec<&Scalar> = Vec::new();
@@ -126,7 +235,11 @@ impl Expression {
             },
             Expression::Zext(_, ref rhs) |
             Expression::Sext(_, ref rhs) |
-            Expression::Trun(_, ref rhs) => {
+            Expression::Trun(_, ref rhs) |
+            Expression::Popcnt(ref rhs) |
+            Expression::Clz(ref rhs) |
+            Expression::Ctz(ref rhs) |
+            Expression::Neg(ref rhs) => {
                 scalars.append(&mut rhs.scalars());
             }
         }
@@ -162,13 +275,588 @@ impl Expression {
             },
             Expression::Zext(_, ref mut rhs) |
             Expression::Sext(_, ref mut rhs) |
-            Expression::Trun(_, ref mut rhs) => {
+            Expression::Trun(_, ref mut rhs) |
+            Expression::Popcnt(ref mut rhs) |
+            Expression::Clz(ref mut rhs) |
+            Expression::Ctz(ref mut rhs) |
+            Expression::Neg(ref mut rhs) => {
                 scalars.append(&mut rhs.scalars_mut());
             }
         }
         scalars
     }
 
+    /// Returns true if any `Scalar` in this `Expression` is in `tainted`.
+    pub fn is_tainted(&self, tainted: &BTreeSet<Scalar>) -> bool {
+        self.scalars()
+            .into_iter()
+            .any(|scalar| tainted.contains(scalar))
+    }
+
+    /// If this is a binary `Expression`, returns its infix operator, its
+    /// precedence, and its operands. Higher precedences bind tighter.
+    fn infix(&self) -> Option<(&'static str, usize, &Expression, &Expression)> {
+        match *self {
+            Expression::Mul(ref lhs, ref rhs) => Some(("*", 7, lhs, rhs)),
+            Expression::Divu(ref lhs, ref rhs) => Some(("/u", 7, lhs, rhs)),
+            Expression::Modu(ref lhs, ref rhs) => Some(("%u", 7, lhs, rhs)),
+            Expression::Divs(ref lhs, ref rhs) => Some(("/s", 7, lhs, rhs)),
+            Expression::Mods(ref lhs, ref rhs) => Some(("%s", 7, lhs, rhs)),
+            Expression::Add(ref lhs, ref rhs) => Some(("+", 6, lhs, rhs)),
+            Expression::Sub(ref lhs, ref rhs) => Some(("-", 6, lhs, rhs)),
+            Expression::Shl(ref lhs, ref rhs) => Some(("<<", 5, lhs, rhs)),
+            Expression::Shr(ref lhs, ref rhs) => Some((">>", 5, lhs, rhs)),
+            Expression::Cmplts(ref lhs, ref rhs) => Some(("<s", 4, lhs, rhs)),
+            Expression::Cmpltu(ref lhs, ref rhs) => Some(("<u", 4, lhs, rhs)),
+            Expression::Cmpeq(ref lhs, ref rhs) => Some(("==", 3, lhs, rhs)),
+            Expression::Cmpneq(ref lhs, ref rhs) => Some(("!=", 3, lhs, rhs)),
+            Expression::And(ref lhs, ref rhs) => Some(("&", 2, lhs, rhs)),
+            Expression::Xor(ref lhs, ref rhs) => Some(("^", 1, lhs, rhs)),
+            Expression::Or(ref lhs, ref rhs) => Some(("|", 0, lhs, rhs)),
+            _ => None
+        }
+    }
+
+    /// Returns a human-readable representation of this `Expression`.
+    ///
+    /// Binary operations are written infix. A nested binary operation is
+    /// parenthesized, unless it is the left operand of an operation of the same
+    /// precedence, so `a + b - c` and `a + (b * c)`. Scalars are written by
+    /// name and constants in hexadecimal, both without their bitness.
+    pub fn pretty(&self) -> String {
+        match *self {
+            Expression::Scalar(ref scalar) => scalar.name().to_string(),
+            Expression::Constant(ref constant) =>
+                format!("0x{}", constant.value_big().to_str_radix(16).to_uppercase()),
+            Expression::Add(_, _) |
+            Expression::Sub(_, _) |
+            Expression::Mul(_, _) |
+            Expression::Divu(_, _) |
+            Expression::Modu(_, _) |
+            Expression::Divs(_, _) |
+            Expression::Mods(_, _) |
+            Expression::And(_, _) |
+            Expression::Or(_, _) |
+            Expression::Xor(_, _) |
+            Expression::Shl(_, _) |
+            Expression::Shr(_, _) |
+            Expression::Cmpeq(_, _) |
+            Expression::Cmpneq(_, _) |
+            Expression::Cmplts(_, _) |
+            Expression::Cmpltu(_, _) => {
+                let (operator, precedence, lhs, rhs) = self.infix().unwrap();
+                let lhs_pretty = match lhs.infix() {
+                    Some((_, lhs_precedence, _, _)) if lhs_precedence != precedence =>
+                        format!("({})", lhs.pretty()),
+                    _ => lhs.pretty()
+                };
+                let rhs_pretty = match rhs.infix() {
+                    Some(_) => format!("({})", rhs.pretty()),
+                    None => rhs.pretty()
+                };
+                format!("{} {} {}", lhs_pretty, operator, rhs_pretty)
+            },
+            Expression::Zext(bits, ref src) => format!("zext.{}({})", bits, src.pretty()),
+            Expression::Sext(bits, ref src) => format!("sext.{}({})", bits, src.pretty()),
+            Expression::Trun(bits, ref src) => format!("trun.{}({})", bits, src.pretty()),
+            Expression::Popcnt(ref src) => format!("popcnt({})", src.pretty()),
+            Expression::Clz(ref src) => format!("clz({})", src.pretty()),
+            Expression::Ctz(ref src) => format!("ctz({})", src.pretty()),
+            Expression::Neg(ref src) => format!("neg({})", src.pretty())
+        }
+    }
+
+    /// Returns this `Expression` as an SMT-LIB2 bitvector term.
+    ///
+    /// Comparisons are 1-bit bitvectors, as they are in Falcon IL. Scalars are
+    /// referred to by name, and must be declared with the output of
+    /// `Expression::smtlib2_declarations`.
+    ///
+    /// # Error
+    /// This `Expression` contains a 0-bit `Scalar` or `Constant`.
+    pub fn smtlib2(&self) -> Result<String> {
+        fn binary(operator: &str, lhs: &Expression, rhs: &Expression) -> Result<String> {
+            Ok(format!("({} {} {})", operator, lhs.smtlib2()?, rhs.smtlib2()?))
+        }
+
+        fn comparison(operator: &str, lhs: &Expression, rhs: &Expression) -> Result<String> {
+            Ok(format!("(ite {} #b1 #b0)", binary(operator, lhs, rhs)?))
+        }
+
+        // Counts the zero bits of src before the first one bit, visiting bits
+        // in the given order.
+        fn count_zeros<I>(src: &Expression, bits: I) -> Result<String>
+        where I: DoubleEndedIterator<Item=usize> + ExactSizeIterator {
+            let src_smtlib2 = src.smtlib2()?;
+            let width = src.bits();
+            let mut term = format!("(_ bv{} {})", bits.len(), width);
+            for (count, bit) in bits.enumerate().rev() {
+                term = format!("(ite (= ((_ extract {} {}) {}) #b1) (_ bv{} {}) {})",
+                               bit, bit, src_smtlib2, count, width, term);
+            }
+            Ok(term)
+        }
+
+        Ok(match *self {
+            Expression::Scalar(ref scalar) => {
+                if scalar.bits() == 0 {
+                    bail!("Can not express 0-bit scalar {} in SMT-LIB2", scalar.name());
+                }
+                smtlib2_symbol(scalar.name())
+            },
+            Expression::Constant(ref constant) => {
+                if constant.bits() == 0 {
+                    bail!("Can not express 0-bit constant in SMT-LIB2");
+                }
+                format!("(_ bv{} {})", constant.value_big().to_str_radix(10), constant.bits())
+            },
+            Expression::Add(ref lhs, ref rhs) => binary("bvadd", lhs, rhs)?,
+            Expression::Sub(ref lhs, ref rhs) => binary("bvsub", lhs, rhs)?,
+            Expression::Mul(ref lhs, ref rhs) => binary("bvmul", lhs, rhs)?,
+            Expression::Divu(ref lhs, ref rhs) => binary("bvudiv", lhs, rhs)?,
+            Expression::Modu(ref lhs, ref rhs) => binary("bvurem", lhs, rhs)?,
+            Expression::Divs(ref lhs, ref rhs) => binary("bvsdiv", lhs, rhs)?,
+            Expression::Mods(ref lhs, ref rhs) => binary("bvsrem", lhs, rhs)?,
+            Expression::And(ref lhs, ref rhs) => binary("bvand", lhs, rhs)?,
+            Expression::Or(ref lhs, ref rhs) => binary("bvor", lhs, rhs)?,
+            Expression::Xor(ref lhs, ref rhs) => binary("bvxor", lhs, rhs)?,
+            Expression::Shl(ref lhs, ref rhs) => binary("bvshl", lhs, rhs)?,
+            Expression::Shr(ref lhs, ref rhs) => binary("bvlshr", lhs, rhs)?,
+            Expression::Cmpeq(ref lhs, ref rhs) => comparison("=", lhs, rhs)?,
+            Expression::Cmpneq(ref lhs, ref rhs) => comparison("distinct", lhs, rhs)?,
+            Expression::Cmplts(ref lhs, ref rhs) => comparison("bvslt", lhs, rhs)?,
+            Expression::Cmpltu(ref lhs, ref rhs) => comparison("bvult", lhs, rhs)?,
+            Expression::Zext(bits, ref src) =>
+                format!("((_ zero_extend {}) {})", bits - src.bits(), src.smtlib2()?),
+            Expression::Sext(bits, ref src) =>
+                format!("((_ sign_extend {}) {})", bits - src.bits(), src.smtlib2()?),
+            Expression::Trun(bits, ref src) =>
+                format!("((_ extract {} 0) {})", bits - 1, src.smtlib2()?),
+            Expression::Popcnt(ref src) => {
+                let src_smtlib2 = src.smtlib2()?;
+                if src.bits() == 1 {
+                    src_smtlib2
+                }
+                else {
+                    let terms = (0..src.bits())
+                        .map(|bit| format!("((_ zero_extend {}) ((_ extract {} {}) {}))",
+                                           src.bits() - 1, bit, bit, src_smtlib2))
+                        .collect::<Vec<String>>();
+                    format!("(bvadd {})", terms.join(" "))
+                }
+            },
+            Expression::Clz(ref src) => count_zeros(src, (0..src.bits()).rev())?,
+            Expression::Ctz(ref src) => count_zeros(src, 0..src.bits())?,
+            Expression::Neg(ref src) => format!("(bvneg {})", src.smtlib2()?)
+        })
+    }
+
+    /// Returns an SMT-LIB2 `declare-fun` for each distinct `Scalar` in this
+    /// `Expression`, one per line.
+    ///
+    /// # Error
+    /// A `Scalar` name is used with two different bitnesses.
+    pub fn smtlib2_declarations(&self) -> Result<String> {
+        Ok(self.scalar_declarations()?
+               .into_iter()
+               .map(|scalar| format!("(declare-fun {} () (_ BitVec {}))",
+                                     smtlib2_symbol(scalar.name()),
+                                     scalar.bits()))
+               .collect::<Vec<String>>()
+               .join("\n"))
+    }
+
+    /// Returns each distinct `Scalar` in this `Expression`.
+    ///
+    /// # Error
+    /// A `Scalar` name is used with two different bitnesses.
+    pub fn scalar_declarations(&self) -> Result<BTreeSet<Scalar>> {
+        let mut declarations: BTreeSet<Scalar> = BTreeSet::new();
+        for scalar in self.scalars() {
+            if let Some(declaration) = declarations.iter()
+                                                   .find(|d| d.name() == scalar.name()) {
+                if declaration.bits() != scalar.bits() {
+                    bail!("Scalar {} used with {} and {} bits",
+                        scalar.name(), declaration.bits(), scalar.bits());
+                }
+            }
+            declarations.insert(scalar.clone());
+        }
+        Ok(declarations)
+    }
+
+    /// Returns this `Expression` with the operands of every commutative
+    /// operation placed in a deterministic order.
+    ///
+    /// Expressions which differ only in the order of operands to `Add`, `Mul`,
+    /// `And`, `Or`, `Xor`, `Cmpeq` and `Cmpneq` canonicalize to equal
+    /// expressions.
+    pub fn canonicalize(&self) -> Expression {
+        fn ordered(lhs: &Expression, rhs: &Expression) -> (Box<Expression>, Box<Expression>) {
+            let lhs = lhs.canonicalize();
+            let rhs = rhs.canonicalize();
+            if lhs <= rhs {
+                (Box::new(lhs), Box::new(rhs))
+            }
+            else {
+                (Box::new(rhs), Box::new(lhs))
+            }
+        }
+
+        fn unordered(lhs: &Expression, rhs: &Expression) -> (Box<Expression>, Box<Expression>) {
+            (Box::new(lhs.canonicalize()), Box::new(rhs.canonicalize()))
+        }
+
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => self.clone(),
+            Expression::Add(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Add(lhs, rhs)
+            },
+            Expression::Sub(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Sub(lhs, rhs)
+            },
+            Expression::Mul(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Mul(lhs, rhs)
+            },
+            Expression::Divu(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Divu(lhs, rhs)
+            },
+            Expression::Modu(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Modu(lhs, rhs)
+            },
+            Expression::Divs(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Divs(lhs, rhs)
+            },
+            Expression::Mods(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Mods(lhs, rhs)
+            },
+            Expression::And(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::And(lhs, rhs)
+            },
+            Expression::Or(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Or(lhs, rhs)
+            },
+            Expression::Xor(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Xor(lhs, rhs)
+            },
+            Expression::Shl(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Shl(lhs, rhs)
+            },
+            Expression::Shr(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Shr(lhs, rhs)
+            },
+            Expression::Cmpeq(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Cmpeq(lhs, rhs)
+            },
+            Expression::Cmpneq(ref lhs, ref rhs) => {
+                let (lhs, rhs) = ordered(lhs, rhs);
+                Expression::Cmpneq(lhs, rhs)
+            },
+            Expression::Cmplts(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Cmplts(lhs, rhs)
+            },
+            Expression::Cmpltu(ref lhs, ref rhs) => {
+                let (lhs, rhs) = unordered(lhs, rhs);
+                Expression::Cmpltu(lhs, rhs)
+            },
+            Expression::Zext(bits, ref src) =>
+                Expression::Zext(bits, Box::new(src.canonicalize())),
+            Expression::Sext(bits, ref src) =>
+                Expression::Sext(bits, Box::new(src.canonicalize())),
+            Expression::Trun(bits, ref src) =>
+                Expression::Trun(bits, Box::new(src.canonicalize())),
+            Expression::Popcnt(ref src) =>
+                Expression::Popcnt(Box::new(src.canonicalize())),
+            Expression::Clz(ref src) =>
+                Expression::Clz(Box::new(src.canonicalize())),
+            Expression::Ctz(ref src) =>
+                Expression::Ctz(Box::new(src.canonicalize())),
+            Expression::Neg(ref src) =>
+                Expression::Neg(Box::new(src.canonicalize()))
+        }
+    }
+
+    /// Returns this `Expression` with every `Sub` rewritten as the `Add` of
+    /// its left operand and the `Neg` of its right operand.
+    pub fn normalize_additive(&self) -> Expression {
+        let mut expression = match *self {
+            Expression::Sub(ref lhs, ref rhs) =>
+                Expression::Add(lhs.clone(), Box::new(Expression::Neg(rhs.clone()))),
+            _ => self.clone()
+        };
+        for child in expression.children_mut() {
+            *child = child.normalize_additive();
+        }
+        expression
+    }
+
+    /// Returns true if this `Expression` and other are equal once both are
+    /// normalized, as a cheap check before using a solver.
+    ///
+    /// Normalization rewrites `Sub` as in `Expression::normalize_additive`,
+    /// evaluates constant subexpressions, removes additions of 0,
+    /// multiplications by 1 and similar identities, and orders operands as in
+    /// `Expression::canonicalize`. Expressions which are equal but differ in
+    /// other ways, such as `a + a` and `a * 2`, are not recognized.
+    pub fn structurally_equivalent(&self, other: &Expression) -> bool {
+        let normalize = |expression: &Expression|
+            expression.normalize_additive().fold_constants().canonicalize();
+        normalize(self) == normalize(other)
+    }
+
+    /// Returns this `Expression` with constant subexpressions evaluated, and
+    /// operations with an identity operand replaced by the other operand.
+    fn fold_constants(&self) -> Expression {
+        let mut expression = self.clone();
+        for child in expression.children_mut() {
+            *child = child.fold_constants();
+        }
+        if let Some(constant) = expression.as_constant() {
+            return Expression::Constant(constant);
+        }
+
+        let is = |expression: &Expression, value: u64|
+            expression.as_constant().map(|constant| constant.value_big() == value.into())
+                == Some(true);
+        let identity = match expression {
+            Expression::Add(ref lhs, ref rhs) |
+            Expression::Or(ref lhs, ref rhs) |
+            Expression::Xor(ref lhs, ref rhs) =>
+                if is(lhs, 0) { Some(rhs.clone()) }
+                else if is(rhs, 0) { Some(lhs.clone()) }
+                else { None },
+            Expression::Mul(ref lhs, ref rhs) =>
+                if is(lhs, 1) { Some(rhs.clone()) }
+                else if is(rhs, 1) { Some(lhs.clone()) }
+                else { None },
+            Expression::Divu(ref lhs, ref rhs) |
+            Expression::Divs(ref lhs, ref rhs) =>
+                if is(rhs, 1) { Some(lhs.clone()) } else { None },
+            Expression::Shl(ref lhs, ref rhs) |
+            Expression::Shr(ref lhs, ref rhs) =>
+                if is(rhs, 0) { Some(lhs.clone()) } else { None },
+            _ => None
+        };
+        match identity {
+            Some(identity) => *identity,
+            None => expression
+        }
+    }
+
+    /// Returns a copy of this `Expression` with every occurrence of `scalar`
+    /// replaced by the constant `value`.
+    /// # Error
+    /// The bitness of `scalar` and `value` differ.
+    pub fn specialize(&self, scalar: &Scalar, value: &Constant) -> Result<Expression> {
+        if scalar.bits() != value.bits() {
+            return Err(ErrorKind::Sort("specialize".to_string(),
+                                       scalar.bits(),
+                                       value.bits()).into());
+        }
+
+        fn replace(expression: &mut Expression, scalar: &Scalar, value: &Constant) {
+            let matches = match *expression {
+                Expression::Scalar(ref s) => s == scalar,
+                _ => false
+            };
+            if matches {
+                *expression = Expression::constant(value.clone());
+            }
+            else {
+                for child in expression.children_mut() {
+                    replace(child, scalar, value);
+                }
+            }
+        }
+
+        let mut expression = self.clone();
+        replace(&mut expression, scalar, value);
+        Ok(expression)
+    }
+
+    /// Returns the operands of this `Expression`.
+    pub(crate) fn children(&self) -> Vec<&Expression> {
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => Vec::new(),
+            Expression::Add(ref lhs, ref rhs) |
+            Expression::Sub(ref lhs, ref rhs) |
+            Expression::Mul(ref lhs, ref rhs) |
+            Expression::Divu(ref lhs, ref rhs) |
+            Expression::Modu(ref lhs, ref rhs) |
+            Expression::Divs(ref lhs, ref rhs) |
+            Expression::Mods(ref lhs, ref rhs) |
+            Expression::And(ref lhs, ref rhs) |
+            Expression::Or(ref lhs, ref rhs) |
+            Expression::Xor(ref lhs, ref rhs) |
+            Expression::Shl(ref lhs, ref rhs) |
+            Expression::Shr(ref lhs, ref rhs) |
+            Expression::Cmpeq(ref lhs, ref rhs) |
+            Expression::Cmpneq(ref lhs, ref rhs) |
+            Expression::Cmplts(ref lhs, ref rhs) |
+            Expression::Cmpltu(ref lhs, ref rhs) =>
+                vec![&**lhs, &**rhs],
+            Expression::Zext(_, ref src) |
+            Expression::Sext(_, ref src) |
+            Expression::Trun(_, ref src) |
+            Expression::Popcnt(ref src) |
+            Expression::Clz(ref src) |
+            Expression::Ctz(ref src) |
+            Expression::Neg(ref src) =>
+                vec![&**src]
+        }
+    }
+
+    /// Returns the operands of this `Expression`.
+    pub(crate) fn children_mut(&mut self) -> Vec<&mut Expression> {
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => Vec::new(),
+            Expression::Add(ref mut lhs, ref mut rhs) |
+            Expression::Sub(ref mut lhs, ref mut rhs) |
+            Expression::Mul(ref mut lhs, ref mut rhs) |
+            Expression::Divu(ref mut lhs, ref mut rhs) |
+            Expression::Modu(ref mut lhs, ref mut rhs) |
+            Expression::Divs(ref mut lhs, ref mut rhs) |
+            Expression::Mods(ref mut lhs, ref mut rhs) |
+            Expression::And(ref mut lhs, ref mut rhs) |
+            Expression::Or(ref mut lhs, ref mut rhs) |
+            Expression::Xor(ref mut lhs, ref mut rhs) |
+            Expression::Shl(ref mut lhs, ref mut rhs) |
+            Expression::Shr(ref mut lhs, ref mut rhs) |
+            Expression::Cmpeq(ref mut lhs, ref mut rhs) |
+            Expression::Cmpneq(ref mut lhs, ref mut rhs) |
+            Expression::Cmplts(ref mut lhs, ref mut rhs) |
+            Expression::Cmpltu(ref mut lhs, ref mut rhs) =>
+                vec![&mut **lhs, &mut **rhs],
+            Expression::Zext(_, ref mut src) |
+            Expression::Sext(_, ref mut src) |
+            Expression::Trun(_, ref mut src) |
+            Expression::Popcnt(ref mut src) |
+            Expression::Clz(ref mut src) |
+            Expression::Ctz(ref mut src) |
+            Expression::Neg(ref mut src) =>
+                vec![&mut **src]
+        }
+    }
+
+    /// Returns true if this `Expression` reads no `Scalar`.
+    pub fn is_constant(&self) -> bool {
+        self.scalars().is_empty()
+    }
+
+    /// Returns the value of this `Expression` if it reads no `Scalar` and can
+    /// be evaluated, or `None` otherwise.
+    pub fn as_constant(&self) -> Option<Constant> {
+        if !self.is_constant() {
+            return None;
+        }
+        ::executor::eval(self).ok()
+    }
+
+    /// Returns the number of nodes on the longest path from this `Expression`
+    /// to a terminal, counting both.
+    pub fn depth(&self) -> usize {
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => 1,
+            Expression::Add(ref lhs, ref rhs) |
+            Expression::Sub(ref lhs, ref rhs) |
+            Expression::Mul(ref lhs, ref rhs) |
+            Expression::Divu(ref lhs, ref rhs) |
+            Expression::Modu(ref lhs, ref rhs) |
+            Expression::Divs(ref lhs, ref rhs) |
+            Expression::Mods(ref lhs, ref rhs) |
+            Expression::And(ref lhs, ref rhs) |
+            Expression::Or(ref lhs, ref rhs) |
+            Expression::Xor(ref lhs, ref rhs) |
+            Expression::Shl(ref lhs, ref rhs) |
+            Expression::Shr(ref lhs, ref rhs) |
+            Expression::Cmpeq(ref lhs, ref rhs) |
+            Expression::Cmpneq(ref lhs, ref rhs) |
+            Expression::Cmplts(ref lhs, ref rhs) |
+            Expression::Cmpltu(ref lhs, ref rhs) =>
+                1 + lhs.depth().max(rhs.depth()),
+            Expression::Zext(_, ref src) |
+            Expression::Sext(_, ref src) |
+            Expression::Trun(_, ref src) |
+            Expression::Popcnt(ref src) |
+            Expression::Clz(ref src) |
+            Expression::Ctz(ref src) |
+            Expression::Neg(ref src) =>
+                1 + src.depth()
+        }
+    }
+
+    /// Returns the number of nodes in this `Expression`, including terminals.
+    pub fn node_count(&self) -> usize {
+        match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => 1,
+            Expression::Add(ref lhs, ref rhs) |
+            Expression::Sub(ref lhs, ref rhs) |
+            Expression::Mul(ref lhs, ref rhs) |
+            Expression::Divu(ref lhs, ref rhs) |
+            Expression::Modu(ref lhs, ref rhs) |
+            Expression::Divs(ref lhs, ref rhs) |
+            Expression::Mods(ref lhs, ref rhs) |
+            Expression::And(ref lhs, ref rhs) |
+            Expression::Or(ref lhs, ref rhs) |
+            Expression::Xor(ref lhs, ref rhs) |
+            Expression::Shl(ref lhs, ref rhs) |
+            Expression::Shr(ref lhs, ref rhs) |
+            Expression::Cmpeq(ref lhs, ref rhs) |
+            Expression::Cmpneq(ref lhs, ref rhs) |
+            Expression::Cmplts(ref lhs, ref rhs) |
+            Expression::Cmpltu(ref lhs, ref rhs) =>
+                1 + lhs.node_count() + rhs.node_count(),
+            Expression::Zext(_, ref src) |
+            Expression::Sext(_, ref src) |
+            Expression::Trun(_, ref src) |
+            Expression::Popcnt(ref src) |
+            Expression::Clz(ref src) |
+            Expression::Ctz(ref src) |
+            Expression::Neg(ref src) =>
+                1 + src.node_count()
+        }
+    }
+
+    /// Returns an estimate of the cost of evaluating this `Expression`.
+    ///
+    /// Terminals cost 1, divisions and remainders 16, multiplications 4, and
+    /// every other operation 2. The cost of an `Expression` is the sum of the
+    /// costs of its nodes.
+    pub fn cost(&self) -> u64 {
+        let cost = match *self {
+            Expression::Scalar(_) |
+            Expression::Constant(_) => 1,
+            Expression::Divu(_, _) |
+            Expression::Modu(_, _) |
+            Expression::Divs(_, _) |
+            Expression::Mods(_, _) => 16,
+            Expression::Mul(_, _) => 4,
+            _ => 2
+        };
+        self.children()
+            .into_iter()
+            .fold(cost, |cost, child| cost + child.cost())
+    }
+
     /// Create a new `Expression` from a `Scalar`.
     pub fn scalar(scalar: Scalar) -> Expression {
         Expression::Scalar(scalar)
@@ -183,7 +871,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same
     pub fn add(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("add", &lhs, &rhs, true));
         Ok(Expression::Add(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -191,7 +879,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn sub(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("sub", &lhs, &rhs, true));
         Ok(Expression::Sub(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -199,7 +887,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn mul(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("mul", &lhs, &rhs, true));
         Ok(Expression::Mul(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -207,7 +895,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn divu(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("divu", &lhs, &rhs, true));
         Ok(Expression::Divu(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -215,7 +903,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn modu(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("modu", &lhs, &rhs, true));
         Ok(Expression::Modu(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -223,7 +911,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn divs(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("divs", &lhs, &rhs, true));
         Ok(Expression::Divs(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -231,7 +919,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn mods(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("mods", &lhs, &rhs, true));
         Ok(Expression::Mods(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -239,7 +927,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn and(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("and", &lhs, &rhs, true));
         Ok(Expression::And(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -247,7 +935,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn or(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("or", &lhs, &rhs, true));
         Ok(Expression::Or(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -255,31 +943,69 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn xor(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("xor", &lhs, &rhs, true));
         Ok(Expression::Xor(Box::new(lhs), Box::new(rhs)))
     }
 
     /// Create a logical shift-left `Expression`.
+    ///
+    /// Shifting by the bitness of lhs or more gives 0. Use
+    /// `Expression::shl_masked` for architectures which mask the shift amount.
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn shl(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("shl", &lhs, &rhs, true));
         Ok(Expression::Shl(Box::new(lhs), Box::new(rhs)))
     }
 
     /// Create a logical shift-right `Expression`.
+    ///
+    /// Shifting by the bitness of lhs or more gives 0. Use
+    /// `Expression::shr_masked` for architectures which mask the shift amount.
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn shr(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, true));
+        try!(Expression::ensure_sort("shr", &lhs, &rhs, true));
         Ok(Expression::Shr(Box::new(lhs), Box::new(rhs)))
     }
 
+    /// Masks the shift amount rhs to the bits needed to shift lhs, as x86 and
+    /// ARM do in hardware.
+    fn mask_shift_amount(operator: &str, lhs: &Expression, rhs: Expression)
+        -> Result<Expression> {
+
+        Expression::ensure_sort(operator, lhs, &rhs, true)?;
+        if !lhs.bits().is_power_of_two() {
+            bail!("{} requires a power of two bitness, not {} bits", operator, lhs.bits());
+        }
+        Expression::and(rhs, expr_const(lhs.bits() as u64 - 1, lhs.bits()))
+    }
+
+    /// Create a logical shift-left `Expression`, where the shift amount is
+    /// taken modulo the bitness of lhs.
+    /// # Error
+    /// The sort of the lhs and the rhs are not the same, or the bitness of lhs
+    /// is not a power of two.
+    pub fn shl_masked(lhs: Expression, rhs: Expression) -> Result<Expression> {
+        let rhs = Expression::mask_shift_amount("shl_masked", &lhs, rhs)?;
+        Expression::shl(lhs, rhs)
+    }
+
+    /// Create a logical shift-right `Expression`, where the shift amount is
+    /// taken modulo the bitness of lhs.
+    /// # Error
+    /// The sort of the lhs and the rhs are not the same, or the bitness of lhs
+    /// is not a power of two.
+    pub fn shr_masked(lhs: Expression, rhs: Expression) -> Result<Expression> {
+        let rhs = Expression::mask_shift_amount("shr_masked", &lhs, rhs)?;
+        Expression::shr(lhs, rhs)
+    }
+
     /// Create an equals comparison `Expression`.
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn cmpeq(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, false));
+        try!(Expression::ensure_sort("cmpeq", &lhs, &rhs, false));
         Ok(Expression::Cmpeq(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -287,7 +1013,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn cmpneq(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, false));
+        try!(Expression::ensure_sort("cmpneq", &lhs, &rhs, false));
         Ok(Expression::Cmpneq(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -295,7 +1021,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn cmpltu(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, false));
+        try!(Expression::ensure_sort("cmpltu", &lhs, &rhs, false));
         Ok(Expression::Cmpltu(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -303,7 +1029,7 @@ impl Expression {
     /// # Error
     /// The sort of the lhs and the rhs are not the same.
     pub fn cmplts(lhs: Expression, rhs: Expression) -> Result<Expression> {
-        try!(Expression::ensure_sort(&lhs, &rhs, false));
+        try!(Expression::ensure_sort("cmplts", &lhs, &rhs, false));
         Ok(Expression::Cmplts(Box::new(lhs), Box::new(rhs)))
     }
 
@@ -313,7 +1039,7 @@ impl Expression {
     /// src has more or equal number of bits than bits
     pub fn zext(bits: usize, src: Expression) -> Result<Expression> {
         if src.bits() >= bits || src.bits() == 0 {
-            return Err(ErrorKind::Sort.into());
+            return Err(ErrorKind::Sort("zext".to_string(), src.bits(), bits).into());
         }
         Ok(Expression::Zext(bits, Box::new(src)))
     }
@@ -323,7 +1049,7 @@ impl Expression {
     /// src has more or equal number of bits than bits
     pub fn sext(bits: usize, src: Expression) -> Result<Expression> {
         if src.bits() >= bits || src.bits() == 0 {
-            return Err(ErrorKind::Sort.into());
+            return Err(ErrorKind::Sort("sext".to_string(), src.bits(), bits).into());
         }
         Ok(Expression::Sext(bits, Box::new(src)))
     }
@@ -334,10 +1060,193 @@ impl Expression {
     /// src has less-than or equal bits than bits
     pub fn trun(bits: usize, src: Expression) -> Result<Expression> {
         if src.bits() <= bits || src.bits() == 0 {
-            return Err(ErrorKind::Sort.into());
+            return Err(ErrorKind::Sort("trun".to_string(), src.bits(), bits).into());
         }
         Ok(Expression::Trun(bits, Box::new(src)))
     }
+
+    /// Create an expression for the number of bits set in src, with the
+    /// bitness of src.
+    /// # Error
+    /// src has a bitness of 0.
+    pub fn popcnt(src: Expression) -> Result<Expression> {
+        Expression::ensure_sort("popcnt", &src, &src, true)?;
+        Ok(Expression::Popcnt(Box::new(src)))
+    }
+
+    /// Create an expression for the number of zero bits in src above its most
+    /// significant one bit, with the bitness of src. This is the bitness of
+    /// src when src is 0.
+    /// # Error
+    /// src has a bitness of 0.
+    pub fn clz(src: Expression) -> Result<Expression> {
+        Expression::ensure_sort("clz", &src, &src, true)?;
+        Ok(Expression::Clz(Box::new(src)))
+    }
+
+    /// Create an expression for the number of zero bits in src below its least
+    /// significant one bit, with the bitness of src. This is the bitness of
+    /// src when src is 0.
+    /// # Error
+    /// src has a bitness of 0.
+    pub fn ctz(src: Expression) -> Result<Expression> {
+        Expression::ensure_sort("ctz", &src, &src, true)?;
+        Ok(Expression::Ctz(Box::new(src)))
+    }
+
+    /// Create an expression for the two's complement negation of src.
+    /// # Error
+    /// src has a bitness of 0.
+    #[allow(clippy::should_implement_trait)]
+    pub fn neg(src: Expression) -> Result<Expression> {
+        Expression::ensure_sort("neg", &src, &src, true)?;
+        Ok(Expression::Neg(Box::new(src)))
+    }
+
+    /// Create an expression for bits `lo` through `hi` inclusive of src, as a
+    /// value of `hi - lo + 1` bits.
+    /// # Error
+    /// `lo` is greater than `hi`, or `hi` is not a bit of src.
+    pub fn extract(src: Expression, lo: usize, hi: usize) -> Result<Expression> {
+        let bits = src.bits();
+        if lo > hi {
+            bail!("Invalid extract of bits {} to {}", lo, hi);
+        }
+        if hi >= bits {
+            bail!("Invalid extract of bit {} from a {}-bit expression", hi, bits);
+        }
+
+        let shifted = if lo > 0 {
+            Expression::shr(src, expr_const(lo as u64, bits))?
+        }
+        else {
+            src
+        };
+
+        let width = hi - lo + 1;
+        if width < bits {
+            Expression::trun(width, shifted)
+        }
+        else {
+            Ok(shifted)
+        }
+    }
+
+    /// Create an expression with the bits of high above the bits of low, as a
+    /// value of `high.bits() + low.bits()` bits.
+    /// # Error
+    /// high or low has a bitness of 0.
+    pub fn concat(high: Expression, low: Expression) -> Result<Expression> {
+        let bits = high.bits() + low.bits();
+        let shift = expr_const(low.bits() as u64, bits);
+        let high = Expression::shl(Expression::zext(bits, high)?, shift)?;
+        Expression::or(high, Expression::zext(bits, low)?)
+    }
+
+    /// Create an expression for dest with bits `lo` through `hi` inclusive
+    /// replaced by value, as a value of `dest.bits()` bits. This is the
+    /// inverse of `Expression::extract`.
+    /// # Error
+    /// `lo` is greater than `hi`, `hi` is not a bit of dest, or value does not
+    /// have `hi - lo + 1` bits.
+    pub fn deposit(dest: Expression, value: Expression, lo: usize, hi: usize)
+        -> Result<Expression> {
+
+        let bits = dest.bits();
+        if lo > hi {
+            bail!("Invalid deposit into bits {} to {}", lo, hi);
+        }
+        if hi >= bits {
+            bail!("Invalid deposit into bit {} of a {}-bit expression", hi, bits);
+        }
+        let width = hi - lo + 1;
+        if value.bits() != width {
+            bail!("Can not deposit a {}-bit expression into {} bits", value.bits(), width);
+        }
+        if width == bits {
+            return Ok(value);
+        }
+
+        let ones = |bits: usize| (BigUint::from(1u64) << bits) - BigUint::from(1u64);
+        let keep = ones(bits) - (ones(width) << lo);
+        let dest = Expression::and(dest, Expression::constant(Constant::new_big(keep, bits)))?;
+
+        let value = Expression::zext(bits, value)?;
+        let value = if lo > 0 {
+            Expression::shl(value, expr_const(lo as u64, bits))?
+        }
+        else {
+            value
+        };
+
+        Expression::or(dest, value)
+    }
+
+    /// Create an expression which reverses the order of the bytes of src.
+    /// # Error
+    /// The bitness of src is not a non-zero multiple of 8.
+    pub fn byte_swap(src: Expression) -> Result<Expression> {
+        let bits = src.bits();
+        if bits == 0 || bits % 8 != 0 {
+            bail!("Can not byte swap a {}-bit expression", bits);
+        }
+
+        // The lowest byte of src becomes the highest byte of the result
+        let mut swapped = Expression::extract(src.clone(), 0, 7)?;
+        for byte in 1..(bits / 8) {
+            let byte = Expression::extract(src.clone(), byte * 8, byte * 8 + 7)?;
+            swapped = Expression::concat(swapped, byte)?;
+        }
+        Ok(swapped)
+    }
+
+    /// Bail unless expression is a 1-bit boolean operand of operator.
+    fn ensure_boolean(operator: &str, expression: &Expression) -> Result<()> {
+        if expression.bits() != 1 {
+            bail!("{} requires 1-bit operands, not {} bits", operator, expression.bits());
+        }
+        Ok(())
+    }
+
+    /// Create a logical and `Expression` of two 1-bit conditions.
+    /// # Error
+    /// lhs or rhs is not 1 bit.
+    pub fn logical_and(lhs: Expression, rhs: Expression) -> Result<Expression> {
+        Expression::ensure_boolean("logical_and", &lhs)?;
+        Expression::ensure_boolean("logical_and", &rhs)?;
+        Expression::and(lhs, rhs)
+    }
+
+    /// Create a logical or `Expression` of two 1-bit conditions.
+    /// # Error
+    /// lhs or rhs is not 1 bit.
+    pub fn logical_or(lhs: Expression, rhs: Expression) -> Result<Expression> {
+        Expression::ensure_boolean("logical_or", &lhs)?;
+        Expression::ensure_boolean("logical_or", &rhs)?;
+        Expression::or(lhs, rhs)
+    }
+
+    /// Create a logical not `Expression` of a 1-bit condition.
+    /// # Error
+    /// expression is not 1 bit.
+    pub fn logical_not(expression: Expression) -> Result<Expression> {
+        Expression::ensure_boolean("logical_not", &expression)?;
+        Expression::xor(expression, expr_const(1, 1))
+    }
+}
+
+
+/// Returns name as an SMT-LIB2 symbol, quoting it if required.
+fn smtlib2_symbol(name: &str) -> String {
+    let simple = name.chars().all(|c| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c))
+                 && !name.starts_with(|c: char| c.is_ascii_digit())
+                 && !name.is_empty();
+    if simple {
+        name.to_string()
+    }
+    else {
+        format!("|{}|", name)
+    }
 }
 
 
@@ -384,6 +1293,435 @@ impl fmt::Display for Expression {
                 write!(f, "sext.{}({})", bits, src),
             Expression::Trun(ref bits, ref src) =>
                 write!(f, "trun.{}({})", bits, src),
+            Expression::Popcnt(ref src) =>
+                write!(f, "popcnt({})", src),
+            Expression::Clz(ref src) =>
+                write!(f, "clz({})", src),
+            Expression::Ctz(ref src) =>
+                write!(f, "ctz({})", src),
+            Expression::Neg(ref src) =>
+                write!(f, "neg({})", src),
         }
     }
 }
+
+
+#[test]
+fn pretty() {
+    let a = || expr_scalar("a", 32);
+    let b = || expr_scalar("b", 32);
+    let c = || expr_scalar("c", 32);
+
+    let expr = Expression::add(a(), Expression::mul(b(), c()).unwrap()).unwrap();
+    assert_eq!(expr.pretty(), "a + (b * c)");
+
+    let expr = Expression::mul(Expression::add(a(), b()).unwrap(), c()).unwrap();
+    assert_eq!(expr.pretty(), "(a + b) * c");
+
+    let expr = Expression::sub(Expression::add(a(), b()).unwrap(), c()).unwrap();
+    assert_eq!(expr.pretty(), "a + b - c");
+
+    let expr = Expression::sub(a(), Expression::add(b(), c()).unwrap()).unwrap();
+    assert_eq!(expr.pretty(), "a - (b + c)");
+
+    let expr = Expression::cmpltu(
+        Expression::zext(32, Expression::trun(8, a()).unwrap()).unwrap(),
+        expr_const(0x10, 32)
+    ).unwrap();
+    assert_eq!(expr.pretty(), "zext.32(trun.8(a)) <u 0x10");
+    assert_eq!(format!("{}", expr), "(zext.32(trun.8(a:32)) <u 0x10:32)");
+}
+
+
+#[test]
+fn smtlib2() {
+    let expr = Expression::cmplts(
+        Expression::sub(expr_scalar("eax", 32), expr_const(1, 32)).unwrap(),
+        Expression::sext(32, expr_scalar("1bl", 8)).unwrap()
+    ).unwrap();
+    assert_eq!(expr.smtlib2().unwrap(),
+               "(ite (bvslt (bvsub eax (_ bv1 32)) ((_ sign_extend 24) |1bl|)) #b1 #b0)");
+    assert_eq!(expr.smtlib2_declarations().unwrap(),
+               "(declare-fun |1bl| () (_ BitVec 8))\n(declare-fun eax () (_ BitVec 32))");
+
+    let expr = Expression::trun(8, expr_scalar("eax", 32)).unwrap();
+    assert_eq!(expr.smtlib2().unwrap(), "((_ extract 7 0) eax)");
+
+    assert!(Expression::cmpeq(expr_scalar("ZF", 0), expr_scalar("CF", 0)).unwrap()
+                                                                         .smtlib2()
+                                                                         .is_err());
+}
+
+
+#[test]
+fn scalar_declarations() {
+    let expr = Expression::add(expr_scalar("eax", 32), expr_scalar("eax", 32)).unwrap();
+    let declarations = expr.scalar_declarations().unwrap();
+    assert_eq!(declarations.len(), 1);
+    assert!(declarations.contains(&scalar("eax", 32)));
+
+    let expr = Expression::add(
+        expr_scalar("eax", 32),
+        Expression::zext(32, expr_scalar("eax", 8)).unwrap()
+    ).unwrap();
+    assert!(expr.scalar_declarations().is_err());
+    assert!(expr.smtlib2_declarations().is_err());
+}
+
+
+#[test]
+fn canonicalize() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    fn hash(expression: &Expression) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        expression.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let a = || expr_scalar("a", 32);
+    let b = || expr_scalar("b", 32);
+    let c = || expr_scalar("c", 32);
+
+    // (a + b) ^ (c * 2)
+    let lhs = Expression::xor(
+        Expression::add(a(), b()).unwrap(),
+        Expression::mul(c(), expr_const(2, 32)).unwrap()
+    ).unwrap();
+    // (2 * c) ^ (b + a)
+    let rhs = Expression::xor(
+        Expression::mul(expr_const(2, 32), c()).unwrap(),
+        Expression::add(b(), a()).unwrap()
+    ).unwrap();
+
+    assert!(lhs != rhs);
+    assert_eq!(lhs.canonicalize(), rhs.canonicalize());
+    assert_eq!(hash(&lhs.canonicalize()), hash(&rhs.canonicalize()));
+
+    let lhs = Expression::sub(a(), b()).unwrap();
+    let rhs = Expression::sub(b(), a()).unwrap();
+    assert!(lhs.canonicalize() != rhs.canonicalize());
+}
+
+
+#[test]
+fn depth_node_count() {
+    let expr = expr_scalar("a", 32);
+    assert_eq!(expr.depth(), 1);
+    assert_eq!(expr.node_count(), 1);
+
+    let expr = Expression::mul(
+        Expression::add(expr_scalar("a", 32), expr_scalar("b", 32)).unwrap(),
+        expr_scalar("c", 32)
+    ).unwrap();
+    assert_eq!(expr.depth(), 3);
+    assert_eq!(expr.node_count(), 5);
+
+    let expr = Expression::zext(64, expr).unwrap();
+    assert_eq!(expr.depth(), 4);
+    assert_eq!(expr.node_count(), 6);
+}
+
+
+#[test]
+fn validate() {
+    let expr = Expression::cmpltu(expr_scalar("a", 32), expr_scalar("b", 32)).unwrap();
+    assert_eq!(expr.bits(), 1);
+    assert!(expr.validate().is_ok());
+
+    let expr = Expression::zext(64, expr_scalar("a", 32)).unwrap();
+    assert_eq!(expr.bits(), 64);
+    assert!(expr.validate().is_ok());
+
+    let expr = Expression::Add(
+        Box::new(Expression::Trun(8, Box::new(expr_scalar("a", 32)))),
+        Box::new(expr_scalar("b", 32))
+    );
+    assert!(expr.validate().is_err());
+
+    let expr = Expression::Cmpeq(
+        Box::new(expr_const(1, 32)),
+        Box::new(Expression::Zext(32, Box::new(expr_const(1, 64))))
+    );
+    assert!(expr.validate().is_err());
+}
+
+
+#[test]
+fn sort_error() {
+    let error = Expression::add(expr_scalar("a", 32), expr_scalar("b", 16)).unwrap_err();
+    match *error.kind() {
+        ErrorKind::Sort(ref operator, lhs, rhs) => {
+            assert_eq!(operator, "add");
+            assert_eq!(lhs, 32);
+            assert_eq!(rhs, 16);
+        },
+        _ => panic!("expected sort error")
+    }
+    assert_eq!(error.to_string(), "sort error: add operands of 32 and 16 bits");
+}
+
+
+#[test]
+fn specialize() {
+    let condition = Expression::cmpltu(
+        Expression::add(expr_scalar("a", 32), expr_const(4, 32)).unwrap(),
+        expr_scalar("b", 32)
+    ).unwrap();
+
+    let a = scalar("a", 32);
+    let b = scalar("b", 32);
+    let condition = condition.specialize(&a, &const_(2, 32)).unwrap();
+    assert!(condition.scalars() == vec![&b]);
+    assert!(::executor::eval(&condition).is_err());
+
+    let condition = condition.specialize(&b, &const_(7, 32)).unwrap();
+    assert!(condition.scalars().is_empty());
+    assert_eq!(::executor::eval(&condition).unwrap(), const_(1, 1));
+
+    assert!(condition.specialize(&a, &const_(2, 64)).is_err());
+}
+
+
+#[test]
+fn extract() {
+    let expr = Expression::extract(expr_scalar("a", 32), 8, 15).unwrap();
+    assert_eq!(expr.bits(), 8);
+    assert_eq!(expr,
+        Expression::trun(8,
+            Expression::shr(expr_scalar("a", 32), expr_const(8, 32)).unwrap()
+        ).unwrap());
+
+    let value = expr.specialize(&scalar("a", 32), &const_(0x12345678, 32)).unwrap();
+    assert_eq!(::executor::eval(&value).unwrap(), const_(0x56, 8));
+
+    assert_eq!(Expression::extract(expr_scalar("a", 32), 0, 31).unwrap(), expr_scalar("a", 32));
+    assert_eq!(Expression::extract(expr_scalar("a", 32), 0, 7).unwrap().bits(), 8);
+    assert_eq!(Expression::extract(expr_scalar("a", 32), 24, 31).unwrap().bits(), 8);
+    assert!(Expression::extract(expr_scalar("a", 32), 8, 32).is_err());
+    assert!(Expression::extract(expr_scalar("a", 32), 9, 8).is_err());
+}
+
+
+#[test]
+fn concat() {
+    let expr = Expression::concat(expr_scalar("dx", 16), expr_scalar("ax", 16)).unwrap();
+    assert_eq!(expr.bits(), 32);
+    assert!(expr.validate().is_ok());
+
+    let expr = expr.specialize(&scalar("dx", 16), &const_(0x1234, 16)).unwrap()
+                   .specialize(&scalar("ax", 16), &const_(0xabcd, 16)).unwrap();
+    assert_eq!(::executor::eval(&expr).unwrap(), const_(0x1234abcd, 32));
+
+    assert!(Expression::concat(expr_scalar("flag", 0), expr_scalar("ax", 16)).is_err());
+}
+
+
+#[test]
+fn deposit() {
+    let expr = Expression::deposit(expr_scalar("a", 32), expr_scalar("b", 8), 8, 15).unwrap();
+    assert_eq!(expr.bits(), 32);
+    assert!(expr.validate().is_ok());
+
+    let value = expr.specialize(&scalar("a", 32), &const_(0x12345678, 32)).unwrap()
+                    .specialize(&scalar("b", 8), &const_(0xab, 8)).unwrap();
+    assert_eq!(::executor::eval(&value).unwrap(), const_(0x1234ab78, 32));
+
+    let extracted = Expression::extract(value, 8, 15).unwrap();
+    assert_eq!(::executor::eval(&extracted).unwrap(), const_(0xab, 8));
+
+    let low = Expression::deposit(expr_const(0xffffffff, 32), expr_const(0, 4), 0, 3).unwrap();
+    assert_eq!(::executor::eval(&low).unwrap(), const_(0xfffffff0, 32));
+    let high = Expression::deposit(expr_const(0, 64), expr_const(1, 1), 63, 63).unwrap();
+    assert_eq!(::executor::eval(&high).unwrap(), const_(0x8000000000000000, 64));
+
+    assert_eq!(Expression::deposit(expr_scalar("a", 32), expr_scalar("b", 32), 0, 31).unwrap(),
+               expr_scalar("b", 32));
+    assert!(Expression::deposit(expr_scalar("a", 32), expr_scalar("b", 16), 8, 15).is_err());
+    assert!(Expression::deposit(expr_scalar("a", 32), expr_scalar("b", 8), 25, 32).is_err());
+    assert!(Expression::deposit(expr_scalar("a", 32), expr_scalar("b", 8), 15, 8).is_err());
+}
+
+
+#[test]
+fn as_constant() {
+    let expr = Expression::mul(
+        Expression::add(expr_const(1, 32), expr_const(2, 32)).unwrap(),
+        Expression::zext(32, expr_const(7, 8)).unwrap()
+    ).unwrap();
+    assert!(expr.is_constant());
+    assert_eq!(expr.as_constant(), Some(const_(21, 32)));
+
+    let expr = Expression::add(expr, expr_scalar("a", 32)).unwrap();
+    assert!(!expr.is_constant());
+    assert_eq!(expr.as_constant(), None);
+
+    let expr = Expression::divu(expr_const(1, 32), expr_const(0, 32)).unwrap();
+    assert!(expr.is_constant());
+    assert_eq!(expr.as_constant(), None);
+}
+
+
+#[test]
+fn logical() {
+    let lt = Expression::cmpltu(expr_scalar("a", 32), expr_scalar("b", 32)).unwrap();
+    let eq = Expression::cmpeq(expr_scalar("c", 32), expr_scalar("d", 32)).unwrap();
+
+    let both = Expression::logical_and(lt.clone(), eq.clone()).unwrap();
+    assert_eq!(both.bits(), 1);
+    assert_eq!(Expression::logical_or(lt.clone(), eq.clone()).unwrap().bits(), 1);
+    let not = Expression::logical_not(both).unwrap();
+    assert_eq!(not.bits(), 1);
+
+    let not = not.specialize(&scalar("a", 32), &const_(1, 32)).unwrap()
+                 .specialize(&scalar("b", 32), &const_(2, 32)).unwrap()
+                 .specialize(&scalar("c", 32), &const_(3, 32)).unwrap()
+                 .specialize(&scalar("d", 32), &const_(3, 32)).unwrap();
+    assert_eq!(::executor::eval(&not).unwrap(), const_(0, 1));
+
+    assert!(Expression::logical_and(lt.clone(), expr_scalar("a", 32)).is_err());
+    assert!(Expression::logical_or(expr_scalar("a", 32), expr_scalar("b", 32)).is_err());
+    assert!(Expression::logical_not(expr_scalar("a", 32)).is_err());
+}
+
+
+#[test]
+fn byte_swap() {
+    let expr = Expression::byte_swap(expr_const(0x11223344, 32)).unwrap();
+    assert_eq!(expr.bits(), 32);
+    assert_eq!(::executor::eval(&expr).unwrap(), const_(0x44332211, 32));
+
+    let expr = Expression::byte_swap(expr_scalar("a", 16)).unwrap()
+                          .specialize(&scalar("a", 16), &const_(0xabcd, 16)).unwrap();
+    assert_eq!(::executor::eval(&expr).unwrap(), const_(0xcdab, 16));
+
+    assert_eq!(Expression::byte_swap(expr_scalar("a", 8)).unwrap(), expr_scalar("a", 8));
+    assert!(Expression::byte_swap(expr_scalar("a", 12)).is_err());
+}
+
+
+#[test]
+fn bit_counting() {
+    let expr = Expression::popcnt(expr_const(0b1011, 32)).unwrap();
+    assert_eq!(expr.bits(), 32);
+    assert_eq!(::executor::eval(&expr).unwrap(), const_(3, 32));
+
+    let clz = |value| ::executor::eval(&Expression::clz(expr_const(value, 32)).unwrap()).unwrap();
+    assert_eq!(clz(0x0001_0000), const_(15, 32));
+    assert_eq!(clz(0x8000_0000), const_(0, 32));
+    assert_eq!(clz(0), const_(32, 32));
+
+    let ctz = |value| ::executor::eval(&Expression::ctz(expr_const(value, 32)).unwrap()).unwrap();
+    assert_eq!(ctz(0x0001_0000), const_(16, 32));
+    assert_eq!(ctz(0), const_(32, 32));
+
+    let wide = Expression::zext(128, expr_const(1, 64)).unwrap();
+    let wide = Expression::shl(wide, expr_const(100, 128)).unwrap();
+    let expr = Expression::clz(wide).unwrap();
+    assert_eq!(::executor::eval(&expr).unwrap(), const_(27, 128));
+
+    let expr = Expression::ctz(expr_scalar("a", 8)).unwrap();
+    assert!(expr.validate().is_ok());
+    assert_eq!(expr.to_string(), "ctz(a:8)");
+    assert!(expr.smtlib2().unwrap().starts_with("(ite (= ((_ extract 0 0) a) #b1) (_ bv0 8) "));
+    assert!(Expression::popcnt(expr_scalar("flag", 0)).is_err());
+}
+
+
+#[test]
+fn normalize_additive() {
+    // (a - b) - (c - 3)
+    let inner = Expression::sub(expr_scalar("c", 32), expr_const(3, 32)).unwrap();
+    let outer = Expression::sub(expr_scalar("a", 32), expr_scalar("b", 32)).unwrap();
+    let expr = Expression::sub(outer, inner).unwrap();
+
+    let normalized = expr.normalize_additive();
+    assert_eq!(normalized.bits(), 32);
+    assert!(normalized.validate().is_ok());
+    assert_eq!(normalized.pretty(), "a + neg(b) + neg(c + neg(0x3))");
+
+    let a = Expression::neg(expr_scalar("b", 32)).unwrap();
+    let a = Expression::add(expr_scalar("a", 32), a).unwrap();
+    let c = Expression::neg(expr_const(3, 32)).unwrap();
+    let c = Expression::add(expr_scalar("c", 32), c).unwrap();
+    let c = Expression::neg(c).unwrap();
+    assert_eq!(normalized, Expression::add(a, c).unwrap());
+
+    // Constant chains still evaluate to the same value
+    let expr = Expression::sub(expr_const(2, 32), expr_const(7, 32)).unwrap();
+    let expr = Expression::sub(expr, expr_const(1, 32)).unwrap();
+    assert_eq!(expr.normalize_additive().as_constant(), Some(const_(0xFFFF_FFFA, 32)));
+    assert_eq!(expr.normalize_additive().as_constant(), expr.as_constant());
+    assert_eq!(Expression::neg(expr_const(1, 8)).unwrap().smtlib2().unwrap(), "(bvneg (_ bv1 8))");
+}
+
+
+#[test]
+fn cost() {
+    let a = || expr_scalar("a", 32);
+    let divu = Expression::divu(a(), Expression::add(a(), expr_const(1, 32)).unwrap()).unwrap();
+    let add = Expression::add(a(), Expression::add(a(), expr_const(1, 32)).unwrap()).unwrap();
+    assert_eq!(divu.node_count(), add.node_count());
+    assert!(divu.cost() > add.cost());
+    assert_eq!(add.cost(), 7);
+    assert_eq!(a().cost(), 1);
+}
+
+
+#[test]
+fn structurally_equivalent() {
+    let a = || expr_scalar("a", 32);
+    let b = || expr_scalar("b", 32);
+    let c = |value| expr_const(value, 32);
+
+    let equivalent = vec![
+        (Expression::add(a(), b()).unwrap(), Expression::add(b(), a()).unwrap()),
+        (Expression::mul(a(), c(1)).unwrap(), a()),
+        (Expression::add(a(), Expression::sub(c(2), c(2)).unwrap()).unwrap(), a()),
+        (Expression::sub(a(), c(0)).unwrap(), a()),
+        (Expression::mul(Expression::add(c(1), c(2)).unwrap(), a()).unwrap(),
+         Expression::mul(a(), c(3)).unwrap()),
+        (Expression::sub(a(), b()).unwrap(),
+         Expression::add(Expression::neg(b()).unwrap(), a()).unwrap()),
+        (Expression::cmpeq(Expression::or(a(), c(0)).unwrap(), b()).unwrap(),
+         Expression::cmpeq(b(), a()).unwrap())
+    ];
+    for (lhs, rhs) in equivalent {
+        assert!(lhs.structurally_equivalent(&rhs), "{} and {}", lhs, rhs);
+        assert!(rhs.structurally_equivalent(&lhs), "{} and {}", rhs, lhs);
+    }
+
+    let not_equivalent = vec![
+        (Expression::sub(a(), b()).unwrap(), Expression::sub(b(), a()).unwrap()),
+        (Expression::mul(a(), c(2)).unwrap(), a()),
+        (Expression::cmpltu(a(), b()).unwrap(), Expression::cmpltu(b(), a()).unwrap()),
+        (a(), expr_scalar("a", 64)),
+        // Equal, but not recognized
+        (Expression::add(a(), a()).unwrap(), Expression::mul(a(), c(2)).unwrap())
+    ];
+    for (lhs, rhs) in not_equivalent {
+        assert!(!lhs.structurally_equivalent(&rhs), "{} and {}", lhs, rhs);
+    }
+}
+
+
+#[test]
+fn masked_shifts() {
+    let eval = |expression: Expression| ::executor::eval(&expression).unwrap();
+    let x = || expr_const(0x8000_0001, 32);
+
+    assert_eq!(eval(Expression::shl(x(), expr_const(33, 32)).unwrap()), const_(0, 32));
+    assert_eq!(eval(Expression::shr(x(), expr_const(33, 32)).unwrap()), const_(0, 32));
+    assert_eq!(eval(Expression::shl(x(), expr_const(32, 32)).unwrap()), const_(0, 32));
+
+    assert_eq!(eval(Expression::shl_masked(x(), expr_const(33, 32)).unwrap()),
+               eval(Expression::shl(x(), expr_const(1, 32)).unwrap()));
+    assert_eq!(eval(Expression::shr_masked(x(), expr_const(33, 32)).unwrap()),
+               const_(0x4000_0000, 32));
+    assert_eq!(eval(Expression::shl_masked(x(), expr_const(31, 32)).unwrap()),
+               const_(0x8000_0000, 32));
+
+    assert!(Expression::shl_masked(expr_const(1, 24), expr_const(1, 24)).is_err());
+    assert!(Expression::shr_masked(x(), expr_const(1, 8)).is_err());
+}
@@ -2,7 +2,12 @@
 //!
 //! We can think of a `Function` as providing _location_ to a `ControlFlowGraph`.
 
+use analysis::calling_convention::CallingConvention;
+use analysis::def_use;
+use analysis::stack_pointer;
 use il::*;
+use il::stable;
+use std::collections::BTreeMap;
 
 
 /// A function for Falcon IL. Provides location and context in a `Program` to a
@@ -16,7 +21,23 @@ pub struct Function {
     // The name of the function
     name: Option<String>,
     // Functions which belong to Programs have indices
-    index: Option<u64>
+    index: Option<u64>,
+    // True if this function is known to never return to its caller
+    #[serde(default)]
+    no_return: bool
+}
+
+
+/// A kind of operation which has no effect, or an edge which does not depend
+/// on its condition, as reported by `Function::trivial_operations`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum TrivialKind {
+    /// An `Assign` of a `Scalar` to itself.
+    SelfAssignment,
+    /// An edge with a condition which is always 1.
+    AlwaysTaken,
+    /// An edge with a condition which is always 0.
+    NeverTaken
 }
 
 
@@ -31,7 +52,8 @@ impl Function {
             address: address,
             control_flow_graph: control_flow_graph,
             name: None,
-            index: None
+            index: None,
+            no_return: false
         }
     }
 
@@ -61,6 +83,98 @@ impl Function {
         locations
     }
 
+    /// Get every `Instruction` in this `Function`, with the index of the
+    /// `Block` it belongs to, in order of `Block` index.
+    pub fn instructions(&self) -> Vec<(u64, &Instruction)> {
+        let mut instructions = Vec::new();
+        for block in self.blocks() {
+            for instruction in block.instructions() {
+                instructions.push((block.index(), instruction));
+            }
+        }
+        instructions
+    }
+
+    /// Get a `RefProgramLocation` for every `Instruction` in this `Function`,
+    /// in the same order as `Function::instructions`.
+    pub fn instruction_locations<'f>(&'f self) -> Vec<RefProgramLocation<'f>> {
+        let mut locations = Vec::new();
+        for block in self.blocks() {
+            for instruction in block.instructions() {
+                locations.push(RefProgramLocation::new(self,
+                    RefFunctionLocation::Instruction(block, instruction)));
+            }
+        }
+        locations
+    }
+
+    /// Get every `Load` and `Store` operation in this `Function`, with its
+    /// location, in the same order as `Function::instructions`.
+    pub fn memory_operations(&self) -> Vec<(ProgramLocation, &Operation)> {
+        let mut operations = Vec::new();
+        for block in self.blocks() {
+            for instruction in block.instructions() {
+                if instruction.is_load() || instruction.is_store() {
+                    let location = RefProgramLocation::new(self,
+                        RefFunctionLocation::Instruction(block, instruction));
+                    operations.push((location.into(), instruction.operation()));
+                }
+            }
+        }
+        operations
+    }
+
+    /// Get the location of every `Store` operation in this `Function`.
+    pub fn store_locations(&self) -> Vec<ProgramLocation> {
+        self.memory_operations()
+            .into_iter()
+            .filter(|&(_, operation)| match *operation {
+                Operation::Store { .. } => true,
+                _ => false
+            })
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Get the location of every `Load` operation in this `Function`.
+    pub fn load_locations(&self) -> Vec<ProgramLocation> {
+        self.memory_operations()
+            .into_iter()
+            .filter(|&(_, operation)| match *operation {
+                Operation::Load { .. } => true,
+                _ => false
+            })
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Get the location of every `Instruction` in this `Function` whose
+    /// `Operation` is of the given kind, in the same order as
+    /// `Function::instructions`.
+    pub fn locations_of_kind(&self, kind: OperationKind) -> Vec<ProgramLocation> {
+        let mut locations = Vec::new();
+        for block in self.blocks() {
+            for instruction in block.instructions_of_kind(kind) {
+                let location = RefProgramLocation::new(self,
+                    RefFunctionLocation::Instruction(block, instruction));
+                locations.push(location.into());
+            }
+        }
+        locations
+    }
+
+    /// Get the `Instruction` at the given `FunctionLocation`, or `None` if the
+    /// location is not an `Instruction` in this `Function`.
+    pub fn instruction_at(&self, location: &FunctionLocation) -> Option<&Instruction> {
+        match *location {
+            FunctionLocation::Instruction(block_index, instruction_index) =>
+                self.block(block_index)
+                    .and_then(|block| block.instruction(instruction_index)),
+            FunctionLocation::Edge(_, _) |
+            FunctionLocation::EmptyBlock(_) => None
+        }
+    }
+
     /// Get the address of this `Function`.
     ///
     /// The address returned will be the address set when this `Function` was created,
@@ -74,6 +188,17 @@ impl Function {
         self.control_flow_graph.block(index)
     }
 
+    /// Return the first `Block`, in index order, whose `Block::address_range`
+    /// covers the given address, or `None` if no `Block` covers it.
+    pub fn block_at_address(&self, address: u64) -> Option<&Block> {
+        self.blocks()
+            .into_iter()
+            .find(|block| match block.address_range() {
+                Some((low, high)) => low <= address && address <= high,
+                None => false
+            })
+    }
+
     /// Return a Vec of all `Block` in this `Function`
     pub fn blocks(&self) -> Vec<&Block> {
         self.control_flow_graph.blocks()
@@ -127,4 +252,1020 @@ impl Function {
     pub fn set_index(&mut self, index: Option<u64>) {
         self.index = index;
     }
-}
\ No newline at end of file
+
+    /// Returns true if this `Function` is known to never return to its
+    /// caller, such as `exit` or `abort`.
+    pub fn is_no_return(&self) -> bool {
+        self.no_return
+    }
+
+    /// Set whether this `Function` is known to never return to its caller.
+    pub fn set_no_return(&mut self, no_return: bool) {
+        self.no_return = no_return;
+    }
+
+    /// Rename every `Scalar` read or written in this `Function`, including in
+    /// edge conditions.
+    ///
+    /// `f` returns the replacement for a `Scalar`, or `None` to leave it as is.
+    /// # Error
+    /// `f` returned a replacement with a different bitness. Scalars renamed
+    /// before the error is found remain renamed.
+    pub fn rename_scalars<F>(&mut self, f: F) -> Result<()>
+        where F: Fn(&Scalar) -> Option<Scalar> {
+
+        let rename = |scalar: &mut Scalar| -> Result<()> {
+            if let Some(renamed) = f(scalar) {
+                if renamed.bits() != scalar.bits() {
+                    bail!("Renaming {} to {} changes its bitness", scalar, renamed);
+                }
+                *scalar = renamed;
+            }
+            Ok(())
+        };
+
+        for block in self.control_flow_graph.blocks_mut() {
+            for instruction in block.instructions_mut() {
+                for scalar in instruction.scalars_read_mut() {
+                    rename(scalar)?;
+                }
+                for scalar in instruction.scalars_written_mut() {
+                    rename(scalar)?;
+                }
+            }
+        }
+
+        for edge in self.control_flow_graph.edges_mut() {
+            if let Some(ref mut condition) = *edge.condition_mut() {
+                for scalar in condition.scalars_mut() {
+                    rename(scalar)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace every `Expression` in this `Function`, including edge
+    /// conditions, with the result of `f` applied to it.
+    ///
+    /// `f` is applied to each whole `Expression` of an `Operation` or edge, not
+    /// to its operands.
+    /// # Error
+    /// `f` returned an error, or a replacement with a different bitness.
+    /// Expressions replaced before the error is found remain replaced.
+    pub fn map_expressions<F>(&mut self, f: F) -> Result<()>
+        where F: Fn(&Expression) -> Result<Expression> {
+
+        let map = |expression: &mut Expression| -> Result<()> {
+            let mapped = f(expression)?;
+            if mapped.bits() != expression.bits() {
+                bail!("Mapping {} to {} changes its bitness", expression, mapped);
+            }
+            *expression = mapped;
+            Ok(())
+        };
+
+        for block in self.control_flow_graph.blocks_mut() {
+            for instruction in block.instructions_mut() {
+                for expression in instruction.operation_mut().expressions_mut() {
+                    map(expression)?;
+                }
+            }
+        }
+
+        for edge in self.control_flow_graph.edges_mut() {
+            if let Some(ref mut condition) = *edge.condition_mut() {
+                map(condition)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the greatest bitness of any `Scalar` or `Constant` in this
+    /// `Function`, including in edge conditions, or 0 if there are none.
+    pub fn max_scalar_bits(&self) -> usize {
+        fn max_bits(expression: &Expression) -> usize {
+            match *expression {
+                Expression::Scalar(ref scalar) => scalar.bits(),
+                Expression::Constant(ref constant) => constant.bits(),
+                _ => expression.children()
+                               .into_iter()
+                               .map(max_bits)
+                               .max()
+                               .unwrap_or(0)
+            }
+        }
+
+        let mut bits = 0;
+        for (_, instruction) in self.instructions() {
+            let operation = instruction.operation();
+            for expression in operation.expressions() {
+                bits = bits.max(max_bits(expression));
+            }
+            for scalar in operation.scalars_written() {
+                bits = bits.max(scalar.bits());
+            }
+        }
+        for edge in self.edges() {
+            if let Some(ref condition) = *edge.condition() {
+                bits = bits.max(max_bits(condition));
+            }
+        }
+        bits
+    }
+
+    /// Ensures the IL of this `Function` is well-formed.
+    ///
+    /// Every `Operation` must be valid, as in `Operation::validate`, every edge
+    /// must join existing blocks, every edge condition must be a valid 1-bit
+    /// `Expression`, and the entry of the `ControlFlowGraph` must be set to an
+    /// existing block.
+    /// # Error
+    /// The first violation found, identifying the offending block and
+    /// instruction, or edge.
+    pub fn validate(&self) -> Result<()> {
+        match self.control_flow_graph.entry() {
+            Some(entry) => if self.block(entry).is_none() {
+                bail!("Entry block 0x{:X} does not exist", entry);
+            },
+            None => bail!("Entry block is not set")
+        }
+
+        for block in self.blocks() {
+            for instruction in block.instructions() {
+                if let Err(error) = instruction.operation().validate() {
+                    bail!("Invalid instruction 0x{:X} in block 0x{:X}: {}",
+                          instruction.index(), block.index(), error);
+                }
+            }
+        }
+
+        for edge in self.edges() {
+            if self.block(edge.head()).is_none() || self.block(edge.tail()).is_none() {
+                bail!("Edge (0x{:X}->0x{:X}) joins a block which does not exist",
+                      edge.head(), edge.tail());
+            }
+            if let Some(ref condition) = *edge.condition() {
+                if let Err(error) = condition.validate() {
+                    bail!("Invalid condition on edge (0x{:X}->0x{:X}): {}",
+                          edge.head(), edge.tail(), error);
+                }
+                if condition.bits() != 1 {
+                    bail!("Condition on edge (0x{:X}->0x{:X}) has {} bits, not 1",
+                          edge.head(), edge.tail(), condition.bits());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the size of the local stack frame allocated by this `Function`.
+    ///
+    /// This is the furthest the given stack pointer moves below its value at
+    /// function entry. Returns `None` if the stack pointer's offset can not be
+    /// determined at every location in the `Function`.
+    pub fn frame_size(&self, stack_pointer: &Scalar) -> Result<Option<u64>> {
+        let offsets = stack_pointer::stack_offsets(self, stack_pointer)?;
+
+        let mut frame_size = 0;
+        for offset in offsets.values() {
+            match *offset {
+                Some(offset) => if offset < 0 && (offset.wrapping_neg() as u64) > frame_size {
+                    frame_size = offset.wrapping_neg() as u64;
+                },
+                None => return Ok(None)
+            }
+        }
+
+        Ok(Some(frame_size))
+    }
+
+    /// Returns true if this `Function` and other have the same control flow
+    /// graph, up to the numbering of their blocks.
+    ///
+    /// Blocks are matched starting from the entries, then by following edges
+    /// with equal conditions. Matched blocks must have the same operations.
+    /// Addresses, comments and the address and name of the `Function` are not
+    /// compared.
+    pub fn structurally_equal(&self, other: &Function) -> bool {
+        let lhs = &self.control_flow_graph;
+        let rhs = &other.control_flow_graph;
+
+        if lhs.blocks().len() != rhs.blocks().len()
+            || lhs.edges().len() != rhs.edges().len() {
+            return false;
+        }
+
+        let (lhs_entry, rhs_entry) = match (lhs.entry(), rhs.entry()) {
+            (Some(lhs_entry), Some(rhs_entry)) => (lhs_entry, rhs_entry),
+            (None, None) => return lhs.blocks().is_empty(),
+            _ => return false
+        };
+
+        // Block indices in self mapped to block indices in other, and back.
+        let mut forward: BTreeMap<u64, u64> = BTreeMap::new();
+        let mut backward: BTreeMap<u64, u64> = BTreeMap::new();
+        forward.insert(lhs_entry, rhs_entry);
+        backward.insert(rhs_entry, lhs_entry);
+        let mut queue = vec![(lhs_entry, rhs_entry)];
+
+        while let Some((lhs_index, rhs_index)) = queue.pop() {
+            let operations_equal = match (lhs.block(lhs_index), rhs.block(rhs_index)) {
+                (Some(lhs_block), Some(rhs_block)) =>
+                    lhs_block.instructions().len() == rhs_block.instructions().len()
+                    && lhs_block.instructions()
+                                .iter()
+                                .zip(rhs_block.instructions())
+                                .all(|(l, r)| l.operation() == r.operation()),
+                _ => false
+            };
+            if !operations_equal {
+                return false;
+            }
+
+            let (lhs_edges, rhs_edges) = match (lhs.edges_out(lhs_index), rhs.edges_out(rhs_index)) {
+                (Some(lhs_edges), Some(rhs_edges)) => (lhs_edges, rhs_edges),
+                _ => return false
+            };
+            if lhs_edges.len() != rhs_edges.len() {
+                return false;
+            }
+
+            let mut matched = vec![false; rhs_edges.len()];
+            for lhs_edge in &lhs_edges {
+                let position = rhs_edges.iter().enumerate().position(|(i, rhs_edge)| {
+                    !matched[i]
+                    && lhs_edge.condition() == rhs_edge.condition()
+                    && forward.get(&lhs_edge.tail()).map_or(true, |t| *t == rhs_edge.tail())
+                    && backward.get(&rhs_edge.tail()).map_or(true, |t| *t == lhs_edge.tail())
+                });
+                let position = match position {
+                    Some(position) => position,
+                    None => return false
+                };
+                matched[position] = true;
+
+                let (lhs_tail, rhs_tail) = (lhs_edge.tail(), rhs_edges[position].tail());
+                if !forward.contains_key(&lhs_tail) {
+                    forward.insert(lhs_tail, rhs_tail);
+                    backward.insert(rhs_tail, lhs_tail);
+                    queue.push((lhs_tail, rhs_tail));
+                }
+            }
+        }
+
+        if forward.len() != lhs.blocks().len() {
+            return false;
+        }
+
+        match (lhs.exit(), rhs.exit()) {
+            (Some(lhs_exit), Some(rhs_exit)) => forward[&lhs_exit] == rhs_exit,
+            (None, None) => true,
+            _ => false
+        }
+    }
+
+    /// Compute a hash of the structure and operations of this `Function`,
+    /// suitable for keying a cache of analysis results.
+    ///
+    /// The hash depends on neither block indices, instruction indices,
+    /// addresses, names nor comments, so functions which are
+    /// `structurally_equal` have the same fingerprint. It is stable across
+    /// runs and platforms.
+    pub fn fingerprint(&self) -> u64 {
+        let cfg = &self.control_flow_graph;
+        let labels = stable::block_labels(cfg).unwrap_or_default();
+
+        let mut blocks = labels.values().collect::<Vec<&u64>>();
+        blocks.sort();
+        let mut edges = cfg.edges()
+                           .into_iter()
+                           .map(|edge| (labels.get(&edge.head()),
+                                        labels.get(&edge.tail()),
+                                        edge.condition()))
+                           .collect::<Vec<_>>();
+        edges.sort();
+
+        stable::stable_hash(&(blocks, edges))
+    }
+
+    /// Get the locations of trivial operations and edges in this `Function`,
+    /// which a lifter should not emit.
+    ///
+    /// Instructions are reported in the same order as `Function::instructions`,
+    /// followed by edges in the order of `Function::edges`.
+    pub fn trivial_operations(&self) -> Vec<(ProgramLocation, TrivialKind)> {
+        let mut trivial = Vec::new();
+
+        for location in self.instruction_locations() {
+            let self_assignment = match location.instruction().map(|i| i.operation()) {
+                Some(&Operation::Assign { ref dst, src: Expression::Scalar(ref src) }) =>
+                    dst == src,
+                _ => false
+            };
+            if self_assignment {
+                trivial.push((location.into(), TrivialKind::SelfAssignment));
+            }
+        }
+
+        for edge in self.edges() {
+            let kind = match edge.condition().as_ref().and_then(|c| c.as_constant()) {
+                Some(ref constant) if constant.value() == 0 => TrivialKind::NeverTaken,
+                Some(_) => TrivialKind::AlwaysTaken,
+                None => continue
+            };
+            let location = RefProgramLocation::new(self, RefFunctionLocation::Edge(edge));
+            trivial.push((location.into(), kind));
+        }
+
+        trivial
+    }
+
+    /// Get the locations of tail calls in this `Function`.
+    ///
+    /// A tail call is a branch to a constant target which is the address of
+    /// another `Function` in the program, where the stack pointer of the
+    /// calling convention is at its value at entry to this `Function`.
+    pub fn tail_calls(&self, program: &Program, calling_convention: &CallingConvention)
+    -> Result<Vec<ProgramLocation>> {
+        let offsets = stack_pointer::stack_offsets(self, calling_convention.stack_pointer())?;
+
+        let mut tail_calls = Vec::new();
+        for location in self.instruction_locations() {
+            let target = match location.instruction().map(|instruction| instruction.operation()) {
+                Some(&Operation::Branch { ref target }) => target.as_constant(),
+                _ => None
+            };
+            let target = match target.and_then(|target| target.value_u64().ok()) {
+                Some(target) => target,
+                None => continue
+            };
+            if target == self.address || program.function_by_address(target).is_none() {
+                continue;
+            }
+
+            let location: ProgramLocation = location.into();
+            if offsets.get(&location) == Some(&Some(0)) {
+                tail_calls.push(location);
+            }
+        }
+
+        Ok(tail_calls)
+    }
+
+    /// Get the locations of `Assign` and `Load` operations whose destination
+    /// is never read before it is written again or this `Function` exits.
+    ///
+    /// Only reachable locations are reported, and nothing is reported if this
+    /// `Function` has no entry.
+    pub fn dead_assignments(&self) -> Vec<ProgramLocation> {
+        let du = match def_use(self) {
+            Ok(du) => du,
+            Err(_) => return Vec::new()
+        };
+
+        let mut dead = du.into_iter()
+                         .filter(|&(ref location, ref uses)| {
+                             let assigns = match location.instruction().map(|i| i.operation()) {
+                                 Some(&Operation::Assign { .. }) |
+                                 Some(&Operation::Load { .. }) => true,
+                                 _ => false
+                             };
+                             assigns && uses.locations().is_empty()
+                         })
+                         .map(|(location, _)| location.into())
+                         .collect::<Vec<ProgramLocation>>();
+        dead.sort();
+        dead
+    }
+
+    /// Return a listing of this `Function` for reading in a terminal.
+    ///
+    /// The listing has a header with the name and address of this `Function`,
+    /// then each `Block` in index order with its instructions and outgoing
+    /// edges.
+    pub fn listing(&self) -> String {
+        let mut listing = format!("function {} @ 0x{:X}\n", self.name(), self.address);
+        let entry = self.control_flow_graph.entry();
+        let exit = self.control_flow_graph.exit();
+
+        for block in self.blocks() {
+            listing.push_str(&format!("\n[ Block: 0x{:X} ]", block.index()));
+            if entry == Some(block.index()) {
+                listing.push_str(" entry");
+            }
+            if exit == Some(block.index()) {
+                listing.push_str(" exit");
+            }
+            listing.push('\n');
+
+            for instruction in block.instructions() {
+                listing.push_str(&format!("  {}\n", instruction));
+            }
+
+            let edges = self.control_flow_graph.edges_out(block.index())
+                                               .unwrap_or_default();
+            for edge in edges {
+                listing.push_str(&format!("  -> 0x{:X}", edge.tail()));
+                if let Some(ref condition) = *edge.condition() {
+                    listing.push_str(&format!(" ? ({})", condition));
+                }
+                if let Some(ref comment) = *edge.comment() {
+                    listing.push_str(&format!(" // {}", comment));
+                }
+                listing.push('\n');
+            }
+        }
+
+        listing
+    }
+}
+
+
+#[test]
+fn frame_size() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+
+        block.assign(scalar("$sp", 32),
+            Expression::sub(expr_scalar("$sp", 32), expr_const(32, 32)).unwrap());
+        block.store(Expression::add(expr_scalar("$sp", 32), expr_const(4, 32)).unwrap(),
+            expr_scalar("$ra", 32));
+
+        block.index()
+    };
+
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+
+        block.load(scalar("$ra", 32),
+            Expression::add(expr_scalar("$sp", 32), expr_const(4, 32)).unwrap());
+        block.assign(scalar("$sp", 32),
+            Expression::add(expr_scalar("$sp", 32), expr_const(32, 32)).unwrap());
+        block.branch(expr_scalar("$ra", 32));
+
+        block.index()
+    };
+
+    control_flow_graph.unconditional_edge(head_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+
+    assert_eq!(function.frame_size(&scalar("$sp", 32)).unwrap(), Some(32));
+
+    // The frame size is unknown once the stack pointer is loaded from memory.
+    let mut function = function;
+    function.control_flow_graph_mut()
+            .block_mut(tail_index).unwrap()
+            .load(scalar("$sp", 32), expr_scalar("$s0", 32));
+
+    assert_eq!(function.frame_size(&scalar("$sp", 32)).unwrap(), None);
+
+    // An adjustment of i64::MIN is a frame of 2^63 bytes.
+    let mut control_flow_graph = ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap()
+                      .assign(scalar("sp", 64),
+                              Expression::add(expr_scalar("sp", 64), expr_const(1 << 63, 64)).unwrap());
+    control_flow_graph.set_entry(0).unwrap();
+    let function = Function::new(0, control_flow_graph);
+    assert_eq!(function.frame_size(&scalar("sp", 64)).unwrap(), Some(1 << 63));
+}
+
+#[test]
+fn instructions() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.assign(scalar("b", 32), expr_const(2, 32));
+        block.index()
+    };
+    let empty_index = control_flow_graph.new_block().unwrap().index();
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("c", 32), expr_const(3, 32));
+        block.index()
+    };
+    control_flow_graph.unconditional_edge(head_index, empty_index).unwrap();
+    control_flow_graph.unconditional_edge(empty_index, tail_index).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+
+    let instructions = function.instructions();
+    let visited = instructions.iter()
+                              .map(|&(block_index, instruction)|
+                                  (block_index, instruction.index()))
+                              .collect::<Vec<(u64, u64)>>();
+    assert_eq!(visited, vec![(head_index, 0), (head_index, 1), (tail_index, 0)]);
+
+    let locations = function.instruction_locations();
+    assert_eq!(locations.len(), 3);
+    for (location, &(block_index, instruction)) in locations.iter().zip(instructions.iter()) {
+        assert_eq!(location.block().unwrap().index(), block_index);
+        assert_eq!(location.instruction().unwrap(), instruction);
+    }
+}
+
+
+#[test]
+fn instruction_at() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.assign(scalar("b", 32), expr_const(2, 32));
+    }
+    let function = Function::new(0, control_flow_graph);
+
+    for location in function.instruction_locations() {
+        let instruction = location.instruction().unwrap();
+        let location: ProgramLocation = location.clone().into();
+        assert_eq!(function.instruction_at(location.function_location()), Some(instruction));
+    }
+
+    assert!(function.instruction_at(&FunctionLocation::Instruction(0, 2)).is_none());
+    assert!(function.instruction_at(&FunctionLocation::Instruction(1, 0)).is_none());
+    assert!(function.instruction_at(&FunctionLocation::EmptyBlock(0)).is_none());
+}
+
+
+#[test]
+fn memory_operations() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(0x1000, 32));
+        block.load(scalar("b", 32), expr_scalar("a", 32));
+        block.index()
+    };
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("c", 32), expr_scalar("b", 32));
+        block.store(expr_scalar("a", 32), expr_scalar("c", 32));
+        block.load(scalar("d", 32), expr_scalar("a", 32));
+        block.index()
+    };
+    control_flow_graph.unconditional_edge(head_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+
+    let location = |block_index, instruction_index| {
+        ProgramLocation::new(None, FunctionLocation::Instruction(block_index, instruction_index))
+    };
+
+    let memory_operations = function.memory_operations();
+    assert_eq!(memory_operations.len(), 3);
+    assert_eq!(memory_operations[0].0, location(head_index, 1));
+    assert_eq!(*memory_operations[0].1, Operation::load(scalar("b", 32), expr_scalar("a", 32)));
+    assert_eq!(memory_operations[1].0, location(tail_index, 1));
+    assert_eq!(memory_operations[2].0, location(tail_index, 2));
+
+    assert_eq!(function.store_locations(), vec![location(tail_index, 1)]);
+    assert_eq!(function.load_locations(), vec![location(head_index, 1), location(tail_index, 2)]);
+}
+
+
+#[test]
+fn validate() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.store(expr_scalar("sp", 32), expr_scalar("a", 32));
+        block.index()
+    };
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(expr_scalar("a", 32));
+        block.index()
+    };
+    control_flow_graph.conditional_edge(head_index, tail_index,
+        Expression::cmpeq(expr_scalar("a", 32), expr_const(0, 32)).unwrap()).unwrap();
+
+    let function = Function::new(0, control_flow_graph.clone());
+    assert_eq!(function.validate().unwrap_err().to_string(), "Entry block is not set");
+
+    control_flow_graph.set_entry(head_index).unwrap();
+    let function = Function::new(0, control_flow_graph.clone());
+    assert!(function.validate().is_ok());
+
+    // a 32-bit edge condition
+    let mut malformed = control_flow_graph.clone();
+    *malformed.edge_mut(head_index, tail_index).unwrap().condition_mut() =
+        Some(expr_scalar("a", 32));
+    let error = Function::new(0, malformed).validate().unwrap_err();
+    assert_eq!(error.to_string(), "Condition on edge (0x0->0x1) has 32 bits, not 1");
+
+    // a condition comparing operands of different bitness
+    let mut malformed = control_flow_graph.clone();
+    *malformed.edge_mut(head_index, tail_index).unwrap().condition_mut() =
+        Some(Expression::Cmpeq(Box::new(expr_scalar("a", 32)),
+                               Box::new(expr_const(0, 16))));
+    let error = Function::new(0, malformed).validate().unwrap_err();
+    assert_eq!(error.to_string(),
+        "Invalid condition on edge (0x0->0x1): sort error: cmpeq operands of 32 and 16 bits");
+
+    // an assignment of 16 bits to a 32-bit scalar
+    let mut malformed = control_flow_graph.clone();
+    malformed.block_mut(head_index)
+             .unwrap()
+             .assign(scalar("b", 32), expr_const(1, 16));
+    let error = Function::new(0, malformed).validate().unwrap_err();
+    assert_eq!(error.to_string(),
+        "Invalid instruction 0x2 in block 0x0: sort error: assign operands of 32 and 16 bits");
+}
+
+
+#[test]
+fn rename_scalars() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("eax", 64), Expression::zext(64, expr_scalar("eax", 32)).unwrap());
+        block.assign(scalar("eax", 32), expr_scalar("ebx", 32));
+        block.index()
+    };
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(expr_scalar("eax", 64));
+        block.index()
+    };
+    control_flow_graph.conditional_edge(head_index, tail_index,
+        Expression::cmpeq(expr_scalar("eax", 64), expr_const(0, 64)).unwrap()).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let mut function = Function::new(0, control_flow_graph);
+
+    function.rename_scalars(|scalar| {
+        if scalar.name() == "eax" && scalar.bits() == 64 {
+            Some(Scalar::new("rax", 64))
+        }
+        else {
+            None
+        }
+    }).unwrap();
+
+    let head = function.block(head_index).unwrap();
+    assert_eq!(head.instructions()[0].scalars_written(), vec![&scalar("rax", 64)]);
+    assert_eq!(head.instructions()[0].scalars_read(), vec![&scalar("eax", 32)]);
+    assert_eq!(head.instructions()[1].scalars_written(), vec![&scalar("eax", 32)]);
+    let tail = function.block(tail_index).unwrap();
+    assert_eq!(tail.instructions()[0].scalars_read(), vec![&scalar("rax", 64)]);
+    let edge = function.edge(head_index, tail_index).unwrap();
+    assert_eq!(edge.condition().as_ref().unwrap().scalars(), vec![&scalar("rax", 64)]);
+    let control_flow_graph = function.control_flow_graph();
+    let edge = control_flow_graph.edges_out(head_index).unwrap()[0];
+    assert_eq!(edge.condition().as_ref().unwrap().scalars(), vec![&scalar("rax", 64)]);
+    let edge = control_flow_graph.edges_in(tail_index).unwrap()[0];
+    assert_eq!(edge.condition().as_ref().unwrap().scalars(), vec![&scalar("rax", 64)]);
+
+    assert!(function.rename_scalars(|scalar| {
+        if scalar.name() == "ebx" { Some(Scalar::new("rbx", 64)) } else { None }
+    }).is_err());
+}
+
+
+#[test]
+fn listing() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.index()
+    };
+
+    let taken_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("b", 32), expr_scalar("a", 32));
+        block.index()
+    };
+
+    let tail_index = control_flow_graph.new_block().unwrap().index();
+
+    let condition = Expression::cmpeq(expr_scalar("a", 32), expr_const(1, 32)).unwrap();
+    control_flow_graph.conditional_edge(head_index, taken_index, condition.clone()).unwrap();
+    control_flow_graph.unconditional_edge(head_index, tail_index).unwrap();
+    control_flow_graph.unconditional_edge(taken_index, tail_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let mut function = Function::new(0x1000, control_flow_graph);
+    function.set_name(Some("check".to_string()));
+    let listing = function.listing();
+
+    assert!(listing.starts_with("function check @ 0x1000\n"));
+    for block in function.blocks() {
+        assert!(listing.contains(&format!("[ Block: 0x{:X} ]", block.index())));
+        for instruction in block.instructions() {
+            assert!(listing.contains(&instruction.to_string()));
+        }
+    }
+    assert!(listing.contains(&format!("-> 0x{:X} ? ({})", taken_index, condition)));
+    assert!(listing.contains(&format!("-> 0x{:X}\n", tail_index)));
+}
+
+
+#[test]
+fn structurally_equal() {
+    use il::control_flow_graph::test_diamond;
+
+    let function = Function::new(0, test_diamond(&[0, 1, 2, 3], 0));
+    let renumbered = Function::new(0, test_diamond(&[2, 3, 0, 1], 0));
+    assert!(function != renumbered);
+    assert!(function.structurally_equal(&renumbered));
+    assert!(renumbered.structurally_equal(&function));
+
+    let mut changed = renumbered.clone();
+    changed.control_flow_graph_mut().block_mut(0).unwrap().nop();
+    assert!(!function.structurally_equal(&changed));
+
+    // Swapping the branch conditions changes which block each one reaches
+    let mut swapped = function.clone();
+    for edge in swapped.control_flow_graph_mut().edges_mut() {
+        if edge.head() == 0 {
+            let condition = if edge.tail() == 1 {
+                Expression::cmpeq(expr_scalar("c", 1), expr_const(0, 1)).unwrap()
+            }
+            else {
+                expr_scalar("c", 1)
+            };
+            *edge.condition_mut() = Some(condition);
+        }
+    }
+    assert!(!function.structurally_equal(&swapped));
+}
+
+
+#[test]
+fn tail_calls() {
+    use analysis::calling_convention::CallingConventionType;
+
+    let calling_convention = CallingConvention::new(CallingConventionType::MipsSystemV);
+    let sp = scalar("$sp", 32);
+
+    // Allocate a frame, then free it and branch to callee, or to an address
+    // which is not a function, or to callee without freeing the frame
+    let mut control_flow_graph = ControlFlowGraph::new();
+
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), Expression::sub(sp.clone().into(), expr_const(16, 32)).unwrap());
+        block.index()
+    };
+
+    let tail_call_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), Expression::add(sp.clone().into(), expr_const(16, 32)).unwrap());
+        block.branch(expr_const(0x2000, 32));
+        block.index()
+    };
+
+    let unknown_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(sp.clone(), Expression::add(sp.clone().into(), expr_const(16, 32)).unwrap());
+        block.branch(expr_const(0x3000, 32));
+        block.index()
+    };
+
+    let framed_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(expr_const(0x2000, 32));
+        block.index()
+    };
+
+    control_flow_graph.unconditional_edge(head_index, tail_call_index).unwrap();
+    control_flow_graph.unconditional_edge(head_index, unknown_index).unwrap();
+    control_flow_graph.unconditional_edge(head_index, framed_index).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let mut callee = ControlFlowGraph::new();
+    let callee_index = callee.new_block().unwrap().index();
+    callee.set_entry(callee_index).unwrap();
+
+    let mut program = Program::new();
+    program.add_function(Function::new(0x1000, control_flow_graph));
+    program.add_function(Function::new(0x2000, callee));
+
+    let function = program.function_by_address(0x1000).unwrap();
+    let tail_calls = function.tail_calls(&program, &calling_convention).unwrap();
+
+    let block = function.block(tail_call_index).unwrap();
+    let location = RefProgramLocation::new(
+        function,
+        RefFunctionLocation::Instruction(block, block.instruction(1).unwrap())
+    );
+    assert_eq!(tail_calls, vec![location.into()]);
+}
+
+
+#[test]
+fn dead_assignments() {
+    /*
+    a = 1
+    a = b
+    c = [a]
+    [0] = c
+    */
+    let mut control_flow_graph = ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.assign(scalar("a", 32), expr_scalar("b", 32));
+        block.load(scalar("c", 32), expr_scalar("a", 32));
+        block.store(expr_const(0, 32), expr_scalar("c", 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+    let block = function.block(0).unwrap();
+    let overwritten: ProgramLocation = RefProgramLocation::new(&function,
+        RefFunctionLocation::Instruction(block, block.instruction(0).unwrap())).into();
+    assert_eq!(function.dead_assignments(), vec![overwritten]);
+
+    /*
+    i = 0
+    i = i + 1
+    [0] = i
+    */
+    let mut control_flow_graph = ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("i", 32), expr_const(0, 32));
+        block.assign(scalar("i", 32),
+                     Expression::add(expr_scalar("i", 32), expr_const(1, 32)).unwrap());
+        block.store(expr_const(0, 32), expr_scalar("i", 32));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+    assert!(function.dead_assignments().is_empty());
+}
+
+
+#[test]
+fn block_at_address() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    for address in &[0x1000, 0x1010] {
+        let block = control_flow_graph.new_block().unwrap();
+        block.nop();
+        block.assign_at(scalar("a", 32), expr_const(1, 32), Some(*address));
+        block.assign_at(scalar("a", 32), expr_const(2, 32), Some(*address + 8));
+    }
+    control_flow_graph.new_block().unwrap().nop();
+
+    let function = Function::new(0x1000, control_flow_graph);
+    assert_eq!(function.block_at_address(0x1000).unwrap().index(), 0);
+    assert_eq!(function.block_at_address(0x1004).unwrap().index(), 0);
+    assert_eq!(function.block_at_address(0x1018).unwrap().index(), 1);
+    assert!(function.block_at_address(0x100C).is_none());
+    assert!(function.block_at_address(0x2000).is_none());
+}
+
+
+#[test]
+fn map_expressions() {
+    let one_plus_two = || Expression::add(expr_const(1, 32), expr_const(2, 32)).unwrap();
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), one_plus_two());
+        block.store(one_plus_two(), expr_scalar("a", 32));
+        block.load(scalar("b", 32), one_plus_two());
+        block.index()
+    };
+    let tail_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.branch(one_plus_two());
+        block.index()
+    };
+    let condition = Expression::cmpeq(one_plus_two(), expr_const(3, 32)).unwrap();
+    control_flow_graph.conditional_edge(head_index, tail_index, condition).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let mut function = Function::new(0, control_flow_graph);
+    function.map_expressions(|expression|
+        Ok(expression.as_constant()
+                     .map(Expression::constant)
+                     .unwrap_or(expression.clone()))).unwrap();
+
+    let operations = function.instructions()
+                             .into_iter()
+                             .map(|(_, instruction)| instruction.operation().to_string())
+                             .collect::<Vec<String>>();
+    assert_eq!(operations, vec!["a:32 = 0x3:32",
+                                "[0x3:32] = a:32",
+                                "b:32 = [0x3:32]",
+                                "branch 0x3:32"]);
+    assert_eq!(function.edge(head_index, tail_index).unwrap().condition(),
+               &Some(expr_const(1, 1)));
+
+    assert!(function.map_expressions(|expression| Expression::zext(64, expression.clone())).is_err());
+}
+
+
+#[test]
+fn locations_of_kind() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    for _ in 0..2 {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        block.branch(expr_scalar("a", 32));
+    }
+    let function = Function::new(0, control_flow_graph);
+
+    let branches = function.locations_of_kind(OperationKind::Branch);
+    assert_eq!(branches, vec![ProgramLocation::new(None, FunctionLocation::Instruction(0, 1)),
+                              ProgramLocation::new(None, FunctionLocation::Instruction(1, 1))]);
+    assert!(function.locations_of_kind(OperationKind::Load).is_empty());
+}
+
+
+#[test]
+fn max_scalar_bits() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 8), expr_const(1, 8));
+        block.store(expr_scalar("sp", 32), expr_scalar("a", 8));
+        block.index()
+    };
+    let tail_index = control_flow_graph.new_block().unwrap().index();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let mut function = Function::new(0, control_flow_graph.clone());
+    assert_eq!(function.max_scalar_bits(), 32);
+
+    function.control_flow_graph_mut()
+            .block_mut(tail_index)
+            .unwrap()
+            .load(scalar("b", 64), expr_scalar("sp", 32));
+    assert_eq!(function.max_scalar_bits(), 64);
+
+    let condition = Expression::cmpeq(expr_scalar("c", 64), expr_const(0, 64)).unwrap();
+    control_flow_graph.conditional_edge(head_index, tail_index, condition).unwrap();
+    assert_eq!(Function::new(0, control_flow_graph).max_scalar_bits(), 64);
+
+    assert_eq!(Function::new(0, ControlFlowGraph::new()).max_scalar_bits(), 0);
+}
+
+
+#[test]
+fn fingerprint() {
+    use il::control_flow_graph::test_diamond;
+
+    let function = Function::new(0x1000, test_diamond(&[0, 1, 2, 3], 0x1000));
+    let renumbered = Function::new(0x2000, test_diamond(&[2, 3, 0, 1], 0x2000));
+    assert!(function.structurally_equal(&renumbered));
+    assert_eq!(function.fingerprint(), renumbered.fingerprint());
+
+    let mut changed = renumbered.clone();
+    *changed.control_flow_graph_mut()
+            .block_mut(0)
+            .unwrap()
+            .instruction_mut(0)
+            .unwrap()
+            .operation_mut() = Operation::assign(scalar("a", 32), expr_const(7, 32));
+    assert!(!function.structurally_equal(&changed));
+    assert!(function.fingerprint() != changed.fingerprint());
+}
+
+
+#[test]
+fn trivial_operations() {
+    let mut control_flow_graph = ControlFlowGraph::new();
+    let head_index = {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("x", 32), expr_scalar("x", 32));
+        block.assign(scalar("x", 32), expr_scalar("y", 32));
+        block.assign(scalar("x", 64), expr_scalar("x", 64));
+        block.index()
+    };
+    let taken_index = control_flow_graph.new_block().unwrap().index();
+    let not_taken_index = control_flow_graph.new_block().unwrap().index();
+    control_flow_graph.conditional_edge(head_index, taken_index, expr_const(1, 1)).unwrap();
+    let never = Expression::cmpeq(expr_const(1, 32), expr_const(2, 32)).unwrap();
+    control_flow_graph.conditional_edge(head_index, not_taken_index, never).unwrap();
+    control_flow_graph.conditional_edge(taken_index, not_taken_index, expr_scalar("c", 1)).unwrap();
+    control_flow_graph.set_entry(head_index).unwrap();
+
+    let function = Function::new(0, control_flow_graph);
+    let location = |location| ProgramLocation::new(None, location);
+    assert_eq!(function.trivial_operations(), vec![
+        (location(FunctionLocation::Instruction(head_index, 0)), TrivialKind::SelfAssignment),
+        (location(FunctionLocation::Instruction(head_index, 2)), TrivialKind::SelfAssignment),
+        (location(FunctionLocation::Edge(head_index, taken_index)), TrivialKind::AlwaysTaken),
+        (location(FunctionLocation::Edge(head_index, not_taken_index)), TrivialKind::NeverTaken)
+    ]);
+}
@@ -84,6 +84,16 @@ impl Instruction {
     }
 
 
+    /// Create a new `Call` instruction.
+    ///
+    /// # Warning
+    /// You almost never want to call this function. You should use the
+    /// `call` method on `il::Block` instead.
+    pub fn call(index: u64, target: Expression, result: Option<Scalar>) -> Instruction {
+        Instruction::new(index, Operation::call(target, result))
+    }
+
+
     /// Create a new `Raise` instruction.
     ///
     /// # Warning
@@ -94,6 +104,28 @@ impl Instruction {
     }
 
 
+    /// Create a new `Intrinsic` instruction.
+    ///
+    /// # Warning
+    /// You almost never want to call this function. You should use the
+    /// `intrinsic` method on `il::Block` instead.
+    pub fn intrinsic<S>(index: u64, name: S, arguments: Vec<Expression>, results: Vec<Scalar>)
+        -> Instruction where S: Into<String> {
+
+        Instruction::new(index, Operation::intrinsic(name, arguments, results))
+    }
+
+
+    /// Create a new `Nop` instruction.
+    ///
+    /// # Warning
+    /// You almost never want to call this function. You should use the
+    /// `nop` method on `il::Block` instead.
+    pub fn nop(index: u64) -> Instruction {
+        Instruction::new(index, Operation::nop())
+    }
+
+
     /// Returns `true` if the `Operation` for this `Instruction` is `Operation::Assign`
     pub fn is_assign(&self) -> bool {
         if let Operation::Assign{..} = self.operation {
@@ -134,6 +166,16 @@ impl Instruction {
         }
     }
 
+    /// Returns `true` if the `Operation` for this `Instruction` is `Operation::Call`
+    pub fn is_call(&self) -> bool {
+        if let Operation::Call{..} = self.operation {
+            true
+        }
+        else {
+            false
+        }
+    }
+
     /// Returns `true` if the `Operation` for this `Instruction` is `Operation::Raise`
     pub fn is_raise(&self) -> bool {
         if let Operation::Raise{..} = self.operation {
@@ -144,6 +186,26 @@ impl Instruction {
         }
     }
 
+    /// Returns `true` if the `Operation` for this `Instruction` is `Operation::Intrinsic`
+    pub fn is_intrinsic(&self) -> bool {
+        if let Operation::Intrinsic{..} = self.operation {
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    /// Returns `true` if the `Operation` for this `Instruction` is `Operation::Nop`
+    pub fn is_nop(&self) -> bool {
+        if let Operation::Nop = self.operation {
+            true
+        }
+        else {
+            false
+        }
+    }
+
     /// Get the `Operation` for this `Instruction`
     pub fn operation(&self) -> &Operation {
         &self.operation
@@ -154,6 +216,18 @@ impl Instruction {
         &mut self.operation
     }
 
+    /// Replace the `Operation` for this `Instruction`, leaving its index,
+    /// comment and address unchanged.
+    pub fn replace_operation(&mut self, operation: Operation) {
+        self.operation = operation;
+    }
+
+    /// Consume this `Instruction`, returning it with the given comment.
+    pub fn with_comment<S>(mut self, comment: S) -> Instruction where S: Into<String> {
+        self.comment = Some(comment.into());
+        self
+    }
+
     /// Get the index for this `Instruction`.
     ///
     /// An `Instruction` index is assigned by its parent `Block` and uniquely identifies the
@@ -198,6 +272,16 @@ impl Instruction {
         }
     }
 
+    /// Get each `Scalar` written by this `Instruction`.
+    pub fn scalars_written(&self) -> Vec<&Scalar> {
+        self.operation.scalars_written()
+    }
+
+    /// Get a mutable reference to each `Scalar` written by this `Instruction`.
+    pub fn scalars_written_mut(&mut self) -> Vec<&mut Scalar> {
+        self.operation.scalars_written_mut()
+    }
+
     /// Get the `Scalar` which will be written by this `Instruction`.
     pub fn scalar_written(&self) -> Option<&Scalar> {
         self.operation.scalar_written()
@@ -238,4 +322,20 @@ impl fmt::Display for Instruction {
             write!(f, "{}", prefix)
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn replace_operation() {
+    let mut instruction = Instruction::assign(3, scalar("a", 32), expr_scalar("b", 32));
+    instruction.set_address(Some(0x1000));
+    instruction.set_comment(Some("a = b".to_string()));
+
+    instruction.replace_operation(Operation::load(scalar("c", 32), expr_scalar("d", 32)));
+
+    assert_eq!(instruction.index(), 3);
+    assert_eq!(instruction.address(), Some(0x1000));
+    assert_eq!(instruction.comment(), &Some("a = b".to_string()));
+    assert!(instruction.is_load());
+    assert_eq!(instruction.scalar_written(), Some(&scalar("c", 32)));
+    assert_eq!(instruction.scalars_read(), vec![&scalar("d", 32)]);
+}
@@ -409,6 +409,11 @@ impl ProgramLocation {
         Some(RefProgramLocation::new(function, function_location))
     }
 
+    /// Get the index of the `Function` for this `ProgramLocation`
+    pub fn function_index(&self) -> Option<u64> {
+        self.function_index
+    }
+
     /// Get the `FunctionLocation` for this `ProgramLocation`
     pub fn function_location(&self) -> &FunctionLocation {
         &self.function_location
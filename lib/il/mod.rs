@@ -5,7 +5,7 @@
 //! Falcon IL is a simple, expression-based, well-defined, semantically-accurate
 //! intermediate language for the analysis of Binary Programs.
 //!
-//! * **Simple** - Falcon IL has 21 expression types (including terminals), and 5
+//! * **Simple** - Falcon IL has 25 expression types (including terminals), and 8
 //! operation types, minimizing the work required to implement analyses.
 //! * **Expression-based** - Falcon IL operates over expression, as opposed to a
 //! [three-address form](https://en.wikipedia.org/wiki/Three-address_code) like
@@ -20,12 +20,11 @@
 //!
 //! ## Limitations
 //!
-//! * Falcon IL does not support operations over values > 64 bits in width.
 //! * Falcon IL does not support floating point operations.
 //!
 //! While Falcon IL allows for analyses that find real bugs, due to these
 //! limitations it cannot completely analyze programs which require floating
-//! point or wide-register instructions.
+//! point instructions.
 //! 
 //! ## Position and Semantics
 //! 
@@ -72,10 +71,11 @@
 //! `Scalar` and `Constant`
 //!
 //! * Terminals: `Scalar`, `Constant`.
-//! * Arithmetic: `Add`, `Sub`, `Mul`, `Divu`, `Modu`, `Divs`, `Mods`, `And`,
-//! `Or`, `Xor`, `Shl`, `Shr`.
+//! * Arithmetic: `Add`, `Sub`, `Neg`, `Mul`, `Divu`, `Modu`, `Divs`, `Mods`,
+//! `And`, `Or`, `Xor`, `Shl`, `Shr`.
 //! * Comparison: `Cmpeq`, `Cmpneq`, `Cmplts`, `Cmpltu`.
 //! * Extension: `Zext`, `Sext`, `Trun`.
+//! * Bit counting: `Popcnt`, `Clz`, `Ctz`.
 //!
 //! Comparison expressions evaluate to a 1-bit expression with the value `1`
 //! representing `True`, and the value `0` representing `False`.
@@ -111,6 +111,7 @@
 //!
 //! `Load` and `Store` are obviously dependent on the endianness of the target
 //! architecture. Endianness is specified in the memory model, not in the IL.
+//! See `memory::paged::Memory`, which is created with an `Endian`.
 //!
 //! ## `Instruction`
 //! 
@@ -180,6 +181,10 @@ use error::*;
 use graph;
 
 mod block;
+mod cached_expression;
+mod call_graph;
+mod cfg_cursor;
+mod cfg_diff;
 mod constant;
 mod control_flow_graph;
 mod edge;
@@ -187,11 +192,19 @@ mod expression;
 mod function;
 mod instruction;
 mod location;
+mod natural_loop;
 mod operation;
+pub mod pattern;
 mod scalar;
 mod program;
+mod stable;
+mod symbol_table;
 
 pub use self::block::*;
+pub use self::cached_expression::*;
+pub use self::call_graph::*;
+pub use self::cfg_cursor::*;
+pub use self::cfg_diff::*;
 pub use self::constant::*;
 pub use self::control_flow_graph::*;
 pub use self::edge::*;
@@ -199,9 +212,11 @@ pub use self::expression::*;
 pub use self::function::*;
 pub use self::instruction::*;
 pub use self::location::*;
+pub use self::natural_loop::*;
 pub use self::operation::*;
 pub use self::scalar::*;
 pub use self::program::*;
+pub use self::symbol_table::*;
 
 /// A convenience function to create a new constant.
 ///
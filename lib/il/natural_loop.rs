@@ -0,0 +1,47 @@
+//! A `Loop` is a natural loop in a `ControlFlowGraph`.
+//!
+//! To find the natural loops of a `ControlFlowGraph`, call
+//! `ControlFlowGraph::natural_loops`.
+
+use il::*;
+use std::collections::BTreeSet;
+
+
+/// A natural loop, identified by its header `Block`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Loop {
+    header: u64,
+    body: BTreeSet<u64>,
+    back_edges: Vec<Edge>
+}
+
+
+impl Loop {
+    pub(crate) fn new(header: u64, body: BTreeSet<u64>, back_edges: Vec<Edge>) -> Loop {
+        Loop {
+            header: header,
+            body: body,
+            back_edges: back_edges
+        }
+    }
+
+    /// Get the index of the header `Block` of this `Loop`.
+    pub fn header(&self) -> u64 {
+        self.header
+    }
+
+    /// Get the indices of every `Block` in this `Loop`, including the header.
+    pub fn body(&self) -> &BTreeSet<u64> {
+        &self.body
+    }
+
+    /// Get the back edges of this `Loop`, the edges whose tail is the header.
+    pub fn back_edges(&self) -> &Vec<Edge> {
+        &self.back_edges
+    }
+
+    /// Returns true if the `Block` with the given index is in this `Loop`.
+    pub fn contains(&self, index: u64) -> bool {
+        self.body.contains(&index)
+    }
+}
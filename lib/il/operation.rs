@@ -26,10 +26,45 @@ pub enum Operation {
     Branch {
         target: Expression
     },
+    /// Call the procedure given by target, optionally placing the value it
+    /// returns in the variable result.
+    ///
+    /// The executor continues at target, as for `Branch`, and never writes
+    /// result.
+    Call {
+        target: Expression,
+        result: Option<Scalar>
+    },
     /// Raise operation for handling things such as system calls.
     Raise {
         expr: Expression,
-    }
+    },
+    /// An operation whose semantics are given by name, such as `cpuid`,
+    /// which reads the arguments and writes the variables in results.
+    ///
+    /// The executor does not know the semantics of any intrinsic, and returns
+    /// an error when it reaches one.
+    Intrinsic {
+        name: String,
+        arguments: Vec<Expression>,
+        results: Vec<Scalar>
+    },
+    /// An operation which does nothing.
+    Nop
+}
+
+
+/// The kind of an `Operation`, without its operands.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum OperationKind {
+    Assign,
+    Store,
+    Load,
+    Branch,
+    Call,
+    Raise,
+    Intrinsic,
+    Nop
 }
 
 
@@ -57,11 +92,100 @@ impl Operation {
         Operation::Branch { target: target }
     }
 
+    /// Create a new `Operation::Call`.
+    pub fn call(target: Expression, result: Option<Scalar>) -> Operation {
+        Operation::Call { target: target, result: result }
+    }
+
     /// Create a new `Operation::Raise`.
     pub fn raise(expr: Expression) -> Operation {
         Operation::Raise { expr: expr }
     }
 
+    /// Create a new `Operation::Intrinsic`.
+    pub fn intrinsic<S>(name: S, arguments: Vec<Expression>, results: Vec<Scalar>)
+        -> Operation where S: Into<String> {
+
+        Operation::Intrinsic {
+            name: name.into(),
+            arguments: arguments,
+            results: results
+        }
+    }
+
+    /// Create a new `Operation::Nop`.
+    pub fn nop() -> Operation {
+        Operation::Nop
+    }
+
+    /// Get the `OperationKind` of this `Operation`.
+    pub fn kind(&self) -> OperationKind {
+        match *self {
+            Operation::Assign { .. } => OperationKind::Assign,
+            Operation::Store { .. } => OperationKind::Store,
+            Operation::Load { .. } => OperationKind::Load,
+            Operation::Branch { .. } => OperationKind::Branch,
+            Operation::Call { .. } => OperationKind::Call,
+            Operation::Raise { .. } => OperationKind::Raise,
+            Operation::Intrinsic { .. } => OperationKind::Intrinsic,
+            Operation::Nop => OperationKind::Nop
+        }
+    }
+
+    /// Ensures every `Expression` in this `Operation` is valid, and agrees in
+    /// bitness with the `Scalar` it is assigned to.
+    /// # Error
+    /// An `Expression` is invalid, an assignment's bitness differs from its
+    /// destination, a memory access has a width which is not a whole number of
+    /// bytes, or an address or target has a bitness of 0.
+    pub fn validate(&self) -> Result<()> {
+        fn ensure_address(kind: &str, address: &Expression) -> Result<()> {
+            address.validate()?;
+            if address.bits() == 0 {
+                bail!("{} {} has a bitness of 0", kind, address);
+            }
+            Ok(())
+        }
+
+        fn ensure_bytes(operation: &Operation, bits: usize) -> Result<()> {
+            if bits == 0 || bits % 8 != 0 {
+                bail!("{} accesses {} bits, which is not a whole number of bytes",
+                      operation, bits);
+            }
+            Ok(())
+        }
+
+        match *self {
+            Operation::Assign { ref dst, ref src } => {
+                src.validate()?;
+                if dst.bits() != src.bits() {
+                    return Err(ErrorKind::Sort("assign".to_string(),
+                                               dst.bits(),
+                                               src.bits()).into());
+                }
+            },
+            Operation::Store { ref index, ref src } => {
+                ensure_address("Address", index)?;
+                src.validate()?;
+                ensure_bytes(self, src.bits())?;
+            },
+            Operation::Load { ref dst, ref index } => {
+                ensure_address("Address", index)?;
+                ensure_bytes(self, dst.bits())?;
+            },
+            Operation::Branch { ref target } |
+            Operation::Call { ref target, .. } => ensure_address("Target", target)?,
+            Operation::Raise { ref expr } => expr.validate()?,
+            Operation::Intrinsic { ref arguments, .. } => {
+                for argument in arguments {
+                    argument.validate()?;
+                }
+            },
+            Operation::Nop => {}
+        }
+        Ok(())
+    }
+
     /// Get each `Scalar` read by this `Operation`.
     pub fn scalars_read(&self) -> Vec<&Scalar> {
         let mut read: Vec<&Scalar> = Vec::new();
@@ -76,12 +200,19 @@ impl Operation {
             Operation::Load { ref index, .. } => {
                 read.append(&mut index.scalars());
             },
-            Operation::Branch { ref target } => {
+            Operation::Branch { ref target } |
+            Operation::Call { ref target, .. } => {
                 read.append(&mut target.scalars());
             },
             Operation::Raise { ref expr } => {
                 read.append(&mut expr.scalars());
-            }
+            },
+            Operation::Intrinsic { ref arguments, .. } => {
+                for argument in arguments {
+                    read.append(&mut argument.scalars());
+                }
+            },
+            Operation::Nop => {}
         }
         read
     }
@@ -100,38 +231,134 @@ impl Operation {
             Operation::Load { ref mut index, .. } => {
                 read.append(&mut index.scalars_mut());
             },
-            Operation::Branch { ref mut target } => {
+            Operation::Branch { ref mut target } |
+            Operation::Call { ref mut target, .. } => {
                 read.append(&mut target.scalars_mut());
             },
             Operation::Raise { ref mut expr } => {
                 read.append(&mut expr.scalars_mut());
-            }
+            },
+            Operation::Intrinsic { ref mut arguments, .. } => {
+                for argument in arguments {
+                    read.append(&mut argument.scalars_mut());
+                }
+            },
+            Operation::Nop => {}
         }
 
         read
     }
 
-    /// Get a reference to the `Scalar` written by this `Operation`, or `None`
-    /// if no `Scalar` is written.
-    pub fn scalar_written(&self) -> Option<&Scalar> {
+    /// Get each `Expression` in this `Operation`.
+    pub fn expressions(&self) -> Vec<&Expression> {
         match *self {
-            Operation::Assign { ref dst, .. } |
-            Operation::Load   { ref dst, .. } => Some(dst),
-            Operation::Store  { .. } |
-            Operation::Branch { .. } |
-            Operation::Raise  { .. } => None
+            Operation::Assign { ref src, .. } => vec![src],
+            Operation::Store { ref index, ref src } => vec![index, src],
+            Operation::Load { ref index, .. } => vec![index],
+            Operation::Branch { ref target } |
+            Operation::Call { ref target, .. } => vec![target],
+            Operation::Raise { ref expr } => vec![expr],
+            Operation::Intrinsic { ref arguments, .. } => arguments.iter().collect(),
+            Operation::Nop => Vec::new()
         }
     }
 
-    /// Get a mutable reference to the `Scalar` written by this `Operation`, or `None`,
-    /// if no `Scalar` is written.
+    /// Get a mutable reference to each `Expression` in this `Operation`.
+    pub fn expressions_mut(&mut self) -> Vec<&mut Expression> {
+        match *self {
+            Operation::Assign { ref mut src, .. } => vec![src],
+            Operation::Store { ref mut index, ref mut src } => vec![index, src],
+            Operation::Load { ref mut index, .. } => vec![index],
+            Operation::Branch { ref mut target } |
+            Operation::Call { ref mut target, .. } => vec![target],
+            Operation::Raise { ref mut expr } => vec![expr],
+            Operation::Intrinsic { ref mut arguments, .. } => arguments.iter_mut().collect(),
+            Operation::Nop => Vec::new()
+        }
+    }
+
+    /// Get a reference to each `Scalar` written by this `Operation`.
+    pub fn scalars_written(&self) -> Vec<&Scalar> {
+        match *self {
+            Operation::Assign    { ref dst, .. } |
+            Operation::Load      { ref dst, .. } => vec![dst],
+            Operation::Call      { ref result, .. } => result.iter().collect(),
+            Operation::Intrinsic { ref results, .. } => results.iter().collect(),
+            Operation::Store     { .. } |
+            Operation::Branch    { .. } |
+            Operation::Raise     { .. } |
+            Operation::Nop => Vec::new()
+        }
+    }
+
+    /// Get a mutable reference to each `Scalar` written by this `Operation`.
+    pub fn scalars_written_mut(&mut self) -> Vec<&mut Scalar> {
+        match *self {
+            Operation::Assign    { ref mut dst, .. } |
+            Operation::Load      { ref mut dst, .. } => vec![dst],
+            Operation::Call      { ref mut result, .. } => result.iter_mut().collect(),
+            Operation::Intrinsic { ref mut results, .. } => results.iter_mut().collect(),
+            Operation::Store     { .. } |
+            Operation::Branch    { .. } |
+            Operation::Raise     { .. } |
+            Operation::Nop => Vec::new()
+        }
+    }
+
+    /// Get a reference to the first `Scalar` written by this `Operation`, or
+    /// `None` if no `Scalar` is written.
+    pub fn scalar_written(&self) -> Option<&Scalar> {
+        self.scalars_written().into_iter().next()
+    }
+
+    /// Get a mutable reference to the first `Scalar` written by this
+    /// `Operation`, or `None`, if no `Scalar` is written.
     pub fn scalar_written_mut(&mut self) -> Option<&mut Scalar> {
+        self.scalars_written_mut().into_iter().next()
+    }
+
+    /// Returns true if this `Operation` has an effect other than writing its
+    /// destination `Scalar`, and so can not be removed or reordered freely.
+    ///
+    /// `Store`, `Branch`, `Call` and `Raise` have side effects, and `Intrinsic`
+    /// is conservatively assumed to. A `Load` only writes its destination, and
+    /// is not considered to have side effects, though it may fault or read
+    /// memory-mapped state. Use `Operation::is_pure` to exclude loads as well.
+    pub fn has_side_effects(&self) -> bool {
         match *self {
-            Operation::Assign { ref mut dst, .. } |
-            Operation::Load   { ref mut dst, .. } => Some(dst),
-            Operation::Store  { .. } |
+            Operation::Store { .. } |
             Operation::Branch { .. } |
-            Operation::Raise  { .. } => None
+            Operation::Call { .. } |
+            Operation::Raise { .. } |
+            Operation::Intrinsic { .. } => true,
+            Operation::Assign { .. } |
+            Operation::Load { .. } |
+            Operation::Nop => false
+        }
+    }
+
+    /// Returns true if this `Operation` neither has side effects nor reads
+    /// memory, so its result depends only on the `Scalar`s it reads.
+    pub fn is_pure(&self) -> bool {
+        match *self {
+            Operation::Load { .. } => false,
+            _ => !self.has_side_effects()
+        }
+    }
+
+    /// If this is a `Raise` of a system call, returns the system call number.
+    ///
+    /// By convention, a lifter raises a system call as a `Raise` whose
+    /// expression evaluates to the constant system call number, for example
+    /// `raise 0x3C:32`. A `Raise` of any other expression, such as the
+    /// `raise syscall:1` emitted by the MIPS translator, returns `None`, as
+    /// does a number which does not fit in 64 bits.
+    pub fn as_syscall(&self) -> Option<u64> {
+        match *self {
+            Operation::Raise { ref expr } =>
+                expr.as_constant()
+                    .and_then(|constant| constant.value_u64().ok()),
+            _ => None
         }
     }
 }
@@ -148,8 +375,117 @@ impl fmt::Display for Operation {
                 write!(f, "{} = [{}]", dst, index),
             Operation::Branch { ref target } =>
                 write!(f, "branch {}", target),
+            Operation::Call { ref target, ref result } => match *result {
+                Some(ref result) => write!(f, "{} = call {}", result, target),
+                None => write!(f, "call {}", target)
+            },
             Operation::Raise { ref expr } => 
-                write!(f, "raise {}", expr)
+                write!(f, "raise {}", expr),
+            Operation::Intrinsic { ref name, ref arguments, ref results } => {
+                let arguments = arguments.iter()
+                                         .map(|argument| format!("{}", argument))
+                                         .collect::<Vec<String>>();
+                let results = results.iter()
+                                     .map(|result| format!("{}", result))
+                                     .collect::<Vec<String>>();
+                write!(f, "{}({}) -> ({})", name, arguments.join(", "), results.join(", "))
+            },
+            Operation::Nop => write!(f, "nop")
         }
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn call() {
+    let operation = Operation::call(expr_scalar("t9", 32), Some(scalar("v0", 32)));
+    assert_eq!(operation.scalars_read(), vec![&scalar("t9", 32)]);
+    assert_eq!(operation.scalar_written(), Some(&scalar("v0", 32)));
+    assert_eq!(format!("{}", operation), "v0:32 = call t9:32");
+
+    let json = ::serde_json::to_string(&operation).unwrap();
+    assert_eq!(::serde_json::from_str::<Operation>(&json).unwrap(), operation);
+
+    let operation = Operation::call(expr_const(0x1000, 32), None);
+    assert!(operation.scalars_read().is_empty());
+    assert_eq!(operation.scalar_written(), None);
+    assert_eq!(format!("{}", operation), "call 0x1000:32");
+}
+
+
+#[test]
+fn intrinsic() {
+    let operation = Operation::intrinsic("cpuid",
+                                         vec![expr_scalar("eax", 32), expr_const(0, 32)],
+                                         vec![scalar("eax", 32), scalar("ebx", 32)]);
+    assert_eq!(operation.scalars_read(), vec![&scalar("eax", 32)]);
+    assert_eq!(operation.scalar_written(), Some(&scalar("eax", 32)));
+    assert_eq!(format!("{}", operation), "cpuid(eax:32, 0x0:32) -> (eax:32, ebx:32)");
+
+    let json = ::serde_json::to_string(&operation).unwrap();
+    assert_eq!(::serde_json::from_str::<Operation>(&json).unwrap(), operation);
+}
+
+
+#[test]
+fn scalars_written() {
+    let mut operation = Operation::intrinsic("divmod",
+                                             vec![expr_scalar("a", 32), expr_scalar("b", 32)],
+                                             vec![scalar("q", 32), scalar("r", 32)]);
+    assert_eq!(operation.scalars_written(), vec![&scalar("q", 32), &scalar("r", 32)]);
+    assert_eq!(operation.scalar_written(), Some(&scalar("q", 32)));
+
+    for scalar in operation.scalars_written_mut() {
+        *scalar = Scalar::new(format!("{}.1", scalar.name()), scalar.bits());
+    }
+    assert_eq!(operation.scalars_written(), vec![&scalar("q.1", 32), &scalar("r.1", 32)]);
+
+    let operation = Operation::store(expr_scalar("a", 32), expr_scalar("b", 32));
+    assert!(operation.scalars_written().is_empty());
+    assert_eq!(operation.scalar_written(), None);
+}
+
+
+#[test]
+fn nop() {
+    let operation = Operation::nop();
+    assert!(operation.scalars_read().is_empty());
+    assert!(operation.scalars_written().is_empty());
+    assert_eq!(format!("{}", operation), "nop");
+
+    let json = ::serde_json::to_string(&operation).unwrap();
+    assert_eq!(::serde_json::from_str::<Operation>(&json).unwrap(), operation);
+}
+
+
+#[test]
+fn side_effects() {
+    let a = || expr_scalar("a", 32);
+    let cases = vec![
+        (Operation::assign(scalar("b", 32), a()), false, true),
+        (Operation::load(scalar("b", 32), a()), false, false),
+        (Operation::nop(), false, true),
+        (Operation::store(a(), a()), true, false),
+        (Operation::branch(a()), true, false),
+        (Operation::call(a(), None), true, false),
+        (Operation::raise(a()), true, false),
+        (Operation::intrinsic("rdtsc", Vec::new(), vec![scalar("b", 32)]), true, false)
+    ];
+    for (operation, has_side_effects, is_pure) in cases {
+        assert_eq!(operation.has_side_effects(), has_side_effects, "{}", operation);
+        assert_eq!(operation.is_pure(), is_pure, "{}", operation);
+    }
+}
+
+
+#[test]
+fn as_syscall() {
+    assert_eq!(Operation::raise(expr_const(60, 32)).as_syscall(), Some(60));
+    assert_eq!(Operation::raise(Expression::add(expr_const(1, 32), expr_const(3, 32)).unwrap())
+                   .as_syscall(),
+               Some(4));
+    assert_eq!(Operation::raise(expr_scalar("syscall", 1)).as_syscall(), None);
+    assert_eq!(Operation::raise(Expression::add(expr_scalar("v0", 32), expr_const(1, 32)).unwrap())
+                   .as_syscall(),
+               None);
+    assert_eq!(Operation::nop().as_syscall(), None);
+}
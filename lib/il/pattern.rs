@@ -0,0 +1,224 @@
+//! Patterns over `Expression`, for writing rewrite rules.
+//!
+//! A pattern is an `Expression` in which scalars whose names begin with `?`
+//! are wildcards. The rule `(?x + 0) => ?x` for 32-bit values is written:
+//!
+//! ```
+//! # use falcon::il::*;
+//! # use falcon::il::pattern::*;
+//! let pattern = ExpressionPattern::new(
+//!     Expression::add(ExpressionPattern::wildcard("x", 32), expr_const(0, 32)).unwrap()
+//! );
+//! let template = ExpressionPattern::wildcard("x", 32);
+//!
+//! let expression = Expression::add(expr_scalar("a", 32), expr_const(0, 32)).unwrap();
+//! assert_eq!(rewrite(&pattern, &template, &expression).unwrap(), expr_scalar("a", 32));
+//! ```
+
+use il::*;
+use std::collections::BTreeMap;
+
+
+/// An `Expression` with named wildcards.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct ExpressionPattern {
+    pattern: Expression
+}
+
+
+impl ExpressionPattern {
+    /// Create an `ExpressionPattern`, where scalars in pattern whose names
+    /// begin with `?` are wildcards.
+    pub fn new(pattern: Expression) -> ExpressionPattern {
+        ExpressionPattern { pattern: pattern }
+    }
+
+    /// Create a wildcard named name, to use in a pattern or template.
+    ///
+    /// A wildcard matches an `Expression` of any bitness. The bitness given
+    /// here is only used when creating the pattern or template.
+    pub fn wildcard(name: &str, bits: usize) -> Expression {
+        expr_scalar(format!("?{}", name), bits)
+    }
+
+    /// Get the `Expression` of this `ExpressionPattern`.
+    pub fn pattern(&self) -> &Expression {
+        &self.pattern
+    }
+
+    /// Match this `ExpressionPattern` against the root of expression.
+    ///
+    /// Returns the `Expression` bound to each wildcard, by name without the
+    /// `?`, or `None` if expression does not match. A wildcard which appears
+    /// more than once must match equal expressions.
+    pub fn matches(&self, expression: &Expression) -> Option<BTreeMap<String, Expression>> {
+        let mut bindings = BTreeMap::new();
+        if match_expression(&self.pattern, expression, &mut bindings) {
+            Some(bindings)
+        }
+        else {
+            None
+        }
+    }
+}
+
+
+fn wildcard_name(expression: &Expression) -> Option<&str> {
+    match *expression {
+        Expression::Scalar(ref scalar) if scalar.name().starts_with('?') =>
+            Some(&scalar.name()[1..]),
+        _ => None
+    }
+}
+
+
+fn match_expression(
+    pattern: &Expression,
+    expression: &Expression,
+    bindings: &mut BTreeMap<String, Expression>
+) -> bool {
+    if let Some(name) = wildcard_name(pattern) {
+        if let Some(bound) = bindings.get(name) {
+            return bound == expression;
+        }
+        bindings.insert(name.to_string(), expression.clone());
+        return true;
+    }
+
+    match *pattern {
+        Expression::Scalar(_) |
+        Expression::Constant(_) => return pattern == expression,
+        Expression::Zext(bits, _) |
+        Expression::Sext(bits, _) |
+        Expression::Trun(bits, _) => if bits != expression.bits() {
+            return false;
+        },
+        _ => {}
+    }
+
+    pattern.operator() == expression.operator()
+    && pattern.children()
+              .into_iter()
+              .zip(expression.children())
+              .all(|(pattern, expression)| match_expression(pattern, expression, bindings))
+}
+
+
+/// Replace each wildcard in template with the `Expression` bound to it.
+///
+/// # Error
+/// A wildcard in template is not bound, or the result is not a valid
+/// `Expression`.
+pub fn instantiate(template: &Expression, bindings: &BTreeMap<String, Expression>)
+-> Result<Expression> {
+    fn substitute(expression: &mut Expression, bindings: &BTreeMap<String, Expression>)
+    -> Result<()> {
+        let replacement = match wildcard_name(expression) {
+            Some(name) => Some(bindings.get(name)
+                                       .cloned()
+                                       .ok_or(format!("Wildcard ?{} is not bound", name))?),
+            None => None
+        };
+        match replacement {
+            Some(replacement) => *expression = replacement,
+            None => for child in expression.children_mut() {
+                substitute(child, bindings)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut expression = template.clone();
+    substitute(&mut expression, bindings)?;
+    expression.validate()?;
+    Ok(expression)
+}
+
+
+/// Rewrite every sub-expression of expression which matches pattern with
+/// template.
+///
+/// Sub-expressions are rewritten from the leaves up, and each is rewritten at
+/// most once, so the result may match pattern again.
+///
+/// # Error
+/// template uses a wildcard which is not in pattern, or a rewrite creates an
+/// invalid `Expression`.
+pub fn rewrite(pattern: &ExpressionPattern, template: &Expression, expression: &Expression)
+-> Result<Expression> {
+    fn rewrite_in_place(
+        pattern: &ExpressionPattern,
+        template: &Expression,
+        expression: &mut Expression
+    ) -> Result<()> {
+        for child in expression.children_mut() {
+            rewrite_in_place(pattern, template, child)?;
+        }
+        if let Some(bindings) = pattern.matches(expression) {
+            *expression = instantiate(template, &bindings)?;
+        }
+        Ok(())
+    }
+
+    let mut expression = expression.clone();
+    rewrite_in_place(pattern, template, &mut expression)?;
+    Ok(expression)
+}
+
+
+#[test]
+fn matches() {
+    // (?x * ?y) + ?x
+    let pattern = ExpressionPattern::new(Expression::add(
+        Expression::mul(ExpressionPattern::wildcard("x", 32),
+                        ExpressionPattern::wildcard("y", 32)).unwrap(),
+        ExpressionPattern::wildcard("x", 32)
+    ).unwrap());
+
+    let a_plus_one = Expression::add(expr_scalar("a", 32), expr_const(1, 32)).unwrap();
+    let expression = Expression::add(
+        Expression::mul(a_plus_one.clone(), expr_scalar("b", 32)).unwrap(),
+        a_plus_one.clone()
+    ).unwrap();
+
+    let bindings = pattern.matches(&expression).unwrap();
+    assert_eq!(bindings.len(), 2);
+    assert_eq!(bindings["x"], a_plus_one);
+    assert_eq!(bindings["y"], expr_scalar("b", 32));
+
+    // ?x must match the same expression both times
+    let expression = Expression::add(
+        Expression::mul(a_plus_one, expr_scalar("b", 32)).unwrap(),
+        expr_scalar("a", 32)
+    ).unwrap();
+    assert!(pattern.matches(&expression).is_none());
+
+    // Wildcards match any bitness, but casts must match theirs
+    let pattern = ExpressionPattern::new(Expression::zext(64, ExpressionPattern::wildcard("x", 8)).unwrap());
+    assert!(pattern.matches(&Expression::zext(64, expr_scalar("a", 32)).unwrap()).is_some());
+    assert!(pattern.matches(&Expression::zext(32, expr_scalar("a", 8)).unwrap()).is_none());
+}
+
+
+#[test]
+fn rewrite_identity() {
+    let pattern = ExpressionPattern::new(
+        Expression::add(ExpressionPattern::wildcard("x", 32), expr_const(0, 32)).unwrap()
+    );
+    let template = ExpressionPattern::wildcard("x", 32);
+
+    // ((a + 0) * (b + 0)) + 0
+    let expression = Expression::add(
+        Expression::mul(
+            Expression::add(expr_scalar("a", 32), expr_const(0, 32)).unwrap(),
+            Expression::add(expr_scalar("b", 32), expr_const(0, 32)).unwrap()
+        ).unwrap(),
+        expr_const(0, 32)
+    ).unwrap();
+
+    assert_eq!(rewrite(&pattern, &template, &expression).unwrap(),
+               Expression::mul(expr_scalar("a", 32), expr_scalar("b", 32)).unwrap());
+
+    let unbound = ExpressionPattern::wildcard("y", 32);
+    assert!(rewrite(&pattern, &unbound, &expression).is_err());
+}
@@ -1,17 +1,23 @@
 //! A `Program` holds multiple `Function`.
 
+use analysis::calling_convention::CallingConvention;
+use analysis::summaries::{self, FunctionSummary};
 use il::*;
 use RC;
+use serde_json;
 use std::collections::BTreeMap;
 use std::fmt;
 
 /// A representation of a program by `il::Function`
-#[derive(Clone, Debug, Deserialize, Hash, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct Program {
     // Mapping of function indices (not addresses) to `Function`.
     functions: BTreeMap<u64, RC<Function>>,
     // The next index to assign to a function when added to the program.
-    next_index: u64
+    next_index: u64,
+    // Symbols attached to the program, independent of its functions.
+    #[serde(default)]
+    symbol_table: SymbolTable
 }
 
 
@@ -20,7 +26,8 @@ impl Program {
     pub fn new() -> Program {
         Program {
             functions: BTreeMap::new(),
-            next_index: 0
+            next_index: 0,
+            symbol_table: SymbolTable::new()
         }
     }
 
@@ -35,6 +42,48 @@ impl Program {
         None
     }
 
+    /// Search for a `Function` by its address, and return a mutable reference
+    /// to it, or `None` if not found.
+    ///
+    /// If the `Function` is shared, for example through `function_rc`, it is
+    /// cloned first.
+    pub fn function_by_address_mut(&mut self, address: u64) -> Option<&mut Function> {
+        for function in self.functions.values_mut() {
+            if function.address() == address {
+                return Some(RC::make_mut(function));
+            }
+        }
+        None
+    }
+
+    /// Search for a `Function` by the name of a symbol at its address.
+    /// Returns the `Function` if found, or `None` if not found.
+    pub fn function_by_name(&self, name: &str) -> Option<&Function> {
+        self.symbol_table
+            .resolve_name(name)
+            .and_then(|address| self.function_by_address(address))
+    }
+
+    /// Get the `SymbolTable` for this `Program`.
+    pub fn symbol_table(&self) -> &SymbolTable {
+        &self.symbol_table
+    }
+
+    /// Add a symbol with the given name at the given address.
+    pub fn add_symbol<S>(&mut self, name: S, address: u64) where S: Into<String> {
+        self.symbol_table.add_symbol(name, address)
+    }
+
+    /// Get the address of the symbol with the given name.
+    pub fn resolve_name(&self, name: &str) -> Option<u64> {
+        self.symbol_table.resolve_name(name)
+    }
+
+    /// Get the name of the symbol at the given address.
+    pub fn resolve_address(&self, address: u64) -> Option<&str> {
+        self.symbol_table.resolve_address(address)
+    }
+
     /// Get all `Function` for this `Program`.
     pub fn functions(&self) -> Vec<&Function> {
         let mut v = Vec::new();
@@ -73,6 +122,128 @@ impl Program {
     }
 
 
+    /// Mark the `Function` with the given index as never returning to its
+    /// caller.
+    ///
+    /// If the `Function` is shared, for example through `function_rc`, it is
+    /// cloned first.
+    ///
+    /// # Error
+    /// No `Function` has the given index.
+    pub fn mark_no_return(&mut self, function_index: u64) -> Result<()> {
+        let function = self.functions
+                           .get_mut(&function_index)
+                           .ok_or(format!("Function {} not found", function_index))?;
+        RC::make_mut(function).set_no_return(true);
+        Ok(())
+    }
+
+
+    /// Replace every `Function` in this `Program` with the result of `f`
+    /// applied to it, applying `f` to the functions in parallel.
+    ///
+    /// Each replacement keeps the index of the `Function` it replaces.
+    ///
+    /// # Error
+    /// `f` returned an error. The error for the lowest function index is
+    /// returned, and no `Function` is replaced.
+    #[cfg(feature = "rayon")]
+    pub fn par_map_functions<F>(&mut self, f: F) -> Result<()>
+        where F: Fn(&Function) -> Result<Function> + Sync {
+
+        use rayon::prelude::*;
+
+        let mapped = {
+            let functions = self.functions
+                                .iter()
+                                .map(|(index, function)| (*index, &**function))
+                                .collect::<Vec<(u64, &Function)>>();
+            functions.into_par_iter()
+                     .map(|(index, function)| (index, f(function)))
+                     .collect::<Vec<(u64, Result<Function>)>>()
+        };
+
+        let mut functions = BTreeMap::new();
+        for (index, function) in mapped {
+            let mut function = function?;
+            function.set_index(Some(index));
+            functions.insert(index, RC::new(function));
+        }
+        self.functions = functions;
+
+        Ok(())
+    }
+
+
+    /// Get the `Instruction` at the given `ProgramLocation`, or `None` if the
+    /// location is not an `Instruction` in this `Program`.
+    pub fn instruction_at(&self, location: &ProgramLocation) -> Option<&Instruction> {
+        location.function_index()
+                .and_then(|index| self.function(index))
+                .and_then(|function| function.instruction_at(location.function_location()))
+    }
+
+
+    /// Create the `CallGraph` for this `Program`.
+    ///
+    /// Every `Operation::Call` and `Operation::Branch` is treated as a call. A
+    /// call to a constant address where a `Function` was found is a call to
+    /// that `Function`. Any other call, including returns lifted as branches,
+    /// is a call to `CallGraph::unknown_index()`.
+    pub fn call_graph(&self) -> Result<CallGraph> {
+        let mut call_graph = CallGraph::new();
+
+        for index in self.functions.keys() {
+            call_graph.add_function(*index)?;
+        }
+
+        for (index, function) in &self.functions {
+            for block in function.blocks() {
+                for instruction in block.instructions() {
+                    let target = match *instruction.operation() {
+                        Operation::Branch { ref target } |
+                        Operation::Call { ref target, .. } => target,
+                        _ => continue
+                    };
+                    let callee = match *target {
+                        Expression::Constant(ref constant) =>
+                            self.function_by_address(constant.value())
+                                .and_then(|function| function.index())
+                                .unwrap_or(CallGraph::unknown_index()),
+                        _ => CallGraph::unknown_index()
+                    };
+                    call_graph.add_call(*index, callee)?;
+                }
+            }
+        }
+
+        Ok(call_graph)
+    }
+
+
+    /// Compute a `FunctionSummary` of the registers read and written by each
+    /// `Function` in this `Program`, by index.
+    ///
+    /// See `analysis::summaries::compute_summaries`.
+    pub fn compute_summaries(&self, calling_convention: &CallingConvention)
+    -> BTreeMap<u64, FunctionSummary> {
+        summaries::compute_summaries(self, calling_convention)
+    }
+
+
+    /// Serialize this `Program` to a JSON document.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+
+    /// Deserialize a `Program` from a JSON document created by
+    /// `Program::to_json`.
+    pub fn from_json(json: &str) -> Result<Program> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+
     /// Add a `Function` to the `Program`.
     ///
     /// This will also assign an index to the `Function`.
@@ -91,4 +262,176 @@ impl fmt::Display for Program {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn json() {
+    let mut program = Program::new();
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    {
+        let block = control_flow_graph.new_block().unwrap();
+        block.assign(scalar("a", 32), expr_const(1, 32));
+        let instruction = block.instruction_mut(0).unwrap();
+        instruction.set_comment(Some("set a".to_string()));
+        instruction.set_address(Some(0x1000));
+    }
+    control_flow_graph.set_entry(0).unwrap();
+    let mut function = Function::new(0x1000, control_flow_graph);
+    function.set_name(Some("first".to_string()));
+    program.add_function(function);
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().branch(expr_scalar("ra", 32));
+    program.add_function(Function::new(0x2000, control_flow_graph));
+
+    let reloaded = Program::from_json(&program.to_json().unwrap()).unwrap();
+    assert_eq!(reloaded, program);
+
+    let function = reloaded.function(0).unwrap();
+    assert_eq!(function.name(), "first");
+    assert_eq!(function.address(), 0x1000);
+    let instruction = function.block(0).unwrap().instruction(0).unwrap();
+    assert_eq!(instruction.comment(), &Some("set a".to_string()));
+    assert_eq!(instruction.address(), Some(0x1000));
+    assert_eq!(reloaded.function(1).unwrap().address(), 0x2000);
+
+    assert!(Program::from_json("{").is_err());
+}
+
+
+#[test]
+fn function_by_address() {
+    let mut program = Program::new();
+    program.add_function(Function::new(0x1000, ControlFlowGraph::new()));
+    program.add_function(Function::new(0x2000, ControlFlowGraph::new()));
+
+    assert_eq!(program.function_by_address(0x1000).unwrap().index(), Some(0));
+    assert_eq!(program.function_by_address(0x2000).unwrap().index(), Some(1));
+    assert!(program.function_by_address(0x3000).is_none());
+
+    program.function_by_address_mut(0x2000)
+           .unwrap()
+           .set_name(Some("second".to_string()));
+    assert_eq!(program.function(1).unwrap().name(), "second");
+    assert!(program.function_by_address_mut(0x3000).is_none());
+}
+
+
+#[test]
+fn call_graph() {
+    let mut program = Program::new();
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().branch(expr_const(0x2000, 32));
+    program.add_function(Function::new(0x1000, control_flow_graph));
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().branch(expr_scalar("eax", 32));
+    program.add_function(Function::new(0x2000, control_flow_graph));
+
+    let call_graph = program.call_graph().unwrap();
+
+    assert_eq!(call_graph.callees(0).unwrap(), vec![1]);
+    assert_eq!(call_graph.callees(1).unwrap(), vec![CallGraph::unknown_index()]);
+    assert_eq!(call_graph.callers(1).unwrap(), vec![0]);
+    assert_eq!(call_graph.callers(CallGraph::unknown_index()).unwrap(), vec![1]);
+    assert!(call_graph.callees(2).is_err());
+}
+
+
+#[test]
+fn instruction_at() {
+    let mut program = Program::new();
+    for _ in 0..2 {
+        let mut control_flow_graph = ControlFlowGraph::new();
+        control_flow_graph.new_block().unwrap().assign(scalar("a", 32), expr_const(1, 32));
+        program.add_function(Function::new(0, control_flow_graph));
+    }
+
+    let function = program.function(1).unwrap();
+    let location = function.instruction_locations().into_iter().next().unwrap();
+    let instruction = location.instruction().unwrap();
+    let location: ProgramLocation = location.clone().into();
+    assert_eq!(program.instruction_at(&location), Some(instruction));
+    assert!(::std::ptr::eq(program.instruction_at(&location).unwrap(), instruction));
+
+    let stale = ProgramLocation::new(Some(2), FunctionLocation::Instruction(0, 0));
+    assert!(program.instruction_at(&stale).is_none());
+    let stale = ProgramLocation::new(None, FunctionLocation::Instruction(0, 0));
+    assert!(program.instruction_at(&stale).is_none());
+}
+
+
+#[test]
+fn symbols() {
+    let mut program = Program::new();
+
+    let mut control_flow_graph = ControlFlowGraph::new();
+    control_flow_graph.new_block().unwrap().call(expr_const(0x2000, 32), None);
+    program.add_function(Function::new(0x1000, control_flow_graph));
+    program.add_function(Function::new(0x2000, ControlFlowGraph::new()));
+
+    program.add_symbol("memcpy", 0x2000);
+
+    let instruction = program.function(0).unwrap().block(0).unwrap().instruction(0).unwrap();
+    let target = match *instruction.operation() {
+        Operation::Call { ref target, .. } => target.as_constant().unwrap().value(),
+        _ => panic!("expected a call")
+    };
+    assert_eq!(program.resolve_address(target), Some("memcpy"));
+    assert_eq!(program.resolve_name("memcpy"), Some(0x2000));
+    assert_eq!(program.function_by_name("memcpy").unwrap().index(), Some(1));
+    assert!(program.function_by_name("memset").is_none());
+
+    let reloaded = Program::from_json(&program.to_json().unwrap()).unwrap();
+    assert_eq!(reloaded.symbol_table(), program.symbol_table());
+}
+
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_map_functions() {
+    let mut program = Program::new();
+    for i in 0..64 {
+        let mut control_flow_graph = ControlFlowGraph::new();
+        {
+            let block = control_flow_graph.new_block().unwrap();
+            block.assign(scalar("a", 32),
+                         Expression::sub(expr_scalar("b", 32), expr_const(i, 32)).unwrap());
+        }
+        control_flow_graph.set_entry(0).unwrap();
+        let mut function = Function::new(i * 0x100, control_flow_graph);
+        function.set_name(Some(format!("function_{}", i)));
+        program.add_function(function);
+    }
+    program.add_function(Function::new(0x10000, ControlFlowGraph::new()));
+
+    let simplify = |function: &Function| -> Result<Function> {
+        let mut function = function.clone();
+        function.map_expressions(|expression| Ok(expression.normalize_additive()))?;
+        Ok(function)
+    };
+
+    let mut sequential = Program::new();
+    for function in program.functions() {
+        sequential.add_function(simplify(function).unwrap());
+    }
+
+    let mut parallel = program.clone();
+    parallel.par_map_functions(&simplify).unwrap();
+    assert_eq!(parallel, sequential);
+    for (index, function) in parallel.functions_map() {
+        assert_eq!(function.index(), Some(*index));
+    }
+    assert!(parallel.function(0).unwrap() != program.function(0).unwrap());
+
+    let fail = |function: &Function| -> Result<Function> {
+        if function.index() == Some(3) || function.index() == Some(40) {
+            bail!("function {:?} failed", function.index());
+        }
+        Ok(function.clone())
+    };
+    let error = parallel.clone().par_map_functions(fail).unwrap_err();
+    assert_eq!(error.to_string(), "function Some(3) failed");
+}
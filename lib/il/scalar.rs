@@ -1,11 +1,18 @@
 //! A `Scalar` is a variable which holds a single value.
 
 use std::fmt;
+use std::sync::Arc;
 use il::*;
 
+/// The name of a `Scalar` is shared between clones, so cloning a `Scalar` (and
+/// the `Expression` which holds it) does not allocate. Comparison, hashing and
+/// serialization are all by the value of the name.
+///
+/// This is an `Arc` regardless of `thread_safe`, as graph vertices and edges
+/// must be `Send` and `Sync`.
 #[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct Scalar {
-    name: String,
+    name: Arc<str>,
     bits: usize
 }
 
@@ -15,7 +22,7 @@ impl Scalar {
     /// Create a new `Scalar` with the given name and bitness.
     pub fn new<S>(name: S, bits: usize) -> Scalar where S: Into<String> {
         Scalar {
-            name: name.into(),
+            name: Arc::from(name.into()),
             bits: bits
         }
     }
@@ -49,4 +56,31 @@ impl Into<Expression> for Scalar {
     fn into(self) -> Expression {
         Expression::scalar(self)
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn shared_name() {
+    let a = Scalar::new("eax", 32);
+    let b = Scalar::new(String::from("eax"), 32);
+    assert_eq!(a, b);
+    assert_eq!(a.name(), "eax");
+    assert!(a != Scalar::new("eax", 16));
+    assert!(Arc::ptr_eq(&a.name, &a.clone().name));
+
+    let json = ::serde_json::to_string(&a).unwrap();
+    assert_eq!(json, "{\"name\":\"eax\",\"bits\":32}");
+    assert_eq!(::serde_json::from_str::<Scalar>(&json).unwrap(), a);
+}
+
+
+#[test]
+fn clone_deep_expression() {
+    let mut expression = expr_scalar("a", 32);
+    for i in 0..256 {
+        expression = Expression::add(expression, expr_scalar(format!("t{}", i), 32)).unwrap();
+    }
+
+    let clones = (0..1024).map(|_| expression.clone()).collect::<Vec<Expression>>();
+    assert!(clones.iter().all(|clone| *clone == expression));
+    assert_eq!(clones[0].scalars().len(), 257);
+}
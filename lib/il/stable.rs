@@ -0,0 +1,263 @@
+//! Labels for the blocks of a `ControlFlowGraph` which depend only on its
+//! structure and content, and a JSON document keyed by these labels.
+//!
+//! To create a document, call `ControlFlowGraph::serialize_stable`, and to
+//! read one, `ControlFlowGraph::deserialize_stable`.
+
+use il::*;
+use serde_json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+
+/// A 64-bit FNV-1a `Hasher`.
+///
+/// Unlike the standard library's default `Hasher`, this is not seeded, and
+/// integers are hashed as little-endian 64-bit values, so hashes are the same
+/// across runs and platforms.
+pub(crate) struct FnvHasher(u64);
+
+
+impl FnvHasher {
+    pub(crate) fn new() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) { self.write_u64(u64::from(i)) }
+    fn write_u16(&mut self, i: u16) { self.write_u64(u64::from(i)) }
+    fn write_u32(&mut self, i: u32) { self.write_u64(u64::from(i)) }
+    fn write_u64(&mut self, i: u64) { self.write(&i.to_le_bytes()) }
+    fn write_usize(&mut self, i: usize) { self.write_u64(i as u64) }
+    fn write_i8(&mut self, i: i8) { self.write_u64(i as u64) }
+    fn write_i16(&mut self, i: i16) { self.write_u64(i as u64) }
+    fn write_i32(&mut self, i: i32) { self.write_u64(i as u64) }
+    fn write_i64(&mut self, i: i64) { self.write_u64(i as u64) }
+    fn write_isize(&mut self, i: isize) { self.write_u64(i as u64) }
+}
+
+
+/// Hash a value with `FnvHasher`.
+pub(crate) fn stable_hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = FnvHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+
+/// Compute a label for every block of a `ControlFlowGraph`, by index.
+///
+/// A block's label is first the hash of its operations and whether it is the
+/// entry or exit. Labels are then refined, as in the Weisfeiler-Lehman test,
+/// by hashing each label with the sorted labels and edge conditions of the
+/// block's successors and predecessors, until refining distinguishes no more
+/// blocks. Labels depend on neither block indices, instruction indices,
+/// addresses nor comments.
+pub(crate) fn block_labels(cfg: &ControlFlowGraph) -> Result<BTreeMap<u64, u64>> {
+    let mut labels: BTreeMap<u64, u64> = BTreeMap::new();
+    for block in cfg.blocks() {
+        let operations = block.instructions()
+                              .iter()
+                              .map(|instruction| instruction.operation())
+                              .collect::<Vec<&Operation>>();
+        let is_entry = cfg.entry() == Some(block.index());
+        let is_exit = cfg.exit() == Some(block.index());
+        labels.insert(block.index(), stable_hash(&(operations, is_entry, is_exit)));
+    }
+
+    let distinct = |labels: &BTreeMap<u64, u64>| {
+        labels.values().collect::<BTreeSet<&u64>>().len()
+    };
+
+    loop {
+        let mut refined = BTreeMap::new();
+        for block in cfg.blocks() {
+            let index = block.index();
+            let mut successors = cfg.edges_out(index)
+                                    .ok_or("Could not find block")?
+                                    .iter()
+                                    .map(|edge| (labels[&edge.tail()], edge.condition()))
+                                    .collect::<Vec<(u64, &Option<Expression>)>>();
+            successors.sort();
+            let mut predecessors = cfg.edges_in(index)
+                                      .ok_or("Could not find block")?
+                                      .iter()
+                                      .map(|edge| (labels[&edge.head()], edge.condition()))
+                                      .collect::<Vec<(u64, &Option<Expression>)>>();
+            predecessors.sort();
+            refined.insert(index, stable_hash(&(labels[&index], successors, predecessors)));
+        }
+
+        let done = distinct(&refined) == distinct(&labels);
+        labels = refined;
+        if done {
+            break;
+        }
+    }
+
+    Ok(labels)
+}
+
+
+#[derive(Deserialize, Serialize)]
+struct StableInstruction {
+    operation: Operation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<u64>
+}
+
+
+#[derive(Deserialize, Serialize)]
+struct StableBlock {
+    label: String,
+    instructions: Vec<StableInstruction>
+}
+
+
+#[derive(Deserialize, Serialize)]
+struct StableEdge {
+    head: String,
+    tail: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    condition: Option<Expression>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>
+}
+
+
+#[derive(Deserialize, Serialize)]
+struct StableControlFlowGraph {
+    entry: Option<String>,
+    exit: Option<String>,
+    blocks: Vec<StableBlock>,
+    edges: Vec<StableEdge>
+}
+
+
+/// Serialize a `ControlFlowGraph` to a JSON document keyed by block labels.
+///
+/// Blocks are ordered by label, and edges by the labels they join. Blocks
+/// which share a label are told apart by a suffix, in block index order.
+pub(crate) fn serialize(cfg: &ControlFlowGraph) -> Result<String> {
+    let labels = block_labels(cfg)?;
+
+    let mut blocks_by_label: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    for (index, label) in &labels {
+        blocks_by_label.entry(*label).or_insert_with(Vec::new).push(*index);
+    }
+    let mut names: BTreeMap<u64, String> = BTreeMap::new();
+    for (label, indices) in &blocks_by_label {
+        for (n, index) in indices.iter().enumerate() {
+            let name = if indices.len() == 1 {
+                format!("{:016x}", label)
+            }
+            else {
+                format!("{:016x}.{}", label, n)
+            };
+            names.insert(*index, name);
+        }
+    }
+
+    let mut blocks = Vec::new();
+    for indices in blocks_by_label.values() {
+        for index in indices {
+            let block = cfg.block(*index).ok_or("Could not find block")?;
+            let instructions = block.instructions()
+                                    .iter()
+                                    .map(|instruction| StableInstruction {
+                                        operation: instruction.operation().clone(),
+                                        comment: instruction.comment().clone(),
+                                        address: instruction.address()
+                                    })
+                                    .collect();
+            blocks.push(StableBlock {
+                label: names[index].clone(),
+                instructions: instructions
+            });
+        }
+    }
+
+    let mut edges = cfg.edges()
+                       .into_iter()
+                       .map(|edge| StableEdge {
+                           head: names[&edge.head()].clone(),
+                           tail: names[&edge.tail()].clone(),
+                           condition: edge.condition().clone(),
+                           comment: edge.comment().clone()
+                       })
+                       .collect::<Vec<StableEdge>>();
+    edges.sort_by(|lhs, rhs| (&lhs.head, &lhs.tail).cmp(&(&rhs.head, &rhs.tail)));
+
+    let document = StableControlFlowGraph {
+        entry: cfg.entry().map(|entry| names[&entry].clone()),
+        exit: cfg.exit().map(|exit| names[&exit].clone()),
+        blocks: blocks,
+        edges: edges
+    };
+
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+
+/// Deserialize a `ControlFlowGraph` from a document created by `serialize`.
+///
+/// Blocks are indexed from 0 in the order they appear in the document.
+pub(crate) fn deserialize(json: &str) -> Result<ControlFlowGraph> {
+    let document: StableControlFlowGraph = serde_json::from_str(json)?;
+
+    let mut cfg = ControlFlowGraph::new();
+    let mut indices: BTreeMap<String, u64> = BTreeMap::new();
+
+    for stable_block in document.blocks {
+        let block = cfg.new_block()?;
+        for stable_instruction in stable_block.instructions {
+            let mut instruction = Instruction::new(0, stable_instruction.operation);
+            instruction.set_comment(stable_instruction.comment);
+            instruction.set_address(stable_instruction.address);
+            block.append_instruction(instruction);
+        }
+        if indices.insert(stable_block.label.clone(), block.index()).is_some() {
+            bail!("Block {} appears twice", stable_block.label);
+        }
+    }
+
+    let index = |label: &str| -> Result<u64> {
+        indices.get(label)
+               .cloned()
+               .ok_or(format!("Could not find block {}", label).into())
+    };
+
+    for stable_edge in document.edges {
+        let head = index(&stable_edge.head)?;
+        let tail = index(&stable_edge.tail)?;
+        match stable_edge.condition {
+            Some(condition) => cfg.conditional_edge(head, tail, condition)?,
+            None => cfg.unconditional_edge(head, tail)?
+        }
+        cfg.edge_mut(head, tail).unwrap().set_comment(stable_edge.comment);
+    }
+
+    if let Some(entry) = document.entry {
+        cfg.set_entry(index(&entry)?)?;
+    }
+    if let Some(exit) = document.exit {
+        cfg.set_exit(index(&exit)?)?;
+    }
+
+    Ok(cfg)
+}
@@ -0,0 +1,73 @@
+//! A `SymbolTable` maps names to addresses in a `Program`.
+
+use std::collections::BTreeMap;
+
+
+/// A mapping between symbol names and the addresses they refer to.
+///
+/// Each name refers to one address, and each address has one name. Adding a
+/// symbol replaces any symbol with the same name or address.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SymbolTable {
+    names: BTreeMap<String, u64>,
+    addresses: BTreeMap<u64, String>
+}
+
+
+impl SymbolTable {
+    /// Create a new, empty `SymbolTable`.
+    pub fn new() -> SymbolTable {
+        SymbolTable {
+            names: BTreeMap::new(),
+            addresses: BTreeMap::new()
+        }
+    }
+
+    /// Add a symbol with the given name at the given address.
+    pub fn add_symbol<S>(&mut self, name: S, address: u64) where S: Into<String> {
+        let name = name.into();
+        if let Some(previous) = self.names.insert(name.clone(), address) {
+            self.addresses.remove(&previous);
+        }
+        if let Some(previous) = self.addresses.insert(address, name) {
+            self.names.remove(&previous);
+        }
+    }
+
+    /// Get the address of the symbol with the given name.
+    pub fn resolve_name(&self, name: &str) -> Option<u64> {
+        self.names.get(name).cloned()
+    }
+
+    /// Get the name of the symbol at the given address.
+    pub fn resolve_address(&self, address: u64) -> Option<&str> {
+        self.addresses.get(&address).map(|name| name.as_str())
+    }
+
+    /// Get every symbol, as a name and address, in order of address.
+    pub fn symbols(&self) -> Vec<(&str, u64)> {
+        self.addresses
+            .iter()
+            .map(|(address, name)| (name.as_str(), *address))
+            .collect()
+    }
+}
+
+
+#[test]
+fn symbol_table() {
+    let mut symbol_table = SymbolTable::new();
+    symbol_table.add_symbol("main", 0x1000);
+    symbol_table.add_symbol("exit", 0x2000);
+    assert_eq!(symbol_table.resolve_name("main"), Some(0x1000));
+    assert_eq!(symbol_table.resolve_address(0x2000), Some("exit"));
+    assert_eq!(symbol_table.resolve_name("start"), None);
+
+    symbol_table.add_symbol("start", 0x1000);
+    assert_eq!(symbol_table.resolve_name("main"), None);
+    assert_eq!(symbol_table.resolve_address(0x1000), Some("start"));
+
+    symbol_table.add_symbol("start", 0x3000);
+    assert_eq!(symbol_table.resolve_address(0x1000), None);
+    assert_eq!(symbol_table.symbols(), vec![("exit", 0x2000), ("start", 0x3000)]);
+}
@@ -44,6 +44,9 @@ extern crate falcon_capstone;
 extern crate goblin;
 #[macro_use]
 extern crate log;
+extern crate num_bigint;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -88,9 +91,9 @@ pub mod error {
         }
 
         errors {
-            Sort {
+            Sort(operator: String, lhs: usize, rhs: usize) {
                 description("Sort error, invalid bitness between expressions")
-                display("Sort error, invalid bitness between expressions")
+                display("sort error: {} operands of {} and {} bits", operator, lhs, rhs)
             }
             Arithmetic(m: String) {
                 description("Error in evaluation of arithmetic expression")
@@ -503,6 +503,27 @@ mod memory_tests {
     }
 
 
+    #[test]
+    fn little_endian_overlapping() {
+        let mut memory: Memory<il::Constant> = Memory::new(Endian::Little);
+
+        memory.store(0x100, il::const_(0x11223344, 32)).unwrap();
+        memory.store(0x102, il::const_(0xAABBCCDD, 32)).unwrap();
+
+        assert_eq!(memory.load(0x100, 8).unwrap().unwrap(), il::const_(0x44, 8));
+        assert_eq!(memory.load(0x101, 8).unwrap().unwrap(), il::const_(0x33, 8));
+        assert_eq!(memory.load(0x102, 8).unwrap().unwrap(), il::const_(0xDD, 8));
+        assert_eq!(memory.load(0x103, 8).unwrap().unwrap(), il::const_(0xCC, 8));
+        assert_eq!(memory.load(0x104, 8).unwrap().unwrap(), il::const_(0xBB, 8));
+        assert_eq!(memory.load(0x105, 8).unwrap().unwrap(), il::const_(0xAA, 8));
+
+        assert_eq!(
+            memory.load(0x100, 32).unwrap().unwrap(),
+            il::const_(0xCCDD3344, 32)
+        );
+    }
+
+
     #[test]
     fn backed() {
         let mut backing = memory::backing::Memory::new(Endian::Big);
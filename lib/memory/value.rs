@@ -50,11 +50,11 @@ impl Value for il::Constant {
     }
 
     fn trun(&self, bits: usize) -> Result<Self> {
-        eval(&il::Expression::trun(bits, self.clone().into())?)
+        il::Constant::trun(self, bits)
     }
 
     fn zext(&self, bits: usize) -> Result<Self> {
-        eval(&il::Expression::zext(bits, self.clone().into())?)
+        il::Constant::zext(self, bits)
     }
 
     fn or(&self, other: &Self) -> Result<Self> {
@@ -17,6 +17,14 @@
 //! If you are lifting directly from an Elf binary, you do not need to pay
 //! attention to the translators. The correct translator will be chosen
 //! automatically.
+//!
+//! # Adding an architecture
+//!
+//! A new architecture only needs to implement `Translator::translate_block`,
+//! returning one `ControlFlowGraph` per native instruction in a
+//! `BlockTranslationResult`. `Translator::translate_function` then splices
+//! these graphs together into a `Function`. `Architecture::translator` maps
+//! each supported `Architecture` to its boxed `Translator`.
 
 use error::*;
 use il::*;
@@ -253,4 +261,84 @@ pub trait Translator: {
 
         Ok(Function::new(function_address, control_flow_graph))
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod translator_tests {
+    use super::*;
+
+    /// Translates 0x00 to a nop, and 0xc3 to a raise which ends the block.
+    struct NopTranslator;
+
+    impl Translator for NopTranslator {
+        fn translate_block(&self, bytes: &[u8], address: u64) -> Result<BlockTranslationResult> {
+            let mut instructions = Vec::new();
+            let mut length = 0;
+
+            for byte in bytes {
+                let instruction_address = address + length as u64;
+                let mut control_flow_graph = ControlFlowGraph::new();
+                let block_index = {
+                    let block = control_flow_graph.new_block()?;
+                    match *byte {
+                        0x00 => block.nop(),
+                        0xc3 => block.raise(expr_scalar("return", 1)),
+                        _ => bail!("Invalid byte 0x{:x} at 0x{:x}", byte, instruction_address)
+                    }
+                    block.index()
+                };
+                control_flow_graph.set_entry(block_index)?;
+                control_flow_graph.set_exit(block_index)?;
+                instructions.push((instruction_address, control_flow_graph));
+                length += 1;
+                if *byte == 0xc3 {
+                    break;
+                }
+            }
+
+            Ok(BlockTranslationResult::new(instructions, address, length, Vec::new()))
+        }
+    }
+
+    struct TestMemory {
+        address: u64,
+        bytes: Vec<u8>
+    }
+
+    impl TranslationMemory for TestMemory {
+        fn permissions(&self, address: u64) -> Option<MemoryPermissions> {
+            if address >= self.address && address < self.address + self.bytes.len() as u64 {
+                Some(MemoryPermissions::READ | MemoryPermissions::EXECUTE)
+            }
+            else {
+                None
+            }
+        }
+
+        fn get_u8(&self, address: u64) -> Option<u8> {
+            if address < self.address {
+                return None;
+            }
+            self.bytes.get((address - self.address) as usize).cloned()
+        }
+    }
+
+    #[test]
+    fn translate_function() {
+        let memory = TestMemory {
+            address: 0x1000,
+            bytes: vec![0x00, 0x00, 0x00, 0xc3, 0x00]
+        };
+
+        let function = NopTranslator.translate_function(&memory, 0x1000).unwrap();
+        assert_eq!(function.address(), 0x1000);
+
+        let blocks = function.blocks();
+        assert_eq!(blocks.len(), 1);
+        let instructions = blocks[0].instructions();
+        assert_eq!(instructions.len(), 4);
+        assert!(instructions[..3].iter().all(|instruction| instruction.is_nop()));
+        assert!(instructions[3].is_raise());
+    }
+}
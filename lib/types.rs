@@ -2,15 +2,71 @@
 
 use analysis::calling_convention::{CallingConvention, CallingConventionType};
 use il;
+use std::ops;
 use translator;
 
 /// A boolean type with an unknown value
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum PartialBoolean {
     True,
     False,
     Unknown
 }
 
+
+impl PartialBoolean {
+    /// Three-valued logical and. `False` if either is `False`, otherwise
+    /// `Unknown` if either is `Unknown`.
+    pub fn and(self, other: PartialBoolean) -> PartialBoolean {
+        match (self, other) {
+            (PartialBoolean::False, _) |
+            (_, PartialBoolean::False) => PartialBoolean::False,
+            (PartialBoolean::True, PartialBoolean::True) => PartialBoolean::True,
+            _ => PartialBoolean::Unknown
+        }
+    }
+
+    /// Three-valued logical or. `True` if either is `True`, otherwise
+    /// `Unknown` if either is `Unknown`.
+    pub fn or(self, other: PartialBoolean) -> PartialBoolean {
+        match (self, other) {
+            (PartialBoolean::True, _) |
+            (_, PartialBoolean::True) => PartialBoolean::True,
+            (PartialBoolean::False, PartialBoolean::False) => PartialBoolean::False,
+            _ => PartialBoolean::Unknown
+        }
+    }
+
+    /// Returns true if this `PartialBoolean` is `True`.
+    pub fn is_true(&self) -> bool {
+        *self == PartialBoolean::True
+    }
+
+    /// Returns true if this `PartialBoolean` is `False`.
+    pub fn is_false(&self) -> bool {
+        *self == PartialBoolean::False
+    }
+
+    /// Returns true if this `PartialBoolean` is `Unknown`.
+    pub fn is_unknown(&self) -> bool {
+        *self == PartialBoolean::Unknown
+    }
+}
+
+
+impl ops::Not for PartialBoolean {
+    type Output = PartialBoolean;
+
+    /// Three-valued logical not. `Unknown` stays `Unknown`.
+    fn not(self) -> PartialBoolean {
+        match self {
+            PartialBoolean::True => PartialBoolean::False,
+            PartialBoolean::False => PartialBoolean::True,
+            PartialBoolean::Unknown => PartialBoolean::Unknown
+        }
+    }
+}
+
 /// The underlying endianness of this memory model.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Endian {
@@ -49,14 +105,45 @@ impl Architecture {
     /// Get the default calling convention for this Architecture
     pub fn calling_convention(&self) -> CallingConvention {
         match *self {
-            Architecture::Mips |
-            Architecture::Mipsel =>
+            Architecture::Mips =>
                 CallingConvention::new(CallingConventionType::MipsSystemV),
+            Architecture::Mipsel =>
+                CallingConvention::new(CallingConventionType::MipselSystemV),
             Architecture::X86 =>
                 CallingConvention::new(CallingConventionType::Cdecl),
         }
     }
 
+    /// Get the general purpose registers of this architecture, as named by
+    /// its translator.
+    pub fn registers(&self) -> Vec<il::Scalar> {
+        let names: &[&str] = match *self {
+            Architecture::Mips |
+            Architecture::Mipsel => &[
+                "$zero", "$at", "$v0", "$v1", "$a0", "$a1", "$a2", "$a3",
+                "$t0", "$t1", "$t2", "$t3", "$t4", "$t5", "$t6", "$t7",
+                "$s0", "$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7",
+                "$t8", "$t9", "$k0", "$k1", "$gp", "$sp", "$fp", "$ra",
+                "$hi", "$lo"
+            ],
+            Architecture::X86 => &[
+                "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi"
+            ]
+        };
+        names.iter()
+             .map(|name| il::scalar(*name, self.word_size()))
+             .collect()
+    }
+
+    /// Get the size of a word of this architecture in bits.
+    pub fn word_size(&self) -> usize {
+        match *self {
+            Architecture::X86 |
+            Architecture::Mips |
+            Architecture::Mipsel => 32
+        }
+    }
+
     /// Get the stack pointer for this architecture
     pub fn stack_pointer(&self) -> il::Scalar {
         match *self {
@@ -93,4 +180,52 @@ fn test_mipsel() {
     assert_eq!(arch.stack_pointer(), il::scalar("$sp", 32));
     assert_eq!(*arch.calling_convention()
                     .return_register(), il::scalar("$v0", 32));
+    assert_eq!(arch.calling_convention().return_value_64(),
+               Some((il::scalar("$v1", 32), il::scalar("$v0", 32))));
+    assert_eq!(Architecture::Mips.calling_convention().return_value_64(),
+               Some((il::scalar("$v0", 32), il::scalar("$v1", 32))));
+}
+
+#[test]
+fn registers() {
+    for arch in &[Architecture::X86, Architecture::Mips, Architecture::Mipsel] {
+        let registers = arch.registers();
+        assert_eq!(arch.word_size(), 32);
+        assert!(registers.contains(&arch.stack_pointer()));
+        assert!(registers.contains(arch.calling_convention().return_register()));
+        assert!(registers.iter().all(|register| register.bits() == arch.word_size()));
+    }
+    assert_eq!(Architecture::X86.registers().len(), 8);
+    assert_eq!(Architecture::Mips.registers(), Architecture::Mipsel.registers());
+}
+
+
+#[test]
+fn partial_boolean() {
+    use self::PartialBoolean::*;
+
+    let truth_table = [
+        (True,    True,    True,    True),
+        (True,    False,   False,   True),
+        (True,    Unknown, Unknown, True),
+        (False,   True,    False,   True),
+        (False,   False,   False,   False),
+        (False,   Unknown, False,   Unknown),
+        (Unknown, True,    Unknown, True),
+        (Unknown, False,   False,   Unknown),
+        (Unknown, Unknown, Unknown, Unknown),
+    ];
+
+    for &(lhs, rhs, and, or) in truth_table.iter() {
+        assert_eq!(lhs.and(rhs), and);
+        assert_eq!(lhs.or(rhs), or);
+    }
+
+    assert_eq!(!True, False);
+    assert_eq!(!False, True);
+    assert_eq!(!Unknown, Unknown);
+
+    assert!(True.is_true() && !True.is_false() && !True.is_unknown());
+    assert!(False.is_false());
+    assert!(Unknown.is_unknown());
 }